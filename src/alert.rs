@@ -0,0 +1,149 @@
+use regex::Regex;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// more than `count` lines matching `pattern` within `window`
+#[derive(Clone)]
+pub struct AlertRule {
+    pattern: Regex,
+    count: usize,
+    window: Duration,
+    rate: String, // as given, for the banner
+}
+
+/// `REGEX:N/PERIOD`, e.g. `ERROR:10/30s`
+pub fn parse(raw: &str) -> Result<AlertRule, String> {
+    let (pattern, rate) = raw
+        .rsplit_once(':')
+        .ok_or(format!("missing `:N/PERIOD` in `{raw}`"))?;
+    let (count, window) = rate
+        .split_once('/')
+        .ok_or(format!("missing `/PERIOD` in `{raw}`"))?;
+    let count = count
+        .parse::<usize>()
+        .map_err(|_| format!("N must be a whole number in `{raw}`"))?;
+    let window = crate::args::parse_duration(window)?;
+    if window.is_zero() {
+        return Err(format!("the period cannot be zero in `{raw}`"));
+    }
+    Ok(AlertRule {
+        pattern: Regex::new(pattern).map_err(|err| err.to_string())?,
+        count,
+        window,
+        rate: rate.to_string(),
+    })
+}
+
+impl AlertRule {
+    pub fn matches(&self, text: &str) -> bool {
+        self.pattern.is_match(text)
+    }
+}
+
+/// the matching lines still in the window of a rule
+pub struct RateWindow {
+    rule: AlertRule,
+    times: VecDeque<Instant>, // of the matching lines, the oldest first
+    over: bool,               // the threshold was exceeded at the last check
+}
+
+impl RateWindow {
+    pub fn new(rule: AlertRule) -> RateWindow {
+        RateWindow {
+            rule,
+            times: VecDeque::new(),
+            over: false,
+        }
+    }
+
+    /// a line received, matched against the rule by the caller
+    pub fn record(&mut self, matched: bool, received: Instant) {
+        if !matched {
+            return;
+        }
+        self.times.push_back(received);
+        // the oldest times expire first, the ones past the threshold never change the outcome
+        if self.times.len() > self.rule.count + 1 {
+            self.times.pop_front();
+        }
+    }
+
+    /// true when the threshold was just exceeded, the rule fires again only once the rate
+    /// fell back under it
+    pub fn fired(&mut self, now: Instant) -> bool {
+        while self
+            .times
+            .front()
+            .is_some_and(|time| now.duration_since(*time) > self.rule.window)
+        {
+            self.times.pop_front();
+        }
+        let over = self.times.len() > self.rule.count;
+        let fired = over && !self.over;
+        self.over = over;
+        fired
+    }
+
+    pub fn describe(&self) -> String {
+        format!("alert: {} over {}", self.rule.pattern, self.rule.rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules() {
+        let rule = parse("ERROR:10/30s").unwrap();
+        assert_eq!(rule.pattern.as_str(), "ERROR");
+        assert_eq!((rule.count, rule.window), (10, Duration::from_secs(30)));
+        // the rate is after the last `:`, the pattern can hold others
+        let rule = parse(r"level=(error|fatal):\d+:0/1m").unwrap();
+        assert_eq!(rule.pattern.as_str(), r"level=(error|fatal):\d+");
+        assert_eq!((rule.count, rule.window), (0, Duration::from_secs(60)));
+        assert_eq!(rule.rate, "0/1m");
+    }
+
+    #[test]
+    fn broken_rules() {
+        for raw in [
+            "ERROR",
+            "ERROR:10",
+            "ERROR:ten/30s",
+            "ERROR:-1/30s",
+            "ERROR:10/",
+            "ERROR:10/30x",
+            "ERROR:10/0s",
+            "ERROR:10/0ms",
+            "(:10/30s",
+        ] {
+            assert!(parse(raw).is_err(), "{raw}");
+        }
+    }
+
+    #[test]
+    fn fires_once_then_rearms() {
+        let mut window = RateWindow::new(parse("ERROR:2/10s").unwrap());
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        for secs in [0, 1] {
+            window.record(true, at(secs));
+            window.record(false, at(secs));
+        }
+        assert!(!window.fired(at(1)));
+        window.record(true, at(2));
+        assert!(window.fired(at(2)));
+        // still over the threshold, it does not fire again
+        window.record(true, at(3));
+        assert!(!window.fired(at(3)));
+        // the first ones expired, the rate fell back under the threshold
+        assert!(!window.fired(at(12)));
+        window.record(true, at(13));
+        assert!(!window.fired(at(13)));
+        window.record(true, at(13));
+        assert!(window.fired(at(13)));
+    }
+}
@@ -0,0 +1,947 @@
+use crate::{
+    alert::{self, AlertRule},
+    clock::Corner,
+    control,
+    effects::Easing,
+    exec::{self, ExecRule},
+    rng::JitterProfile,
+    scoring::ScorerKind,
+    sources::{self, InputLine, QueuePolicy},
+    text::{Glyphs, Redaction},
+};
+use clap::{FromArgMatches, ValueEnum};
+use glob::Pattern;
+use regex::Regex;
+use std::{path::PathBuf, time::Duration};
+use unicode_width::UnicodeWidthChar;
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)] // ArgEnum here
+#[clap(rename_all = "kebab_case")]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    Default,
+}
+
+impl Color {
+    // the one the color keys switch to, black is skipped for the dark terminals
+    pub(crate) fn next(self) -> Color {
+        match self {
+            Color::Black | Color::Default => Color::Red,
+            Color::Red => Color::Green,
+            Color::Green => Color::Yellow,
+            Color::Yellow => Color::Blue,
+            Color::Blue => Color::Magenta,
+            Color::Magenta => Color::Cyan,
+            Color::Cyan => Color::White,
+            Color::White => Color::Default,
+        }
+    }
+
+    pub(crate) fn to_ansi(self) -> &'static str {
+        match self {
+            Color::Default => "\x1b[0;0m",
+            Color::Black => "\x1b[0;30m",
+            Color::Red => "\x1b[0;31m",
+            Color::Cyan => "\x1b[0;36m",
+            Color::Magenta => "\x1b[0;35m",
+            Color::Yellow => "\x1b[0;33m",
+            Color::Blue => "\x1b[0;34m",
+            Color::White => "\x1b[0;37m",
+            Color::Green => "\x1b[0;32m",
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone)] // ArgEnum here
+#[clap(rename_all = "kebab_case")]
+pub enum Direction {
+    Top,
+    Bottom,
+    SpiralRight,
+    Left,
+    Right,
+}
+
+impl Direction {
+    // the one the direction key switches to
+    pub(crate) fn next(&self) -> Direction {
+        match self {
+            Direction::Top => Direction::Bottom,
+            Direction::Bottom => Direction::SpiralRight,
+            Direction::SpiralRight => Direction::Left,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Top,
+        }
+    }
+
+    // the lines go along the rows of the screen rather than its columns
+    pub(crate) fn horizontal(&self) -> bool {
+        matches!(self, Direction::Left | Direction::Right)
+    }
+}
+
+/// what becomes of a line given to a column already holding --column-cache lines
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[clap(rename_all = "kebab_case")]
+pub(crate) enum ColumnSpill {
+    /// the oldest line waiting in the column is dropped
+    DropOldest,
+    /// the line is dropped and counted on the newest line waiting, e.g. `text [+3]`
+    Merge,
+    /// the line goes to the column with the fewest lines waiting, the oldest one is dropped
+    /// when they are all full
+    Reassign,
+}
+
+/// where the status bar is drawn, off the matrix
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+#[clap(rename_all = "kebab_case")]
+pub(crate) enum StatusBar {
+    Top,
+    Bottom,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[clap(rename_all = "kebab_case")]
+pub(crate) enum SnapshotFormat {
+    Ansi,
+    Text,
+    Html,
+}
+
+impl SnapshotFormat {
+    // the one `FrameBuffer::export` picks the format after
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            SnapshotFormat::Ansi => "ans",
+            SnapshotFormat::Text => "txt",
+            SnapshotFormat::Html => "html",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum SpiralTiles {
+    Auto,
+    Count(u16),
+}
+
+fn parse_spiral_tiles(raw: &str) -> Result<SpiralTiles, String> {
+    match raw {
+        "auto" => Ok(SpiralTiles::Auto),
+        _ => raw
+            .parse::<u16>()
+            .ok()
+            .filter(|count| *count > 0)
+            .map(SpiralTiles::Count)
+            .ok_or(format!("expected `auto` or a positive count, got `{raw}`")),
+    }
+}
+
+#[derive(clap::Args)]
+pub struct ServeArgs {
+    #[clap(long, default_value_t = 2323)]
+    /// TCP port to listen on
+    pub(crate) port: u16,
+    #[clap(long, default_value = "0.0.0.0")]
+    /// address to listen on
+    pub(crate) bind: String,
+    #[command(flatten)]
+    pub(crate) args: Args,
+}
+
+#[derive(clap::Args)]
+pub struct RecordArgs {
+    #[clap(short, long, value_name = "FILE")]
+    /// the cast file, played with `asciinema play` or published as is
+    pub(crate) output: PathBuf,
+    #[command(flatten)]
+    pub(crate) args: Args,
+}
+
+#[derive(clap::Args)]
+pub struct ReplayArgs {
+    /// cast file written by `record`, or log whose lines start with their timestamp
+    pub(crate) file: PathBuf,
+    #[clap(long, default_value_t = 1., value_parser = parse_speed)]
+    /// speed multiplier, 2 plays twice as fast. space pauses and resumes
+    pub(crate) speed: f64,
+    #[command(flatten)]
+    pub(crate) args: Args,
+}
+
+#[derive(clap::Args)]
+#[command(group(clap::ArgGroup::new("clips").required(true).multiple(true).args(["gif", "svg"])))]
+pub struct RenderArgs {
+    #[clap(long, value_name = "FILE")]
+    /// the GIF file, looping forever
+    pub(crate) gif: Option<PathBuf>,
+    #[clap(long, value_name = "FILE")]
+    /// the animated SVG file, looping forever and scaling losslessly
+    pub(crate) svg: Option<PathBuf>,
+    #[clap(long, default_value = "10s", value_parser = parse_duration)]
+    /// length of the clip, drawn in as much time
+    pub(crate) duration: Duration,
+    #[command(flatten)]
+    pub(crate) args: Args,
+}
+
+#[cfg(feature = "ssh")]
+#[derive(clap::Args)]
+pub struct SshArgs {
+    #[clap(long, default_value_t = 2222)]
+    /// TCP port to listen on
+    pub(crate) port: u16,
+    #[clap(long, default_value = "0.0.0.0")]
+    /// address to listen on
+    pub(crate) bind: String,
+    #[clap(long, default_value = "logs")]
+    /// user name the clients log in with, without a password
+    pub(crate) user: String,
+    #[clap(long)]
+    /// ed25519 host key, created when missing. a new one is drawn at every start otherwise
+    pub(crate) host_key: Option<PathBuf>,
+    #[command(flatten)]
+    pub(crate) args: Args,
+}
+
+#[derive(clap::Args)]
+#[command(group(clap::ArgGroup::new("payload").required(true).args(["line", "sticky"])))]
+pub struct SendArgs {
+    /// line to inject in the rain
+    pub(crate) line: Option<String>,
+    #[clap(long)]
+    /// pin an annotation banner on top of the display
+    pub(crate) sticky: Option<String>,
+    #[clap(long, default_value = "60s", value_parser = parse_duration)]
+    /// how long the sticky annotation stays on screen (e.g. 500ms, 30s, 10m, 1h)
+    pub(crate) duration: Duration,
+    #[clap(long, default_value_os_t = control::default_socket().to_path_buf())]
+    /// control socket of the running instance
+    pub(crate) socket: PathBuf,
+}
+
+#[derive(clap::Args, Clone)]
+#[command(group(clap::ArgGroup::new("snapshot").args(["snapshot_out", "snapshot_dir"])))]
+pub struct Args {
+    /// files to read then follow like `tail -f`, `-` for stdin which is read when no file is given
+    pub(crate) files: Vec<PathBuf>,
+    #[clap(long = "files", value_name = "GLOB", value_parser = Pattern::new)]
+    /// follow the files matching GLOB too, e.g. `--files 'logs/*.log'`, repeatable
+    pub(crate) file_globs: Vec<Pattern>,
+    #[clap(long, value_name = "PERIOD", value_parser = parse_duration, requires = "file_globs")]
+    /// look for new files matching the --files globs every PERIOD
+    pub(crate) rescan: Option<Duration>,
+    #[clap(long, value_name = "UNIT")]
+    /// follow the systemd journal, of a single UNIT when given, the lines are colored
+    /// after their priority
+    pub(crate) journal: Option<Option<String>>,
+    #[clap(long, value_name = "CONTAINER")]
+    /// stream the logs of CONTAINER from the Docker socket, repeatable, the socket of
+    /// DOCKER_HOST is used when it is a `unix://` one
+    pub(crate) docker: Vec<String>,
+    #[clap(long)]
+    /// stream the logs of every running container
+    pub(crate) docker_all: bool,
+    #[clap(long, value_name = "PROTO://HOST:PORT", value_parser = sources::parse_syslog_listen)]
+    /// receive syslog messages on `udp://HOST:PORT` or `tcp://HOST:PORT`, repeatable, the
+    /// lines are named after the sending host and colored after their severity
+    pub(crate) listen_syslog: Vec<sources::SyslogListen>,
+    #[clap(long, value_name = "PROTO://ADDRESS", value_parser = sources::parse_listen)]
+    /// receive lines of text on `tcp://HOST:PORT` or `unix:///PATH`, repeatable. every TCP
+    /// client is a source of its own, e.g. `tail -f app.log | nc HOST PORT` from an other
+    /// machine, the lines written to a unix socket are named after it
+    pub(crate) listen: Vec<sources::Listen>,
+    #[clap(long, value_name = "HOST:PORT")]
+    /// receive GELF messages over UDP, repeatable, the short message is displayed and
+    /// colored after its level
+    pub(crate) listen_gelf: Vec<String>,
+    #[clap(long, value_name = "HOST:PORT")]
+    /// receive the logs exported with OTLP over HTTP, JSON or protobuf encoded, on
+    /// `http://HOST:PORT/v1/logs`, repeatable. the lines are named after their service and
+    /// colored after their severity
+    pub(crate) listen_otlp: Vec<String>,
+    #[clap(long, value_name = "HOST:PORT")]
+    /// receive the events of Fluentd and Fluent Bit `forward` outputs, repeatable, the lines
+    /// are named after their tag and colored after their `level`
+    pub(crate) listen_fluent: Vec<String>,
+    #[cfg(feature = "kube")]
+    #[clap(long, value_name = "NAMESPACE/POD[:CONTAINER]", value_parser = sources::parse_kube_target)]
+    /// stream the logs of a pod, or of the pods matching a label selector given as
+    /// `NAMESPACE/LABEL=VALUE,..`, repeatable. the pods restarting are followed again
+    pub(crate) kube: Vec<sources::KubeTarget>,
+    #[cfg(feature = "kube")]
+    #[clap(long, value_name = "URL")]
+    /// Kubernetes API server, defaults to the cluster of the pod logmatrix runs in, else to
+    /// the one of `kubectl proxy`
+    pub(crate) kube_api: Option<String>,
+    #[cfg(feature = "cloudwatch")]
+    #[clap(long, value_name = "GROUP[:STREAM]", value_parser = sources::parse_log_group)]
+    /// poll the new events of an AWS CloudWatch Logs group, of a single stream when given,
+    /// repeatable. the lines are named after their stream, the credentials and the region
+    /// are the ones of the AWS environment variables or of `~/.aws`. a poll which fails is
+    /// counted as dropped and tried again later, up to a minute apart
+    pub(crate) cloudwatch: Vec<sources::LogGroup>,
+    #[cfg(feature = "cloudwatch")]
+    #[clap(long, value_name = "REGION", requires = "cloudwatch")]
+    /// AWS region of the --cloudwatch groups, e.g. `eu-west-1`
+    pub(crate) aws_region: Option<String>,
+    #[cfg(feature = "kafka")]
+    #[clap(long, value_name = "HOST:PORT", requires = "topic")]
+    /// consume the messages of the --topic from these Kafka brokers, repeatable
+    pub(crate) kafka: Vec<String>,
+    #[cfg(any(feature = "kafka", feature = "mqtt"))]
+    #[clap(long, value_name = "TOPIC")]
+    /// topic to consume with --kafka or to subscribe to with --mqtt, repeatable, the
+    /// messages are named after it
+    pub(crate) topic: Vec<String>,
+    #[cfg(feature = "kafka")]
+    #[clap(long, value_name = "GROUP", default_value = "logmatrix")]
+    /// consumer group the offsets are committed for, a restarted instance goes on where it
+    /// stopped
+    pub(crate) kafka_group: String,
+    #[cfg(feature = "kafka")]
+    #[clap(long, requires = "kafka")]
+    /// prefix the messages with their `partition@offset`
+    pub(crate) kafka_offsets: bool,
+    #[cfg(feature = "loki")]
+    #[clap(long, value_name = "URL", requires = "query")]
+    /// tail the entries of the --query from a Grafana Loki server, e.g.
+    /// `http://localhost:3100`, the lines are colored after their `level` label
+    pub(crate) loki: Option<String>,
+    #[cfg(feature = "loki")]
+    #[clap(long, value_name = "LOGQL", requires = "loki")]
+    /// LogQL query to tail, e.g. `{app="api"}`
+    pub(crate) query: Option<String>,
+    #[cfg(feature = "loki")]
+    #[clap(long, value_name = "LABEL", requires = "loki")]
+    /// name the Loki lines after the value of LABEL rather than after their whole label
+    /// set, every value gets a color of its own
+    pub(crate) loki_label: Option<String>,
+    #[cfg(feature = "mqtt")]
+    #[clap(long, value_name = "[USER:PASS@]HOST[:PORT]", requires = "topic")]
+    /// subscribe to the --topic on an MQTT broker, `+` and `#` wildcards included, every
+    /// topic gets a color of its own
+    pub(crate) mqtt: Option<String>,
+    #[cfg(feature = "mqtt")]
+    #[clap(long, requires = "mqtt")]
+    /// prefix the MQTT messages with their topic
+    pub(crate) topic_prefix: bool,
+    #[cfg(feature = "nats")]
+    #[clap(long, value_name = "URL", requires = "subject")]
+    /// subscribe to the --subject on a NATS server, `nats://[USER:PASS@|TOKEN@]HOST[:PORT]`
+    pub(crate) nats: Option<String>,
+    #[cfg(feature = "nats")]
+    #[clap(long, value_name = "SUBJECT", requires = "nats")]
+    /// NATS subject to subscribe to, repeatable, wildcards included. the messages are named
+    /// after their subject and every subject gets a color of its own
+    pub(crate) subject: Vec<String>,
+    #[cfg(feature = "sse")]
+    #[clap(long, value_name = "URL")]
+    /// display the data of the server-sent events of URL, repeatable, the stream resumes
+    /// after the last event received when the connection drops
+    pub(crate) sse: Vec<String>,
+    #[cfg(feature = "ws")]
+    #[clap(long, value_name = "URL")]
+    /// stream the text frames of a WebSocket endpoint, `ws://` or `wss://`, repeatable
+    pub(crate) ws: Vec<String>,
+    #[cfg(feature = "ws")]
+    #[clap(long, value_name = "KEY", requires = "ws")]
+    /// display the value under KEY of the frames holding a JSON object, the other frames
+    /// as they are
+    pub(crate) ws_field: Option<String>,
+    #[clap(short, long, value_enum, default_value = "default")]
+    /// color of the text... color can change due to themed terminal
+    pub(crate) color: Color,
+    #[clap(long, value_enum, default_value = "white")]
+    /// highlight color of the text... color can change due to themed terminal
+    pub(crate) highlight_color: Color,
+    #[clap(long, value_enum, default_value = "3")]
+    /// length of the highlight
+    pub(crate) highlight_threshold: usize,
+    #[clap(long, value_enum, value_name = "EASING")]
+    /// pulse the brightness of the highlight instead of keeping it fixed, the wave runs
+    /// over the highlighted cells of every message with this easing
+    pub(crate) highlight_curve: Option<Easing>,
+    #[clap(long, value_name = "PERIOD", default_value = "1s", value_parser = parse_duration)]
+    /// period of the --highlight-curve pulse
+    pub(crate) pulse_period: Duration,
+    #[clap(short, long, default_value = "100")]
+    /// period between 2 refresh in ms
+    pub(crate) frequency: u64,
+    #[clap(long)]
+    /// run the drawing thread with a real-time priority, or at least a higher one, when
+    /// permitted
+    pub(crate) realtime: bool,
+    #[clap(long)]
+    /// skip drawing the frame after one which missed its deadline, the columns keep
+    /// moving on schedule on the slow terminals
+    pub(crate) skip_frames: bool,
+    #[clap(long, value_enum)]
+    /// show a line with the active sources, the direction, the period, the pause state and
+    /// the backlog above or below the matrix, which gets a row less
+    pub(crate) status_bar: Option<StatusBar>,
+    #[clap(long, value_name = "REGEX", num_args = 0..=1, require_equals = true,
+        default_missing_value = r"(?i)\b(error|fatal|critical|panic)\b", value_parser = Regex::new)]
+    /// show the last critical or error line, or line matching REGEX, in full on the bottom
+    /// row, scrolling when it is too long. the matrix gets a row less
+    pub(crate) error_ticker: Option<Regex>,
+    #[clap(long, value_enum, value_name = "CORNER")]
+    /// show the local time in a corner of the screen, over the matrix
+    pub(crate) clock: Option<Corner>,
+    #[clap(long, requires = "clock")]
+    /// show the time the newest line on screen was received next to the clock
+    pub(crate) clock_last_line: bool,
+    #[clap(long, value_name = "PERIOD", value_parser = parse_duration)]
+    /// fill the screen with random rain once no line was received for PERIOD, until the
+    /// next one arrives
+    pub(crate) idle_after: Option<Duration>,
+    #[clap(long, value_name = "PERIOD", value_parser = parse_duration)]
+    /// blank the cells on screen for longer than PERIOD, so that a quiet system does not
+    /// show stale fragments forever
+    pub(crate) char_ttl: Option<Duration>,
+    #[clap(long)]
+    /// show the frames per second, the period and the render time of the last frame, the
+    /// count of frames that missed their deadline and whether the rendering alone exceeds the
+    /// --frequency budget
+    pub(crate) hud: bool,
+    #[clap(long)]
+    /// keep the frame rate and the effects in a container with less than one CPU of quota,
+    /// they are lowered to fit in it otherwise
+    pub(crate) no_cpu_cap: bool,
+    #[clap(short, long, value_enum, default_value = "bottom")]
+    /// direction to which the logs will go
+    pub(crate) direction: Direction,
+    #[clap(long, value_name = "COUNT", default_value = "auto", value_parser = parse_spiral_tiles)]
+    /// spirals side by side with the spiral-right direction, each fed a share of the input,
+    /// `auto` tiles them from the aspect ratio so wide terminals keep no empty corners
+    pub(crate) spiral_tiles: SpiralTiles,
+    #[clap(short, long, default_value = "1")]
+    /// spaces between 2 messages
+    pub(crate) spaces: u16,
+    #[clap(long)]
+    /// draw every message as a separate drop led by a `█` head and followed by a fading tail
+    pub(crate) drops: bool,
+    #[clap(long)]
+    /// follow every line with how long it waited between its reception and its display,
+    /// e.g. ` +2.3s`, dimmed
+    pub(crate) show_age: bool,
+    #[clap(
+        long,
+        num_args = 0..=1,
+        default_missing_value_os = control::default_socket().as_os_str()
+    )]
+    /// listen for lines and annotations sent with `send`, keeps running once stdin is closed
+    pub(crate) control_socket: Option<PathBuf>,
+    #[clap(long)]
+    /// do not read lines from stdin, only from the files and the control socket
+    pub(crate) no_stdin: bool,
+    #[clap(long, value_name = "CMD")]
+    /// shell command run in the foreground when the handoff key is pressed, the matrix
+    /// resumes once it exits, e.g. `less +F /var/log/app.log`
+    pub(crate) handoff_cmd: Option<String>,
+    #[clap(
+        long,
+        value_name = "KEY",
+        default_value_t = 'h',
+        requires = "handoff_cmd"
+    )]
+    /// key of the controlling terminal running the handoff command
+    pub(crate) handoff_key: char,
+    #[clap(long, value_name = "FILE")]
+    /// write the frame on screen to FILE when the snapshot key is pressed, with its colors
+    /// as ANSI art, as an HTML snippet for a `.html` file or the characters alone for a `.txt`
+    /// one
+    pub(crate) snapshot_out: Option<PathBuf>,
+    #[clap(long, value_name = "DIR")]
+    /// write every snapshot to a new file of DIR, named after the time it was taken
+    pub(crate) snapshot_dir: Option<PathBuf>,
+    #[clap(long, value_enum, default_value = "ansi", requires = "snapshot_dir")]
+    /// format of the snapshots written to the snapshot directory
+    pub(crate) snapshot_format: SnapshotFormat,
+    #[clap(long, value_name = "KEY", default_value_t = 's', requires = "snapshot")]
+    /// key of the controlling terminal taking a snapshot
+    pub(crate) snapshot_key: char,
+    #[clap(long, value_name = "KEY")]
+    /// key of the controlling terminal switching to the next direction, the lines waiting
+    /// in the columns are kept
+    pub(crate) direction_key: Option<char>,
+    #[clap(long, value_name = "KEY")]
+    /// key of the controlling terminal switching to the next color of the text
+    pub(crate) color_key: Option<char>,
+    #[clap(long, value_name = "KEY")]
+    /// key of the controlling terminal switching to the next highlight color
+    pub(crate) highlight_key: Option<char>,
+    #[clap(long, value_name = "KEY")]
+    /// key of the controlling terminal switching to the next theme, a pair of text and
+    /// highlight colors, some of them pulsing the highlight as --highlight-curve does
+    pub(crate) theme_key: Option<char>,
+    #[clap(long)]
+    /// a click on a column freezes it so its text can be read and pops up the whole line,
+    /// its source, time and fields, a second one releases it. the lines keep being queued
+    /// in the frozen columns
+    pub(crate) mouse: bool,
+    #[clap(long, value_name = "KEY", default_value_t = 'y')]
+    /// key of the controlling terminal copying the line of the popup to the clipboard, through
+    /// the OSC 52 sequence of the terminal. the text matching --redact stays masked
+    pub(crate) copy_key: char,
+    #[clap(long, value_name = "KEY")]
+    /// key of the controlling terminal switching to a pager over the last lines received,
+    /// as they were received, and back to the animation
+    pub(crate) pager_key: Option<char>,
+    #[clap(long, value_name = "KEY", num_args = 0..=1, require_equals = true, default_missing_value = "i")]
+    /// key of the controlling terminal showing and hiding the rate and the counts of the
+    /// lines received, `i` when none is given
+    pub(crate) stats_key: Option<char>,
+    #[clap(
+        long,
+        value_name = "N",
+        default_value_t = 10000,
+        requires = "pager_key"
+    )]
+    /// lines kept for the pager
+    pub(crate) history: usize,
+    #[clap(long, conflicts_with = "tmux_pane")]
+    /// open the matrix in a tmux popup, stdin is forwarded to it
+    pub(crate) tmux_popup: bool,
+    #[clap(long, value_name = "TARGET")]
+    /// run the matrix in the given tmux pane instead of its current command
+    pub(crate) tmux_pane: Option<String>,
+    #[clap(long, default_value = "80%")]
+    /// width and height of the tmux popup, in cells or percent of the window
+    pub(crate) tmux_size: String,
+    #[clap(long, default_value = "4")]
+    /// number of spaces a tab expands to, 0 drops tabs
+    pub(crate) tab_width: usize,
+    #[clap(long)]
+    /// ring the bell and forward the OSC 777 desktop notifications found in the lines
+    /// instead of displaying them as control characters
+    pub(crate) passthrough_notifications: bool,
+    #[clap(long, default_value = "?")]
+    /// character displayed in place of non printable characters and of double width
+    /// characters that would break the columns alignment
+    pub(crate) placeholder: char,
+    #[clap(long, value_enum, default_value = "plain")]
+    /// glyphs used to draw the text past the highlight
+    pub(crate) glyphs: Glyphs,
+    #[clap(long, value_name = "RAMP", value_parser = parse_shade_ramp)]
+    /// draw every visible character with a glyph of RAMP picked by its age, from the
+    /// lightest to the densest used for the newest one, e.g. `--shade-ramp ' .:-=+*#%@'`
+    pub(crate) shade_ramp: Option<String>,
+    #[clap(long)]
+    /// seed of every random choice, the same seed and input replay the same animation
+    pub(crate) seed: Option<u64>,
+    #[clap(long, value_enum, default_value = "uniform")]
+    /// texture of the randomness used for column assignment, speed jitter and glitches
+    pub(crate) jitter: JitterProfile,
+    #[clap(long, default_value = "0", value_parser = parse_probability)]
+    /// probability for a column to skip a tick, between 0 and 1
+    pub(crate) speed_jitter: f32,
+    #[clap(long, value_name = "N", default_value_t = 1, conflicts_with = "adaptive_speed",
+        value_parser = clap::value_parser!(u16).range(1..))]
+    /// characters every column takes from its lines per frame, for the volumes one
+    /// character per frame cannot keep up with
+    pub(crate) chars_per_tick: u16,
+    #[clap(long, alias = "catch-up")]
+    /// tick the columns more often while lines wait in them so bursts do not lag behind,
+    /// and slow back down once caught up
+    pub(crate) adaptive_speed: bool,
+    #[clap(long, value_name = "N", default_value_t = 1, requires = "adaptive_speed",
+        value_parser = clap::value_parser!(u16).range(1..))]
+    /// ticks per frame of the columns with no backlog
+    pub(crate) min_ticks: u16,
+    #[clap(long, value_name = "N", default_value_t = 8, requires = "adaptive_speed",
+        value_parser = clap::value_parser!(u16).range(1..))]
+    /// most ticks per frame of a column however long its backlog
+    pub(crate) max_ticks: u16,
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    /// keep at most N lines waiting in every column, the next ones are handled by
+    /// --column-spill. unbounded by default
+    pub(crate) column_cache: Option<u64>,
+    #[clap(
+        long,
+        value_enum,
+        default_value = "drop-oldest",
+        requires = "column_cache"
+    )]
+    /// what becomes of the lines given to a full column
+    pub(crate) column_spill: ColumnSpill,
+    #[clap(long, default_value = "0", value_parser = parse_probability)]
+    /// probability for a visible character to glitch for one frame, between 0 and 1
+    pub(crate) glitch_rate: f32,
+    #[clap(long)]
+    /// for motion sensitive users, the columns scroll a whole screen at once every few
+    /// seconds instead of continuously and the glitches and the highlight pulse are disabled
+    pub(crate) reduced_motion: bool,
+    #[clap(long = "weight", value_name = "KEY=VALUE:WEIGHT", value_parser = parse_weight)]
+    /// lines from the source VALUE (`source=VALUE`) or holding the KEY=VALUE field pick the
+    /// least busy of WEIGHT random columns, e.g. `--weight source=api.log:3`, other lines keep
+    /// a purely random column
+    pub(crate) weights: Vec<Weight>,
+    #[clap(long, value_enum)]
+    #[cfg_attr(feature = "lua", clap(requires_if("script", "script")))]
+    /// order of the lines waiting in a column with more than a screen of characters behind,
+    /// the most severe, the newest or the most unusual ones first instead of the oldest. the
+    /// highest number the `on_score` function of the --script returns first with `script`
+    pub(crate) scorer: Option<ScorerKind>,
+    #[clap(long = "source-color", value_name = "SOURCE=COLOR", value_parser = parse_source_color)]
+    /// color of the lines from SOURCE, a file name or `stdin`, repeatable. every input
+    /// gets a distinct color on its own when several are followed
+    pub(crate) source_colors: Vec<SourceColor>,
+    #[clap(long, value_parser = Regex::new)]
+    /// mask the text matching REGEX with `*` as the line is received, before it is shown,
+    /// copied or written anywhere. repeatable
+    pub(crate) redact: Vec<Regex>,
+    #[clap(long, value_enum)]
+    /// mask emails, IP addresses or bearer tokens with `*`, repeatable
+    pub(crate) redact_builtin: Vec<Redaction>,
+    #[clap(long, value_name = "CMD")]
+    /// pipe every line through the shell command and display its output instead, e.g.
+    /// `jq -r .msg`. the lines it cannot keep up with are dropped
+    pub(crate) filter_cmd: Option<String>,
+    #[clap(
+        long,
+        value_name = "N",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    /// keep at most N lines waiting in the columns and N more queued behind them, the next
+    /// ones are handled by --queue-policy. unbounded by default
+    pub(crate) queue_size: Option<usize>,
+    #[clap(long, value_name = "N", default_value_t = 0)]
+    /// parse, match, color, redact and sanitize the lines on N threads ahead of the matrix,
+    /// for the volumes a single one cannot keep up with. the lines of a source keep their
+    /// order. the plugins, the script and the scorer still run on the matrix thread
+    pub(crate) workers: usize,
+    #[clap(long, value_enum, default_value = "block", requires = "queue_size")]
+    /// what becomes of the lines received while the queue is full, the dropped ones are
+    /// counted in the statistics
+    pub(crate) queue_policy: QueuePolicy,
+    #[cfg(feature = "lua")]
+    #[clap(long, value_name = "FILE")]
+    /// pass every line to the `on_line` function of a Lua script, which drops it, rewrites
+    /// it, colors it or picks its column
+    pub(crate) script: Option<PathBuf>,
+    #[cfg(feature = "wasm")]
+    #[clap(long, value_name = "DIR")]
+    /// pass every line through the WebAssembly plugins of DIR, its `.wasm` files in the
+    /// order of their names
+    pub(crate) plugin_dir: Option<PathBuf>,
+    #[clap(long, value_name = "N")]
+    /// truncate lines longer than N characters so they do not monopolize a column
+    pub(crate) max_line_length: Option<usize>,
+    #[clap(long, default_value = "…")]
+    /// appended to the truncated lines
+    pub(crate) ellipsis: String,
+    #[clap(long, requires = "max_line_length")]
+    /// split the long lines over several columns instead of truncating them
+    pub(crate) split_long_lines: bool,
+    #[clap(long, value_name = "PERIOD", value_parser = parse_duration)]
+    /// periodically inject a line with the uptime, memory use and counters of logmatrix
+    pub(crate) self_report: Option<Duration>,
+    #[clap(long, value_name = "FIELD")]
+    /// sequence number carried by the `FIELD=N` token of the lines, a jump in the numbers
+    /// of a source shows a `missing N lines` marker and is counted in the statistics overlay
+    /// and the self report
+    pub(crate) seq_field: Option<String>,
+    #[clap(long, value_name = "REGEX", value_parser = Regex::new)]
+    /// log every stage the lines matching REGEX go through to the --debug-log, from their
+    /// reception to the frames they show up in, to find out why a line is or is not displayed
+    pub(crate) trace_line: Option<Regex>,
+    #[clap(long, value_name = "FILE", default_value = "/tmp/logmatrix-debug.log")]
+    /// file the traces are appended to
+    pub(crate) debug_log: PathBuf,
+    #[clap(long, requires = "trace_line")]
+    /// show the stages of the traced lines as banners too
+    pub(crate) trace_overlay: bool,
+    #[clap(long, value_name = "BUNDLE", conflicts_with = "repro_replay")]
+    /// capture the seed, the input and the terminal size of every frame into a tar bundle
+    /// that replays the exact same animation, handy to report rendering bugs
+    pub(crate) repro: Option<PathBuf>,
+    #[clap(long, value_name = "FILE")]
+    /// append every line received, from stdin or any other input, to FILE verbatim, the
+    /// secrets --redact and --redact-builtin mask on screen included
+    pub(crate) tee: Option<PathBuf>,
+    #[clap(long, value_name = "FILE", requires = "match_regex")]
+    /// append the lines matching --match to FILE verbatim as they stream by, `/dev/fd/N`
+    /// for an open file descriptor
+    pub(crate) match_out: Option<PathBuf>,
+    #[clap(long = "match", value_name = "REGEX", value_parser = Regex::new, requires = "match_out")]
+    /// pattern of the lines written to --match-out, repeatable, a line matching any of them
+    /// is written
+    pub(crate) match_regex: Vec<Regex>,
+    #[clap(long, value_name = "REGEX", value_parser = Regex::new)]
+    /// pause the animation once a line matching REGEX is displayed, in the highlight color
+    /// and named in a banner, until a key is pressed
+    pub(crate) pause_on: Option<Regex>,
+    #[clap(long = "alert", value_name = "REGEX:N/PERIOD", value_parser = alert::parse)]
+    /// flash the screen when more than N lines matching REGEX are received within PERIOD,
+    /// e.g. `ERROR:10/30s`, repeatable
+    pub(crate) alerts: Vec<AlertRule>,
+    #[cfg(feature = "notify")]
+    #[clap(long, value_name = "REGEX", value_parser = Regex::new)]
+    /// raise a desktop notification with the lines matching REGEX
+    pub(crate) notify: Option<Regex>,
+    #[cfg(feature = "notify")]
+    #[clap(long, value_name = "PERIOD", default_value = "10s", value_parser = parse_duration)]
+    /// least time between 2 notifications, the lines matching in between are counted in the
+    /// next one
+    pub(crate) notify_interval: Duration,
+    #[clap(long = "exec-on", value_name = "REGEX:CMD", value_parser = exec::parse)]
+    /// run the shell command for the lines matching REGEX, the line is its argument `"$1"`,
+    /// e.g. `--exec-on 'OOM:logger -t oom "$1"'`. `{}` stands for the line, quoted or not,
+    /// repeatable
+    pub(crate) exec_on: Vec<ExecRule>,
+    #[clap(long, value_name = "N", default_value_t = 4, requires = "exec_on")]
+    /// commands of --exec-on running at once, the lines matching past it run nothing
+    pub(crate) exec_max: usize,
+    #[clap(long, value_name = "PERIOD", default_value = "1s", value_parser = parse_duration, requires = "exec_on")]
+    /// least time between 2 runs of the command of an --exec-on
+    pub(crate) exec_interval: Duration,
+    #[clap(long, value_name = "REGEX", value_parser = Regex::new)]
+    /// ring the bell of the terminal when a line matching REGEX is received, heard over ssh
+    /// too
+    pub(crate) bell: Option<Regex>,
+    #[clap(long, value_name = "PERIOD", default_value = "5s", value_parser = parse_duration, requires = "bell")]
+    /// least time between 2 rings of --bell
+    pub(crate) bell_cooldown: Duration,
+    #[clap(long, value_name = "REGEX", value_parser = Regex::new)]
+    /// once the inputs end or on quit, print how many lines matched REGEX and exit with a
+    /// failure status if any did, for CI pipelines
+    pub(crate) fail_on: Option<Regex>,
+    #[clap(long, value_name = "FILE")]
+    /// write a self-contained HTML report of the session on exit, with the rate of lines,
+    /// the totals per source, the most common alerts and a few screens, all redacted. the
+    /// alerts are the lines of warning severity or worse and the ones matching an --alert
+    pub(crate) report: Option<PathBuf>,
+    #[clap(long, value_name = "BUNDLE")]
+    /// replay a bundle captured with --repro, the other options are taken from the bundle
+    pub(crate) repro_replay: Option<PathBuf>,
+    #[clap(long, value_name = "[HOST]:PORT")]
+    /// stream the frames as chunked ANSI over HTTP instead of drawing them, for
+    /// `curl HOST:PORT` to show the animation, `?cols=N&rows=N` up to 1000x500 sets the size
+    pub(crate) serve_http: Option<String>,
+    #[clap(long, value_name = "COLSxROWS", default_value = "80x24", value_parser = parse_size)]
+    /// size of the frames streamed with --serve-http, overridden by the `?cols=N&rows=N` of
+    /// a request
+    pub(crate) virtual_size: (u16, u16),
+    #[clap(skip)]
+    pub(crate) timed_replay: Option<sources::TimedReplay>,
+}
+
+impl Args {
+    /// the options of a command line without a subcommand, the program name first. exits on
+    /// invalid options like the command line does
+    pub fn parse_from<I, T>(argv: I) -> Args
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let command = <Args as clap::Args>::augment_args(clap::Command::new("logmatrix"));
+        let matches = command.get_matches_from(argv);
+        Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit())
+    }
+}
+
+pub(crate) fn parse_duration(raw: &str) -> Result<Duration, String> {
+    let split = raw
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(raw.len());
+    let (value, unit) = raw.split_at(split);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("invalid duration `{raw}`"))?;
+    let seconds = match unit {
+        "ms" => value / 1000.,
+        "" | "s" => value,
+        "m" => value * 60.,
+        "h" => value * 3600.,
+        _ => return Err(format!("unknown duration unit `{unit}`, use ms, s, m or h")),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("duration `{raw}` out of range"))
+}
+
+fn parse_probability(raw: &str) -> Result<f32, String> {
+    match raw.parse::<f32>() {
+        Ok(probability) if (0. ..=1.).contains(&probability) => Ok(probability),
+        _ => Err(format!(
+            "expected a probability between 0 and 1, got `{raw}`"
+        )),
+    }
+}
+
+fn parse_speed(raw: &str) -> Result<f64, String> {
+    match raw.parse::<f64>() {
+        Ok(speed) if speed > 0. && speed.is_finite() => Ok(speed),
+        _ => Err(format!("expected a positive multiplier, got `{raw}`")),
+    }
+}
+
+fn parse_size(raw: &str) -> Result<(u16, u16), String> {
+    let size = raw.split_once('x').and_then(|(cols, rows)| {
+        Some((cols.parse().ok()?, rows.parse().ok()?)).filter(|&(cols, rows)| cols > 0 && rows > 0)
+    });
+    size.ok_or(format!("expected `COLSxROWS`, got `{raw}`"))
+}
+
+#[derive(Clone)]
+pub(crate) struct Weight {
+    pub(crate) key: String,
+    pub(crate) value: String,
+    pub(crate) weight: usize,
+}
+
+impl Weight {
+    pub(crate) fn matches(&self, line: &InputLine) -> bool {
+        if self.key == "source" && self.value == line.source {
+            return true;
+        }
+        line.text.split_whitespace().any(|token| {
+            token.split_once('=').is_some_and(|(key, value)| {
+                key == self.key && value.trim_matches('"') == self.value
+            })
+        })
+    }
+}
+
+fn parse_weight(raw: &str) -> Result<Weight, String> {
+    let (field, weight) = raw
+        .rsplit_once(':')
+        .ok_or(format!("missing `:WEIGHT` in `{raw}`"))?;
+    let (key, value) = field
+        .split_once('=')
+        .ok_or(format!("missing `KEY=VALUE` in `{raw}`"))?;
+    let weight = weight
+        .parse::<usize>()
+        .ok()
+        .filter(|weight| *weight > 0)
+        .ok_or(format!("weight must be a positive integer in `{raw}`"))?;
+    Ok(Weight {
+        key: key.to_string(),
+        value: value.to_string(),
+        weight,
+    })
+}
+
+// distinct colors given in turn to the inputs without a --source-color
+pub(crate) const SOURCE_PALETTE: [Color; 7] = [
+    Color::Green,
+    Color::Cyan,
+    Color::Yellow,
+    Color::Magenta,
+    Color::Blue,
+    Color::Red,
+    Color::White,
+];
+
+// the theme key cycles through these, a theme with a curve pulses its highlight
+pub(crate) struct Theme {
+    pub(crate) color: Color,
+    pub(crate) highlight: Color,
+    pub(crate) curve: Option<Easing>,
+}
+
+pub(crate) const THEMES: [Theme; 6] = [
+    Theme {
+        color: Color::Default,
+        highlight: Color::White,
+        curve: None,
+    },
+    Theme {
+        color: Color::Green,
+        highlight: Color::White,
+        curve: Some(Easing::EaseOut),
+    },
+    Theme {
+        color: Color::Cyan,
+        highlight: Color::Blue,
+        curve: None,
+    },
+    Theme {
+        color: Color::Red,
+        highlight: Color::Yellow,
+        curve: Some(Easing::EaseInOut),
+    },
+    Theme {
+        color: Color::Magenta,
+        highlight: Color::Cyan,
+        curve: Some(Easing::Linear),
+    },
+    Theme {
+        color: Color::Yellow,
+        highlight: Color::Red,
+        curve: None,
+    },
+];
+
+#[derive(Clone)]
+pub(crate) struct SourceColor {
+    pub(crate) source: String,
+    pub(crate) color: Color,
+}
+
+fn parse_source_color(raw: &str) -> Result<SourceColor, String> {
+    let (source, color) = raw
+        .rsplit_once('=')
+        .ok_or(format!("missing `=COLOR` in `{raw}`"))?;
+    Ok(SourceColor {
+        source: source.to_string(),
+        color: Color::from_str(color, true)?,
+    })
+}
+
+// the --source-color mappings completed with a palette color for every other input
+// when several are followed, the highlight color is kept apart
+pub(crate) fn source_colors(opt: &Args) -> Vec<SourceColor> {
+    let mut colors = opt.source_colors.clone();
+    let mut inputs = opt.files.clone();
+    inputs.extend(sources::expand_globs(&opt.file_globs));
+    if inputs.len() < 2 {
+        return colors;
+    }
+    let mut palette = SOURCE_PALETTE
+        .into_iter()
+        .filter(|color| *color != opt.highlight_color)
+        .cycle();
+    for source in inputs.iter().map(|path| sources::source_name(path)) {
+        if colors.iter().all(|mapping| mapping.source != source)
+            && let Some(color) = palette.next()
+        {
+            colors.push(SourceColor { source, color });
+        }
+    }
+    colors
+}
+
+fn parse_shade_ramp(raw: &str) -> Result<String, String> {
+    if raw.is_empty() {
+        return Err("the ramp needs at least one character".to_string());
+    }
+    match raw.chars().all(|c| c.width() == Some(1)) {
+        true => Ok(raw.to_string()),
+        false => Err(format!(
+            "every character of `{raw}` must be one column wide"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probabilities_stay_between_0_and_1() {
+        for valid in ["0", "0.25", "1"] {
+            assert!(parse_probability(valid).is_ok(), "{valid}");
+        }
+        for invalid in ["-0.1", "1.5", "NaN", "inf", "often"] {
+            assert!(parse_probability(invalid).is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{
+    MATRIX_MODES, RUNNING,
+    keys::Keyboard,
+    renderer::{Renderer, terminal_size_or_default},
+    term::{self, TermMode},
+};
+use serde_json::{Value, json};
+use std::{
+    env,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Stdout, Write},
+    path::Path,
+    sync::atomic::Ordering,
+    thread::sleep,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use terminal_size::{Height, Width, terminal_size};
+
+// the keyboard is checked this often while waiting for the next frame
+const TICK: Duration = Duration::from_millis(50);
+const PLAYER_MODES: [TermMode; 2] = [TermMode::AltScreen, TermMode::HiddenCursor];
+
+/// shows the frames and records them into an asciicast v2 file, one event per frame
+pub struct CastRecorder {
+    stdout: Stdout,
+    cast: BufWriter<File>,
+    started: Instant,
+    frame: Vec<u8>,
+    size: Option<(u16, u16)>,
+}
+
+impl CastRecorder {
+    pub fn create(path: &Path) -> io::Result<CastRecorder> {
+        let size = terminal_size().map(|(Width(width), Height(height))| (width, height));
+        let (width, height) = size.unwrap_or((80, 24));
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let header = json!({
+            "version": 2,
+            "width": width,
+            "height": height,
+            "timestamp": timestamp.as_secs(),
+            "env": { "TERM": env::var("TERM").unwrap_or_default() },
+        });
+        let mut cast = BufWriter::new(File::create(path)?);
+        writeln!(cast, "{header}")?;
+        Ok(CastRecorder {
+            stdout: io::stdout(),
+            cast,
+            started: Instant::now(),
+            frame: vec![],
+            size,
+        })
+    }
+
+    fn event(&mut self, kind: &str, data: &str) -> io::Result<()> {
+        let at = self.started.elapsed().as_secs_f64();
+        writeln!(
+            self.cast,
+            "{}",
+            json!([(at * 1e6).round() / 1e6, kind, data])
+        )
+    }
+}
+
+impl Write for CastRecorder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.frame.extend_from_slice(buf);
+        self.stdout.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    // a frame is flushed at once, whole characters only
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()?;
+        let size = terminal_size().map(|(Width(width), Height(height))| (width, height));
+        if size != self.size
+            && let Some((width, height)) = size
+        {
+            self.size = size;
+            self.event("r", &format!("{width}x{height}"))?;
+        }
+        if !self.frame.is_empty() {
+            let frame = std::mem::take(&mut self.frame);
+            self.event("o", &String::from_utf8_lossy(&frame))?;
+        }
+        self.cast.flush()
+    }
+}
+
+// shown on the terminal like the frames are
+impl Renderer for CastRecorder {
+    fn size(&self) -> (u16, u16) {
+        terminal_size_or_default()
+    }
+
+    fn enter(&mut self) {
+        term::enter(&MATRIX_MODES);
+    }
+
+    fn leave(&mut self) {
+        term::leave(&MATRIX_MODES);
+    }
+}
+
+/// true when the file starts with the header of an asciicast v2 file
+pub fn is_cast(path: &Path) -> bool {
+    let Ok(file) = File::open(path) else {
+        return false;
+    };
+    let mut header = String::new();
+    let _ = BufReader::new(file).read_line(&mut header);
+    serde_json::from_str::<Value>(&header).is_ok_and(|header| header["version"] == 2)
+}
+
+/// the output events of a cast at their pace, divided by the speed. space pauses and resumes
+/// the replay, q ends it
+pub fn play(path: &Path, speed: f64) -> io::Result<()> {
+    let mut events = BufReader::new(File::open(path)?).lines().skip(1);
+    // none without a terminal, the replay can't be paused then
+    let mut keyboard = Keyboard::open().ok();
+    let mut stdout = io::stdout();
+    term::enter(&PLAYER_MODES);
+    let (mut previous, mut paused) = (0., false);
+    'events: while let Some(event) = events.next().transpose()? {
+        // [SECONDS, "o", DATA], the input and resize events can't be replayed
+        let Ok(Value::Array(event)) = serde_json::from_str(&event) else {
+            continue;
+        };
+        let field = |index: usize| event.get(index);
+        let (Some(at), Some("o"), Some(data)) = (
+            field(0).and_then(Value::as_f64),
+            field(1).and_then(Value::as_str),
+            field(2).and_then(Value::as_str),
+        ) else {
+            continue;
+        };
+        // the times of a broken cast can be anything
+        let mut wait =
+            Duration::try_from_secs_f64((at - previous).max(0.) / speed).unwrap_or(Duration::MAX);
+        previous = at;
+        while !wait.is_zero() || paused {
+            if !RUNNING.load(Ordering::SeqCst) {
+                break 'events;
+            }
+            let keys = keyboard.as_mut().map(Keyboard::pressed).unwrap_or_default();
+            if keys.contains(&b'q') {
+                break 'events;
+            }
+            if keys.contains(&b' ') {
+                paused = !paused;
+            }
+            let tick = wait.min(TICK);
+            sleep(if paused { TICK } else { tick });
+            if !paused {
+                wait -= tick;
+            }
+        }
+        stdout.write_all(data.as_bytes())?;
+        stdout.flush()?;
+    }
+    term::leave(&PLAYER_MODES);
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use std::{fs, time::Duration};
+
+// frame rate allowed per core of quota, a constrained container gets less than one core
+const FPS_PER_CORE: f64 = 20.;
+const MIN_FPS: f64 = 2.;
+
+/// CPU quota of the cgroup of the process in cores, none when it is unlimited
+pub fn cpu_limit() -> Option<f64> {
+    let cgroups = fs::read_to_string("/proc/self/cgroup").unwrap_or_default();
+    cgroup_v2_limit(&cgroups).or_else(|| cgroup_v1_limit(&cgroups))
+}
+
+/// shortest frame period fitting in the quota
+pub fn min_frame_period(cores: f64) -> Duration {
+    Duration::from_secs_f64(1. / (cores * FPS_PER_CORE).max(MIN_FPS))
+}
+
+// `cpu.max` in the cgroup of the process or at the root of the namespace of a container
+fn cgroup_v2_limit(cgroups: &str) -> Option<f64> {
+    let own = v2_path(cgroups).map(|path| format!("/sys/fs/cgroup{path}/cpu.max"));
+    let max = own
+        .into_iter()
+        .chain(["/sys/fs/cgroup/cpu.max".to_string()])
+        .find_map(|path| fs::read_to_string(path).ok())?;
+    parse_cpu_max(&max)
+}
+
+// the quota and period files of the cpu controller, in the cgroup of the process first
+fn cgroup_v1_limit(cgroups: &str) -> Option<f64> {
+    let own = v1_path(cgroups);
+    let paths = own.iter().map(String::as_str).chain([""]);
+    let dirs = ["/sys/fs/cgroup/cpu,cpuacct", "/sys/fs/cgroup/cpu"];
+    paths
+        .flat_map(|path| dirs.map(|dir| format!("{dir}{path}")))
+        .find_map(|dir| {
+            let read = |file| fs::read_to_string(format!("{dir}/{file}")).ok();
+            parse_cfs(&read("cpu.cfs_quota_us")?, &read("cpu.cfs_period_us")?)
+        })
+}
+
+// the cgroup v2 of the process in `/proc/self/cgroup`, without its trailing `/`
+fn v2_path(cgroups: &str) -> Option<String> {
+    let path = cgroups.lines().find_map(|line| line.strip_prefix("0::"))?;
+    Some(path.trim_end_matches('/').to_string())
+}
+
+// the cgroup v1 of the cpu controller in `/proc/self/cgroup`, without its trailing `/`
+fn v1_path(cgroups: &str) -> Option<String> {
+    cgroups.lines().find_map(|line| {
+        let (_, rest) = line.split_once(':')?;
+        let (controllers, path) = rest.split_once(':')?;
+        controllers
+            .split(',')
+            .any(|name| name == "cpu")
+            .then(|| path.trim_end_matches('/').to_string())
+    })
+}
+
+// `cpu.max` holds `QUOTA PERIOD`, or `max PERIOD` when unlimited
+fn parse_cpu_max(max: &str) -> Option<f64> {
+    let (quota, period) = max.trim().split_once(' ')?;
+    cores(quota.parse().ok()?, period.parse().ok()?)
+}
+
+// `cpu.cfs_quota_us` is -1 when unlimited
+fn parse_cfs(quota: &str, period: &str) -> Option<f64> {
+    let quota: i64 = quota.trim().parse().ok()?;
+    let period: i64 = period.trim().parse().ok()?;
+    cores(quota.try_into().ok()?, period.try_into().ok()?)
+}
+
+fn cores(quota: u64, period: u64) -> Option<f64> {
+    (period > 0).then(|| quota as f64 / period as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_max() {
+        assert_eq!(parse_cpu_max("50000 100000\n"), Some(0.5));
+        assert_eq!(parse_cpu_max("200000 100000"), Some(2.));
+        assert_eq!(parse_cpu_max("max 100000\n"), None);
+        assert_eq!(parse_cpu_max("50000 0"), None);
+        assert_eq!(parse_cpu_max(""), None);
+    }
+
+    #[test]
+    fn cfs_quota() {
+        assert_eq!(parse_cfs("25000\n", "100000\n"), Some(0.25));
+        assert_eq!(parse_cfs("-1\n", "100000\n"), None);
+        assert_eq!(parse_cfs("25000", "0"), None);
+        assert_eq!(parse_cfs("lots", "100000"), None);
+    }
+
+    #[test]
+    fn cgroup_paths() {
+        let v2 = "0::/system.slice/app.service\n";
+        assert_eq!(v2_path(v2).as_deref(), Some("/system.slice/app.service"));
+        assert_eq!(v2_path("0::/\n").as_deref(), Some(""));
+        let v1 = "12:memory:/docker/abc\n4:cpu,cpuacct:/docker/abc/\n1:name=systemd:/\n";
+        assert_eq!(v1_path(v1).as_deref(), Some("/docker/abc"));
+        assert_eq!(v2_path(v1), None);
+        assert_eq!(v1_path(v2), None);
+        assert_eq!(v1_path("3:cpuset:/a\n"), None);
+    }
+}
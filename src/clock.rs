@@ -0,0 +1,75 @@
+use clap::ValueEnum;
+use std::{
+    mem::MaybeUninit,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// corner of the screen the clock is drawn in
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[clap(rename_all = "kebab_case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    /// column and row of the first character of a text `width` columns wide
+    pub fn position(self, width: u16, size: (u16, u16)) -> (u16, u16) {
+        let right = size.0.saturating_sub(width) + 1;
+        match self {
+            Corner::TopLeft => (1, 1),
+            Corner::TopRight => (right, 1),
+            Corner::BottomLeft => (1, size.1),
+            Corner::BottomRight => (right, size.1),
+        }
+    }
+}
+
+/// `HH:MM:SS` in the local time zone, in UTC when it cannot be found
+pub fn local_time(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut tm = MaybeUninit::<libc::tm>::uninit();
+    let timestamp = seconds as libc::time_t;
+    // SAFETY: both pointers are valid, the result is only read when it was filled
+    if !unsafe { libc::localtime_r(&timestamp, tm.as_mut_ptr()) }.is_null() {
+        // SAFETY: filled by localtime_r
+        let tm = unsafe { tm.assume_init() };
+        return format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec);
+    }
+    let time = seconds % 86400;
+    format!("{:02}:{:02}:{:02}", time / 3600, time / 60 % 60, time % 60)
+}
+
+/// days since 1970-01-01 of the date, from Howard Hinnant's algorithm
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// year, month and day of the days since 1970-01-01, from Howard Hinnant's algorithm
+pub fn civil_date(days: u64) -> (u64, u64, u64) {
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
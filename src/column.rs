@@ -0,0 +1,516 @@
+use crate::{
+    Args, Color, Direction, Matrix, detail::Record, renderer::Renderer, text::GlyphTransform,
+};
+use std::{
+    collections::VecDeque,
+    io,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// one grapheme cluster with its color
+#[derive(Clone)]
+pub(crate) struct Cell {
+    pub(crate) glyph: String,
+    pub(crate) color: Color,
+    pub(crate) highlight: Option<usize>, // position in the highlight of the message
+    pub(crate) dim: bool,
+    pub(crate) record: Option<Arc<Record>>, // the line the glyph was taken from
+}
+
+impl Cell {
+    fn new(glyph: String, color: Color) -> Cell {
+        Cell {
+            glyph,
+            color,
+            highlight: None,
+            dim: false,
+            record: None,
+        }
+    }
+
+    fn highlighted(glyph: String, color: Color, position: usize) -> Cell {
+        Cell {
+            highlight: Some(position),
+            ..Cell::new(glyph, color)
+        }
+    }
+
+    fn dimmed(glyph: String, color: Color) -> Cell {
+        Cell {
+            dim: true,
+            ..Cell::new(glyph, color)
+        }
+    }
+
+    fn blank() -> Cell {
+        Cell::new(" ".to_string(), Color::Default)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct CircularCharQueue {
+    pub(crate) data: Vec<Cell>,
+    pub(crate) pushed: Vec<Instant>, // when each cell was pushed, for --char-ttl
+    pub(crate) front_index: usize,   // pointer to the watch head of the circular buffer
+    pub(crate) back_index: usize,    //pointer to the head of the circular buffer
+}
+
+impl CircularCharQueue {
+    fn new(size: usize) -> CircularCharQueue {
+        CircularCharQueue {
+            data: vec![Cell::blank(); size],
+            pushed: vec![Instant::now(); size],
+            front_index: size,
+            back_index: 0,
+        }
+    }
+
+    fn push_back(&mut self, cell: Cell) {
+        self.push_back_at(cell, Instant::now());
+    }
+
+    fn push_back_at(&mut self, cell: Cell, pushed: Instant) {
+        self.data[self.back_index] = cell;
+        self.pushed[self.back_index] = pushed;
+
+        self.back_index = if self.back_index == 0 {
+            self.data.len() - 1
+        } else {
+            self.back_index - 1
+        };
+
+        self.front_index = self.back_index;
+    }
+
+    // keep the newest cells fitting in the new size, blanks are added before them
+    pub(crate) fn resize(&mut self, size: usize) {
+        let len = self.data.len();
+        let newest_first: Vec<(Cell, Instant)> = (1..=len)
+            .map(|age| (self.back_index + age) % len)
+            .map(|index| (self.data[index].clone(), self.pushed[index]))
+            .take(size)
+            .collect();
+        *self = CircularCharQueue::new(size);
+        for _ in newest_first.len()..size {
+            self.push_back(Cell::blank());
+        }
+        for (cell, pushed) in newest_first.into_iter().rev() {
+            self.push_back_at(cell, pushed);
+        }
+    }
+
+    // the cells pushed longer than `ttl` ago are blanked
+    pub(crate) fn expire(&mut self, ttl: Duration) {
+        let now = Instant::now();
+        for (cell, pushed) in self.data.iter_mut().zip(&self.pushed) {
+            if now.duration_since(*pushed) >= ttl && cell.glyph != " " {
+                *cell = Cell::blank();
+            }
+        }
+    }
+
+    // also tells how fresh the cell is, 1 for the newest down to 0 for the oldest
+    // the cell returned by the call `rank` of the last `calls` ones to `get_next`
+    pub(crate) fn drawn(&self, rank: usize, calls: usize, direction: &Direction) -> &Cell {
+        let len = self.data.len();
+        let back = (calls - rank) % len;
+        let index = match direction {
+            Direction::Top | Direction::SpiralRight | Direction::Left => self.front_index + back,
+            Direction::Bottom | Direction::Right => self.front_index + len - back,
+        };
+        &self.data[index % len]
+    }
+
+    // the next `get_next` starts over from the newest cell
+    pub(crate) fn rewind(&mut self) {
+        self.front_index = self.back_index;
+    }
+
+    pub(crate) fn get_next(&mut self, direction: &Direction) -> (Cell, f32) {
+        let cc = self.data[self.front_index].clone();
+        let len = self.data.len();
+        let age = (self.front_index + len - self.back_index - 1) % len;
+        let freshness = 1.0 - age as f32 / len as f32;
+
+        self.front_index = match direction {
+            Direction::Top | Direction::SpiralRight | Direction::Left => {
+                if self.front_index == 0 {
+                    self.data.len() - 1
+                } else {
+                    self.front_index - 1
+                }
+            }
+            Direction::Bottom | Direction::Right => {
+                if self.front_index == self.data.len() - 1 {
+                    0
+                } else {
+                    self.front_index + 1
+                }
+            }
+        };
+
+        (cc, freshness)
+    }
+}
+
+// lines waiting in a column before --adaptive-speed ticks it faster
+const ADAPTIVE_SLACK: usize = 2;
+// and the ones adding a tick per frame past them
+const ADAPTIVE_LINES_PER_TICK: usize = 4;
+pub(crate) const TRACE_LAST_GLYPH: &str = "last glyph on screen";
+
+const DROP_HEAD: &str = "█";
+const DROP_TAIL: [&str; 3] = ["▓", "▒", "░"];
+
+// lifecycle of the drop a message falls in when --drops is set
+#[derive(Clone, Copy)]
+pub(crate) enum DropState {
+    Idle,
+    Falling,
+    Fading(usize, Color), // next step of the tail, color of the message
+}
+
+// line waiting in a column
+#[derive(Clone)]
+pub(crate) struct QueuedLine {
+    pub(crate) text: String,
+    pub(crate) color: Color,
+    pub(crate) score: f64,
+    pub(crate) trace: Option<u64>,          // id given by --trace-line
+    pub(crate) record: Option<Arc<Record>>, // none for the markers and fillers
+    pub(crate) merged: usize,               // lines dropped in its favor by --column-spill merge
+    pub(crate) filler: bool,                // random glyphs of the demo and --idle-after
+}
+
+/// a column of the rain, its lines queued until they fall glyph by glyph
+#[derive(Clone)]
+pub struct ColumnMat {
+    pub(crate) invisible_cache: VecDeque<QueuedLine>,
+    pub(crate) visible_line: CircularCharQueue,
+    pub(crate) index: usize, // index in the current invisible_cache
+    pub(crate) color: Color,
+    pub(crate) highlight: Color,
+    pub(crate) highlight_threshold: usize,
+    pub(crate) placeholder: char,
+    pub(crate) wide_cells: bool, // cells are 2 columns wide and can hold double width characters
+    pub(crate) glyphs: GlyphTransform,
+    pub(crate) drops: bool,
+    pub(crate) drop: DropState,
+    pub(crate) ages: bool,
+    pub(crate) age: Vec<String>, // glyphs of the age of the current line still to display, reversed
+    pub(crate) trace_events: Vec<(u64, &'static str)>, // of the traced lines, since the last frame
+    pub(crate) frozen: bool,     // clicked, the lines queue up until released
+    pub(crate) ticks: u16,       // per frame with --adaptive-speed
+}
+
+impl ColumnMat {
+    pub(crate) fn new(
+        height: usize,
+        color: Color,
+        highlight: Color,
+        highlight_threshold: usize,
+        placeholder: char,
+        wide_cells: bool,
+        glyphs: GlyphTransform,
+    ) -> Self {
+        ColumnMat {
+            invisible_cache: VecDeque::new(),
+            visible_line: CircularCharQueue::new(height),
+            index: 0,
+            color,
+            highlight,
+            highlight_threshold,
+            placeholder,
+            wide_cells,
+            glyphs,
+            drops: false,
+            drop: DropState::Idle,
+            ages: false,
+            age: vec![],
+            trace_events: vec![],
+            frozen: false,
+            ticks: 1,
+        }
+    }
+
+    // a tick more per frame for every few lines waiting beyond a couple, speeding up at
+    // once and slowing down a tick at a time
+    pub(crate) fn adapt_speed(&mut self, min: u16, max: u16) -> u16 {
+        let waiting = self.invisible_cache.len().saturating_sub(ADAPTIVE_SLACK);
+        let extra = u16::try_from(waiting / ADAPTIVE_LINES_PER_TICK).unwrap_or(u16::MAX);
+        let target = min.saturating_add(extra);
+        self.ticks = target.max(self.ticks.saturating_sub(1)).min(max).max(min);
+        self.ticks
+    }
+
+    /// a column of `length` cells drawn as `opt` asks, like the ones of the top, bottom,
+    /// left and right directions
+    pub fn from_args(length: u16, opt: &Args) -> ColumnMat {
+        ColumnMat::new(
+            length as usize,
+            opt.color,
+            opt.highlight_color,
+            opt.highlight_threshold,
+            opt.placeholder,
+            false,
+            opt.glyphs.transform(),
+        )
+        .with_drops(opt.drops)
+        .with_ages(opt.show_age)
+    }
+
+    /// queue a line, drawn with the color of the column unless it has its own
+    pub fn push_line(&mut self, text: impl Into<String>, color: Option<Color>) {
+        self.add_line(text.into(), color, 0., None, None);
+    }
+
+    pub(crate) fn with_drops(mut self, drops: bool) -> Self {
+        self.drops = drops;
+        self
+    }
+
+    pub(crate) fn with_ages(mut self, ages: bool) -> Self {
+        self.ages = ages;
+        self
+    }
+
+    // substitute what cannot fit exactly in one cell
+    fn fit(&self, grapheme: &str) -> String {
+        match grapheme.width() {
+            1 => grapheme.to_string(),
+            2 if self.wide_cells => grapheme.to_string(),
+            _ => self.placeholder.to_string(),
+        }
+    }
+
+    // the line is drawn with the color of the column unless it has its own
+    pub(crate) fn add_line(
+        &mut self,
+        addon: String,
+        color: Option<Color>,
+        score: f64,
+        trace: Option<u64>,
+        record: Option<Arc<Record>>,
+    ) {
+        self.invisible_cache.push_back(QueuedLine {
+            text: addon,
+            color: color.unwrap_or(self.color),
+            score,
+            trace,
+            record,
+            merged: 0,
+            filler: false,
+        });
+    }
+
+    // random glyphs falling while no line is received, dropped once one is
+    pub(crate) fn add_filler(&mut self, filler: String) {
+        self.add_line(filler, None, 0., None, None);
+        if let Some(line) = self.invisible_cache.back_mut() {
+            line.filler = true;
+        }
+    }
+
+    // only the real lines stay, the ones buffered by a frozen column included
+    pub(crate) fn drop_fillers(&mut self) {
+        if self.invisible_cache.front().is_some_and(|line| line.filler) {
+            self.index = 0;
+            self.drop = DropState::Idle;
+        }
+        self.invisible_cache.retain(|line| !line.filler);
+    }
+
+    // the oldest line waiting, after the one being displayed
+    pub(crate) fn drop_oldest(&mut self) -> Option<QueuedLine> {
+        self.invisible_cache.remove(usize::from(self.index > 0))
+    }
+
+    // one more line dropped in favor of the newest one, counted at its end
+    pub(crate) fn merge_newest(&mut self) {
+        let Some(line) = self.invisible_cache.back_mut() else {
+            return;
+        };
+        if line.merged > 0 {
+            let counter = format!(" [+{}]", line.merged);
+            line.text.truncate(line.text.len() - counter.len());
+        }
+        line.merged += 1;
+        line.text += &format!(" [+{}]", line.merged);
+    }
+
+    // bytes waiting to be displayed
+    pub(crate) fn backlog(&self) -> usize {
+        self.invisible_cache
+            .iter()
+            .map(|line| line.text.len())
+            .sum()
+    }
+
+    // more than a screen of characters waiting, the best scored line goes first instead
+    // of the oldest one
+    fn promote_best(&mut self) {
+        if self.backlog() <= self.visible_line.data.len() {
+            return;
+        }
+        let best = self
+            .invisible_cache
+            .iter()
+            .enumerate()
+            .fold(0, |best, (idx, line)| {
+                if line.score > self.invisible_cache[best].score {
+                    idx
+                } else {
+                    best
+                }
+            });
+        if let Some(line) = self.invisible_cache.remove(best) {
+            self.invisible_cache.push_front(line);
+        }
+    }
+
+    /// fall by one cell, `spaces` blank cells are left between two lines
+    pub fn tick(&mut self, spaces: u16) {
+        match self.drop {
+            DropState::Idle if self.drops && !self.invisible_cache.is_empty() => {
+                self.visible_line
+                    .push_back(Cell::new(DROP_HEAD.to_string(), self.highlight));
+                self.drop = DropState::Falling;
+                return;
+            }
+            DropState::Fading(step, color) if step < DROP_TAIL.len() => {
+                self.visible_line
+                    .push_back(Cell::new(DROP_TAIL[step].to_string(), color));
+                self.drop = DropState::Fading(step + 1, color);
+                return;
+            }
+            DropState::Fading(..) => {
+                self.drop = DropState::Idle;
+                for _ in 0..spaces {
+                    self.visible_line.push_back(Cell::blank());
+                }
+                return;
+            }
+            _ => {}
+        }
+        if self.index == 0 {
+            self.promote_best();
+        }
+        // grapheme clusters keep combining sequences and emoji in a single cell
+        let next = self.invisible_cache.front().map(|line| {
+            let glyph = line
+                .text
+                .graphemes(true)
+                .nth(self.index)
+                .map(|g| self.fit(g));
+            (glyph, line.color, line.trace, line.record.clone())
+        });
+        match next {
+            None => self.visible_line.push_back(Cell::blank()),
+            Some((None, color, ..)) if !self.age.is_empty() => {
+                let glyph = self.age.pop().unwrap_or_default();
+                self.visible_line.push_back(Cell::dimmed(glyph, color));
+            }
+            Some((None, color, trace, _)) if self.drops => {
+                self.invisible_cache.pop_front();
+                self.index = 0;
+                self.trace_events
+                    .extend(trace.map(|id| (id, TRACE_LAST_GLYPH)));
+                self.drop = DropState::Fading(0, color);
+                self.tick(spaces);
+            }
+            Some((None, _, trace, _)) => {
+                self.invisible_cache.pop_front();
+                self.index = 0;
+                self.trace_events
+                    .extend(trace.map(|id| (id, TRACE_LAST_GLYPH)));
+                for _ in 0..spaces {
+                    self.visible_line.push_back(Cell::blank());
+                }
+            }
+            Some((Some(glyph), color, trace, record)) => {
+                if self.index == 0 {
+                    self.trace_events
+                        .extend(trace.map(|id| (id, "first glyph on screen")));
+                    if self.ages
+                        && let Some(record) = &record
+                    {
+                        let age = format!(" +{:.1}s", record.received.elapsed().as_secs_f32());
+                        self.age = age.chars().rev().map(String::from).collect();
+                    }
+                }
+                let cell = if self.index < self.highlight_threshold {
+                    Cell::highlighted(glyph, self.highlight, self.index)
+                } else {
+                    Cell::new((self.glyphs)(&glyph).into_owned(), color)
+                };
+                self.visible_line.push_back(Cell { record, ..cell });
+                self.index += 1;
+            }
+        };
+    }
+
+    // scroll a whole screen of characters at once
+    pub(crate) fn turn_page(&mut self, spaces: u16) {
+        for _ in 0..self.visible_line.data.len() {
+            self.tick(spaces);
+        }
+    }
+
+    pub(crate) fn get_next(&mut self, dir: &Direction) -> (Cell, f32) {
+        self.visible_line.get_next(dir)
+    }
+
+    /// draw the column at the terminal column `at`, or the row with the left and right
+    /// directions, the newest glyph on the side `direction` comes from
+    pub fn draw_frame(
+        &mut self,
+        out: &mut dyn Renderer,
+        at: u16,
+        direction: &Direction,
+    ) -> io::Result<()> {
+        self.visible_line.rewind();
+        for rank in 1..=self.visible_line.data.len() as u16 {
+            let (cell, _) = self.get_next(direction);
+            let (x, y) = if direction.horizontal() {
+                (rank, at)
+            } else {
+                (at, rank)
+            };
+            Matrix::place_cursor(out, x, y);
+            write!(out, "{}{}", cell.color.to_ansi(), cell.glyph)?;
+        }
+        write!(out, "{}", Color::Default.to_ansi())?;
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::MemoryRenderer;
+
+    #[test]
+    fn column_drawn_on_its_own() {
+        let opt = Args::parse_from(["logmatrix", "--spaces", "0"]);
+        let mut column = ColumnMat::from_args(3, &opt);
+        column.push_line("abc", None);
+        for _ in 0..3 {
+            column.tick(opt.spaces);
+        }
+        let out = MemoryRenderer::new(10, 3);
+        column
+            .draw_frame(&mut out.clone(), 4, &Direction::Top)
+            .unwrap();
+        let drawn = String::from_utf8(out.contents()).unwrap();
+        let rows: Vec<&str> = drawn.split("\x1b[").filter(|s| s.ends_with('H')).collect();
+        assert_eq!(rows, ["1;4H", "2;4H", "3;4H"]);
+        let glyphs: String = drawn
+            .split("\x1b[")
+            .filter_map(|sequence| sequence.split_once('m').map(|(_, glyph)| glyph))
+            .collect();
+        assert_eq!(glyphs, "abc");
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{
+    Args, Counters, HANDED_OFF, Matrix, RUNNING, RecordArgs, RenderArgs, ReplayArgs, SendArgs,
+    Sources,
+    cast::{self, CastRecorder},
+    control::{ControlClient, ControlMessage},
+    render::{GifRenderer, SvgRenderer},
+    renderer::Remote,
+    repro::ReproReplay,
+    serve::{self, Feed, Viewer},
+    source_colors, sources, stop, term, tmux,
+};
+use std::{
+    io,
+    process::exit,
+    sync::{Arc, atomic::Ordering},
+    thread::{sleep, spawn},
+};
+
+pub(crate) fn or_exit<T>(result: io::Result<T>, context: &str) -> T {
+    result.unwrap_or_else(|err| {
+        eprintln!("{context}: {err}");
+        exit(1)
+    })
+}
+
+// the errors of the matrix already tell what could not be opened
+fn or_start(result: io::Result<Matrix>) -> Matrix {
+    result.unwrap_or_else(|err| {
+        eprintln!("{err}");
+        exit(1)
+    })
+}
+
+// Ctrl-C stops the commands running a matrix, unless the handoff command has the terminal
+fn stop_on_interrupt() {
+    ctrlc::set_handler(|| {
+        if !HANDED_OFF.load(Ordering::SeqCst) {
+            stop()
+        }
+    })
+    .expect("Error setting Ctrl-C handler");
+}
+
+// options of the captured run, its inputs are replaced by the bundle
+fn replayed_args(replay: &ReproReplay) -> Args {
+    let mut args = Args::parse_from(&replay.args);
+    args.seed = Some(replay.seed);
+    // the colors given to the captured inputs outlive them
+    args.source_colors = source_colors(&args);
+    // the sockets of the captured run are not the replay's to remove
+    args.listen = vec![];
+    args.control_socket = None;
+    args.handoff_cmd = None;
+    args.self_report = None;
+    args.repro = None;
+    args
+}
+
+pub fn send_command(send: SendArgs) {
+    let msg = match send.sticky {
+        Some(text) => ControlMessage::Sticky {
+            text,
+            duration: send.duration,
+        },
+        None => ControlMessage::Line(send.line.unwrap_or_default()),
+    };
+    let sent = ControlClient::connect(&send.socket).and_then(|mut client| client.send(&msg));
+    if let Err(err) = sent {
+        eprintln!("could not reach {}: {err}", send.socket.display());
+        exit(1);
+    }
+}
+
+pub fn record_command(record: RecordArgs) {
+    let recorder = or_exit(
+        CastRecorder::create(&record.output),
+        "could not create the cast file",
+    );
+    stop_on_interrupt();
+    let mut mat = or_start(Matrix::new(record.args)).with_renderer(Box::new(recorder));
+    mat.main_loop();
+    mat.check_failures();
+}
+
+pub fn replay_command(replay: ReplayArgs) {
+    let ReplayArgs {
+        file,
+        speed,
+        mut args,
+    } = replay;
+    if !cast::is_cast(&file) {
+        args.timed_replay = Some(sources::TimedReplay { path: file, speed });
+        stop_on_interrupt();
+        let mut mat = or_start(Matrix::new(args));
+        mat.main_loop();
+        mat.check_failures();
+        return;
+    }
+    ctrlc::set_handler(|| RUNNING.store(false, Ordering::SeqCst))
+        .expect("Error setting Ctrl-C handler");
+    or_exit(cast::play(&file, speed), "could not play the cast file");
+}
+
+// nothing is shown, the clip ends after its duration or with the inputs
+pub fn render_command(render: RenderArgs) {
+    let RenderArgs {
+        gif,
+        svg,
+        duration,
+        args,
+    } = render;
+    let counters = Arc::new(Counters::default());
+    let feed = or_exit(Feed::spawn(&args, &counters), "could not open the inputs");
+    let size = args.virtual_size;
+    let viewer = Arc::new(Viewer::new());
+    viewer.resize(size.0, size.1);
+    ctrlc::set_handler(|| RUNNING.store(false, Ordering::SeqCst))
+        .expect("Error setting Ctrl-C handler");
+    let ending = viewer.clone();
+    spawn(move || {
+        sleep(duration);
+        ending.leave();
+    });
+    let out = Box::new(Remote::new(io::sink(), viewer));
+    let mut mat = or_start(Matrix::remote(serve::viewer_args(args), feed, out));
+    // the cpu cap may have slowed the frames down
+    let period = mat.frame_period;
+    if let Some(path) = gif {
+        let renderer = GifRenderer::create(&path, size, period);
+        mat.clips
+            .push(Box::new(or_exit(renderer, "could not create the GIF")));
+    }
+    if let Some(path) = svg {
+        mat.clips
+            .push(Box::new(SvgRenderer::new(&path, size, period)));
+    }
+    mat.main_loop();
+    mat.check_failures();
+}
+
+pub fn reset_command() {
+    match term::reset() {
+        Ok(modes) => eprintln!("restored terminal modes: {}", modes.join(", ")),
+        Err(err) => {
+            eprintln!("could not reset the terminal: {err}");
+            exit(1);
+        }
+    }
+}
+
+/// the animation on the terminal, or served over HTTP, or in tmux, or replaying a bundle,
+/// as the options ask for
+pub fn run(args: Args) {
+    if args.serve_http.is_some() {
+        serve::serve_http(args);
+        return;
+    }
+    if tmux::wants_launch(&args) {
+        if let Err(err) = tmux::launch(&args) {
+            eprintln!("could not launch in tmux: {err}");
+            exit(1);
+        }
+        return;
+    }
+    stop_on_interrupt();
+    let mut mat = match &args.repro_replay {
+        Some(path) => {
+            let replay = or_exit(ReproReplay::open(path), "could not read the bundle");
+            let args = replayed_args(&replay);
+            or_start(Matrix::with_sources(args, Sources::default())).with_replay(replay)
+        }
+        None => or_start(Matrix::new(args)),
+    };
+    mat.main_loop();
+    mat.check_failures();
+}
@@ -0,0 +1,115 @@
+use crate::{Args, Color, Direction};
+use std::{path::PathBuf, time::Duration};
+
+/// the options of the matrix for the programs embedding it, the ones left alone keep the
+/// defaults of the command line
+///
+/// ```no_run
+/// use matrix_text::{Color, Config, Direction, Matrix};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let args = Config::new()
+///     .files(["/var/log/syslog"])
+///     .color(Color::Green)
+///     .direction(Direction::Bottom)
+///     .build();
+/// Matrix::new(args)?.main_loop();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Config {
+    args: Args,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config::new()
+    }
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config {
+            args: Args::parse_from(["logmatrix"]),
+        }
+    }
+
+    /// files to follow like `tail -f`, stdin is no longer read then
+    pub fn files<I, P>(mut self, files: I) -> Config
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.args.files = files.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// stdin is read when no other input is given, unless told not to
+    pub fn no_stdin(mut self, no_stdin: bool) -> Config {
+        self.args.no_stdin = no_stdin;
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Config {
+        self.args.color = color;
+        self
+    }
+
+    pub fn highlight_color(mut self, color: Color) -> Config {
+        self.args.highlight_color = color;
+        self
+    }
+
+    pub fn direction(mut self, direction: Direction) -> Config {
+        self.args.direction = direction;
+        self
+    }
+
+    /// time between two frames
+    pub fn frame_period(mut self, period: Duration) -> Config {
+        self.args.frequency = period.as_millis() as u64;
+        self
+    }
+
+    /// blank cells between two messages of a column
+    pub fn spaces(mut self, spaces: u16) -> Config {
+        self.args.spaces = spaces;
+        self
+    }
+
+    pub fn drops(mut self, drops: bool) -> Config {
+        self.args.drops = drops;
+        self
+    }
+
+    /// the same seed draws the same animation out of the same lines
+    pub fn seed(mut self, seed: u64) -> Config {
+        self.args.seed = Some(seed);
+        self
+    }
+
+    /// probability for a visible character to glitch for one frame, brought between 0 and 1
+    pub fn glitch_rate(mut self, rate: f32) -> Config {
+        self.args.glitch_rate = match rate.is_nan() {
+            true => 0.,
+            false => rate.clamp(0., 1.),
+        };
+        self
+    }
+
+    pub fn reduced_motion(mut self, reduced_motion: bool) -> Config {
+        self.args.reduced_motion = reduced_motion;
+        self
+    }
+
+    /// size of the animation when there is no terminal to take it from
+    pub fn virtual_size(mut self, width: u16, height: u16) -> Config {
+        self.args.virtual_size = (width, height);
+        self
+    }
+
+    pub fn build(self) -> Args {
+        self.args
+    }
+}
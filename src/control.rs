@@ -0,0 +1,166 @@
+use crate::sources::clients;
+use std::{
+    env,
+    fs::{self, DirBuilder},
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::{DirBuilderExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        LazyLock,
+        mpsc::{self, Receiver},
+    },
+    thread::spawn,
+    time::Duration,
+};
+
+static DEFAULT_SOCKET: LazyLock<PathBuf> = LazyLock::new(|| runtime_path("sock"));
+
+/// `logmatrix.EXTENSION` in the runtime directory of the user, or named after their uid in
+/// the temporary one when they have none
+pub fn runtime_path(extension: &str) -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => Path::new(&dir).join(format!("logmatrix.{extension}")),
+        _ => env::temp_dir().join(format!("logmatrix-{}.{extension}", unsafe {
+            libc::getuid()
+        })),
+    }
+}
+
+/// the socket of the user, in their runtime directory when there is one
+pub fn default_socket() -> &'static Path {
+    &DEFAULT_SOCKET
+}
+
+/// message understood by the control socket of a running instance
+/// one message per line: `line <text>`, `sticky <duration in ms> <text>` or `quit`
+pub enum ControlMessage {
+    Line(String),
+    Sticky { text: String, duration: Duration },
+    Quit,
+}
+
+impl ControlMessage {
+    fn parse(raw: &str) -> Option<ControlMessage> {
+        let (verb, rest) = raw.split_once(' ').unwrap_or((raw, ""));
+        match verb {
+            "line" => Some(ControlMessage::Line(rest.to_string())),
+            "sticky" => {
+                let (millis, text) = rest.split_once(' ')?;
+                Some(ControlMessage::Sticky {
+                    text: text.to_string(),
+                    duration: Duration::from_millis(millis.parse().ok()?),
+                })
+            }
+            "quit" => Some(ControlMessage::Quit),
+            _ => None,
+        }
+    }
+
+    fn encode(&self) -> String {
+        match self {
+            ControlMessage::Line(text) => format!("line {}", text.replace('\n', " ")),
+            ControlMessage::Sticky { text, duration } => format!(
+                "sticky {} {}",
+                duration.as_millis(),
+                text.replace('\n', " ")
+            ),
+            ControlMessage::Quit => "quit".to_string(),
+        }
+    }
+}
+
+/// listen on the control socket, every client gets its own thread
+pub fn spawn_control_channel(path: &Path) -> io::Result<Receiver<ControlMessage>> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                "another instance is listening on this socket",
+            ));
+        }
+        // stale socket left by a killed instance
+        fs::remove_file(path)?;
+    }
+
+    let listener = bind_private(path)?;
+    let (tx, rx) = mpsc::channel::<ControlMessage>();
+    spawn(move || {
+        for stream in clients(listener.incoming()) {
+            let tx = tx.clone();
+            spawn(move || {
+                for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                    if let Some(msg) = ControlMessage::parse(&line)
+                        && tx.send(msg).is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    Ok(rx)
+}
+
+// the lines and the banners reach the terminal, only the user may send them. the socket is
+// bound in a directory only the user can enter and restricted before being moved in place,
+// so others can never connect in between
+fn bind_private(path: &Path) -> io::Result<UnixListener> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let dir = parent.join(format!(".logmatrix-{}", process::id()));
+    let staged = dir.join("control.sock");
+    // left by a killed instance which had the same pid
+    let _ = fs::remove_file(&staged);
+    let _ = fs::remove_dir(&dir);
+    DirBuilder::new().mode(0o700).create(&dir)?;
+    let bound = UnixListener::bind(&staged).and_then(|listener| {
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o600))?;
+        fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    let _ = fs::remove_file(&staged);
+    fs::remove_dir(&dir)?;
+    bound
+}
+
+pub struct ControlClient {
+    stream: UnixStream,
+}
+
+impl ControlClient {
+    pub fn connect(path: &Path) -> io::Result<ControlClient> {
+        Ok(ControlClient {
+            stream: UnixStream::connect(path)?,
+        })
+    }
+
+    pub fn send(&mut self, msg: &ControlMessage) -> io::Result<()> {
+        writeln!(self.stream, "{}", msg.encode())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempPath;
+
+    #[test]
+    fn socket_is_private() {
+        let path = TempPath::new("control.sock");
+        let _rx = spawn_control_channel(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // the directory it was bound in is gone
+        assert!(
+            !env::temp_dir()
+                .join(format!(".logmatrix-{}", process::id()))
+                .exists()
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{
+    clock,
+    sources::{InputLine, Severity},
+    text,
+};
+use std::{
+    io::Write,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+use unicode_width::UnicodeWidthStr;
+
+/// the line a cell was taken from, as it was received once redacted
+pub struct Record {
+    pub source: String,
+    pub text: String,
+    pub severity: Option<Severity>,
+    pub received: Instant,
+    time: SystemTime, // of the reception, for the popup
+}
+
+impl Record {
+    pub fn of(line: &InputLine) -> Record {
+        Record {
+            source: line.source.clone(),
+            text: line.text.clone(),
+            severity: line.severity,
+            received: line.received,
+            time: SystemTime::now() - line.received.elapsed(),
+        }
+    }
+
+    // UTC, to the millisecond
+    fn timestamp(&self) -> String {
+        let since_epoch = self.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let seconds = since_epoch.as_secs();
+        let (year, month, day) = clock::civil_date(seconds / 86400);
+        let time = seconds % 86400;
+        format!(
+            "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}.{:03} UTC",
+            time / 3600,
+            time / 60 % 60,
+            time % 60,
+            since_epoch.subsec_millis()
+        )
+    }
+}
+
+/// a box in the middle of the screen with the source, the time and the severity of the
+/// record, its whole text wrapped and the fields parsed out of it
+pub fn draw(out: &mut dyn Write, record: &Record, size: (u16, u16), tab_width: usize) {
+    let (width, height) = (size.0 as usize, size.1 as usize);
+    if width < 8 || height < 4 {
+        return;
+    }
+    let inner = width - 4;
+    let mut lines = vec![
+        format!("source: {}", record.source),
+        format!("received: {}", record.timestamp()),
+    ];
+    if let Some(severity) = record.severity {
+        lines.push(format!(
+            "severity: {}",
+            format!("{severity:?}").to_lowercase()
+        ));
+    }
+    let mut lines: Vec<String> = lines
+        .into_iter()
+        .map(|line| text::truncate(text::sanitize(&line, tab_width, '?'), inner, "…"))
+        .collect();
+    lines.push(String::new());
+    let line = text::sanitize(&record.text, tab_width, '?');
+    lines.extend(text::split(&line, inner));
+    let fields = text::fields(&record.text);
+    if !fields.is_empty() {
+        lines.push(String::new());
+    }
+    for (key, value) in fields {
+        let field = text::sanitize(&format!("{key} = {value}"), tab_width, '?');
+        lines.push(text::truncate(field, inner, "…"));
+    }
+    lines.truncate(height - 2);
+    let used = lines
+        .iter()
+        .map(|line| line.width())
+        .max()
+        .unwrap_or(0)
+        .min(inner);
+    let (left, top) = (
+        (width - used - 2) / 2 + 1,
+        (height - lines.len() - 2) / 2 + 1,
+    );
+    let rule = "─".repeat(used);
+    let _ = write!(out, "\x1b[0m\x1b[{top};{left}H┌{rule}┐");
+    for (row, line) in (top + 1..).zip(&lines) {
+        let padding = " ".repeat(used.saturating_sub(line.width()));
+        let _ = write!(out, "\x1b[{row};{left}H│{line}{padding}│");
+    }
+    let _ = write!(out, "\x1b[{};{left}H└{rule}┘", top + lines.len() + 1);
+}
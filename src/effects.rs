@@ -0,0 +1,63 @@
+use clap::ValueEnum;
+use std::time::Duration;
+
+/// shape of the brightness pulse running over the highlighted cells
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[clap(rename_all = "kebab_case")]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// SGR intensity of a terminal cell
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Intensity {
+    Dim,
+    Normal,
+    Bold,
+}
+
+impl Intensity {
+    pub fn to_ansi(self) -> &'static str {
+        match self {
+            Intensity::Dim => "\x1b[2m",
+            Intensity::Normal => "",
+            Intensity::Bold => "\x1b[1m",
+        }
+    }
+}
+
+/// brightness wave travelling from the first highlighted cell of a message to the last
+/// one, once every period
+pub struct HighlightCurve {
+    pub easing: Easing,
+    pub period: Duration,
+    pub cells: usize,
+}
+
+impl HighlightCurve {
+    pub fn intensity(&self, elapsed: Duration, position: usize) -> Intensity {
+        let time = elapsed.as_secs_f32() / self.period.as_secs_f32().max(f32::EPSILON);
+        let phase = (time - position as f32 / self.cells.max(1) as f32).rem_euclid(1.0);
+        // triangle wave, the top of the pulse in the middle of the period
+        let level = self.easing.apply(1.0 - (2.0 * phase - 1.0).abs());
+        match level {
+            level if level > 2.0 / 3.0 => Intensity::Bold,
+            level if level < 1.0 / 3.0 => Intensity::Dim,
+            _ => Intensity::Normal,
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use regex::Regex;
+use std::{
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+/// the shell command run for the lines matching `pattern`
+#[derive(Clone)]
+pub struct ExecRule {
+    pattern: Regex,
+    command: String,
+}
+
+/// `REGEX:CMD`, split at the first `:` ending a valid regex so `(?:...)` groups are kept.
+/// `{}` in the command stands for the line, `"$1"`
+pub fn parse(raw: &str) -> Result<ExecRule, String> {
+    raw.match_indices(':')
+        .filter(|(at, _)| *at + 1 < raw.len())
+        .find_map(|(at, _)| {
+            Some(ExecRule {
+                pattern: Regex::new(&raw[..at]).ok()?,
+                command: substitute(&raw[at + 1..]),
+            })
+        })
+        .ok_or(format!("expected `REGEX:CMD`, got `{raw}`"))
+}
+
+// `{}` expands to the whole line wherever it is quoted: `"$1"` bare, `$1` between double
+// quotes, and out of single quotes, which expand nothing, for `"$1"` then back in
+fn substitute(command: &str) -> String {
+    let (mut single, mut double, mut escaped) = (false, false, false);
+    let mut substituted = String::with_capacity(command.len());
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        if escaped {
+            escaped = false;
+        } else if c == '{' && chars.peek() == Some(&'}') {
+            chars.next();
+            substituted += match (single, double) {
+                (true, _) => "'\"$1\"'",
+                (_, true) => "$1",
+                _ => "\"$1\"",
+            };
+            continue;
+        } else {
+            match c {
+                '\\' if !single => escaped = true,
+                '\'' if !double => single = !single,
+                '"' if !single => double = !double,
+                _ => {}
+            }
+        }
+        substituted.push(c);
+    }
+    substituted
+}
+
+/// runs the commands of the rules, each at most once per interval and never more than
+/// `max` of them at once. the lines matching past these limits run nothing
+pub struct Executor {
+    rules: Vec<(ExecRule, Option<Instant>)>, // with the last time the command was started
+    running: Vec<Child>,
+    max: usize,
+    interval: Duration,
+}
+
+impl ExecRule {
+    pub fn matches(&self, text: &str) -> bool {
+        self.pattern.is_match(text)
+    }
+}
+
+impl Executor {
+    pub fn new(rules: &[ExecRule], max: usize, interval: Duration) -> Executor {
+        Executor {
+            rules: rules.iter().map(|rule| (rule.clone(), None)).collect(),
+            running: vec![],
+            max,
+            interval,
+        }
+    }
+
+    /// the line is the first argument of the commands, `$1`. it is never part of the
+    /// script the shell parses, whatever it holds and however the command quotes it. the
+    /// caller matched the line against the rules, in their order
+    pub fn on_line(&mut self, line: &str, matches: &[bool]) {
+        self.running
+            .retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+        let now = Instant::now();
+        for ((rule, last), matched) in self.rules.iter_mut().zip(matches) {
+            if self.running.len() >= self.max
+                || last.is_some_and(|last| now.duration_since(last) < self.interval)
+                || !matched
+            {
+                continue;
+            }
+            // their output would be drawn over the matrix
+            let child = Command::new("sh")
+                .args(["-c", &rule.command, "sh", line])
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn();
+            if let Ok(child) = child {
+                self.running.push(child);
+                *last = Some(now);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempPath;
+    use std::fs;
+
+    // the command of the rule run on the line, once it exited
+    fn run(rule: &str, line: &str) {
+        let rule = parse(rule).unwrap();
+        let mut executor = Executor::new(std::slice::from_ref(&rule), 1, Duration::ZERO);
+        executor.on_line(line, &[rule.matches(line)]);
+        for child in &mut executor.running {
+            child.wait().unwrap();
+        }
+    }
+
+    #[test]
+    fn line_is_never_parsed() {
+        let dir = TempPath::dir("exec");
+        let (out, injected) = (dir.join("out"), dir.join("injected"));
+        let touch = format!("touch {}", injected.display());
+        let line = format!("ERR $({touch}) `{touch}` '\"; {touch}");
+        for argument in [
+            r#""{}""#,
+            "'{}'",
+            "{}",
+            r#""$1""#,
+            r#""at {}.""#,
+            "'at {}.'",
+        ] {
+            let _ = fs::remove_file(&out);
+            run(
+                &format!("ERR:printf %s {argument} > {}", out.display()),
+                &line,
+            );
+            assert!(!injected.exists(), "{argument}");
+            let printed = fs::read_to_string(&out).unwrap();
+            let expected = match argument.contains("at ") {
+                true => format!("at {line}."),
+                false => line.clone(),
+            };
+            assert_eq!(printed, expected, "{argument}");
+        }
+    }
+
+    #[test]
+    fn braces_follow_the_quotes() {
+        assert_eq!(substitute("echo {}"), r#"echo "$1""#);
+        assert_eq!(substitute(r#"echo "[{}]""#), r#"echo "[$1]""#);
+        assert_eq!(substitute("echo '[{}]'"), r#"echo '['"$1"']'"#);
+        assert_eq!(substitute(r#"echo "it's {}""#), r#"echo "it's $1""#);
+        assert_eq!(substitute(r"echo \'{}"), r#"echo \'"$1""#);
+        assert_eq!(substitute("echo {x}"), "echo {x}");
+    }
+}
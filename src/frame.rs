@@ -0,0 +1,233 @@
+use crate::{Color, effects::Intensity};
+use std::{fmt::Write, fs, io, path::Path};
+use unicode_width::UnicodeWidthStr;
+
+/// a cell as drawn on the terminal
+#[derive(Clone, PartialEq)]
+pub struct Styled {
+    pub glyph: String,
+    pub color: Color,
+    pub intensity: Intensity,
+}
+
+impl Styled {
+    fn blank() -> Styled {
+        Styled {
+            glyph: " ".to_string(),
+            color: Color::Default,
+            intensity: Intensity::Normal,
+        }
+    }
+
+    // the colors are only set when they differ from the ones of the cell written before,
+    // `style` is what the terminal is left with
+    fn push_ansi(&self, out: &mut String, style: &mut Option<(Color, Intensity)>) {
+        if *style != Some((self.color, self.intensity)) {
+            out.push_str(self.color.to_ansi());
+            out.push_str(self.intensity.to_ansi());
+            *style = Some((self.color, self.intensity));
+        }
+        out.push_str(&self.glyph);
+    }
+}
+
+// back to the default colors after the cells written in `style`
+fn reset(out: &mut String, style: Option<(Color, Intensity)>) {
+    if style.is_some_and(|style| style != (Color::Default, Intensity::Normal)) {
+        out.push_str(Color::Default.to_ansi());
+    }
+}
+
+/// the cells of the last frame, addressed from 1 like the cursor
+#[derive(Clone)]
+pub struct FrameBuffer {
+    rows: Vec<Vec<Styled>>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: u16, height: u16) -> FrameBuffer {
+        FrameBuffer {
+            rows: vec![vec![Styled::blank(); width as usize]; height as usize],
+        }
+    }
+
+    // a double width glyph covers the next cell too, the ones off the screen are dropped
+    pub fn set(&mut self, x: u16, y: u16, cell: Styled) {
+        let (Some(x), Some(y)) = (x.checked_sub(1), y.checked_sub(1)) else {
+            return;
+        };
+        let Some(row) = self.rows.get_mut(y as usize) else {
+            return;
+        };
+        let x = x as usize;
+        let wide = cell.glyph.width() == 2;
+        if let Some(styled) = row.get_mut(x) {
+            *styled = cell;
+        }
+        if wide && let Some(styled) = row.get_mut(x + 1) {
+            styled.glyph.clear();
+        }
+    }
+
+    pub fn rows(&self) -> &[Vec<Styled>] {
+        &self.rows
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        let width = self.rows.first().map_or(0, Vec::len);
+        (width as u16, self.rows.len() as u16)
+    }
+
+    /// the cursor moves and the cells drawing this frame over `shown`, the cells which did
+    /// not change are skipped. the rows up to `repainted` are drawn whole
+    pub fn diff(&self, shown: &FrameBuffer, repainted: u16, out: &mut String) {
+        let mut style = None;
+        for (y, (row, shown)) in (1..).zip(self.rows.iter().zip(&shown.rows)) {
+            // the column the cursor is at after the last cell written on the row
+            let mut cursor = None;
+            for (x, (cell, before)) in (1..).zip(row.iter().zip(shown)) {
+                if y > repainted && cell == before {
+                    continue;
+                }
+                if cursor != Some(x) {
+                    let _ = write!(out, "\x1b[{y};{x}H");
+                }
+                cell.push_ansi(out, &mut style);
+                cursor = Some(x + cell.glyph.width());
+            }
+        }
+        reset(out, style);
+    }
+
+    /// the row as it is printed, with the colors of every run of cells
+    pub fn ansi_row(&self, y: u16) -> String {
+        let Some(row) = y.checked_sub(1).and_then(|y| self.rows.get(y as usize)) else {
+            return String::new();
+        };
+        let (mut out, mut style) = (String::new(), None);
+        for cell in row {
+            cell.push_ansi(&mut out, &mut style);
+        }
+        reset(&mut out, style);
+        out
+    }
+
+    /// the glyphs alone, a line per row
+    pub fn text(&self) -> String {
+        let rows: Vec<String> = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.glyph.as_str()).collect())
+            .collect();
+        rows.join("\n")
+    }
+
+    /// the frame with its colors, as ANSI art
+    pub fn ansi(&self) -> String {
+        let rows: Vec<String> = (1..=self.rows.len() as u16)
+            .map(|y| self.ansi_row(y))
+            .collect();
+        format!("{}\n", rows.join("\n"))
+    }
+
+    /// the frame as a standalone `<pre>`, a span with inline styles per run of cells of the
+    /// same colors, to be pasted into a wiki page or an incident report
+    pub fn html(&self) -> String {
+        let mut html = String::from(
+            "<pre style=\"background:#000;color:#ccc;font-family:monospace;padding:1em\">",
+        );
+        for (y, row) in self.rows.iter().enumerate() {
+            if y > 0 {
+                html.push('\n');
+            }
+            for run in row.chunk_by(|a, b| (a.color, a.intensity) == (b.color, b.intensity)) {
+                let text: String = run.iter().map(|cell| escape(&cell.glyph)).collect();
+                match style(run[0].color, run[0].intensity, false) {
+                    Some(style) => html += &format!("<span style=\"{style}\">{text}</span>"),
+                    None => html += &text,
+                }
+            }
+        }
+        html + "</pre>\n"
+    }
+
+    /// `.txt` files get the glyphs alone, `.html` ones an HTML snippet, the other ones the
+    /// ANSI art
+    pub fn export(&self, path: &Path) -> io::Result<()> {
+        let content = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("txt") => self.text() + "\n",
+            Some("html" | "htm") => self.html(),
+            _ => self.ansi(),
+        };
+        fs::write(path, content)
+    }
+}
+
+/// the cells as the terminal shows them, for a frame to only draw the ones which changed
+#[derive(Default)]
+pub struct Screen {
+    shown: Option<FrameBuffer>, // none to repaint them all
+    painted: String,            // the cells written in the last frame, kept allocated
+}
+
+impl Screen {
+    /// every cell is drawn by the next frame, after the screen was cleared
+    pub fn forget(&mut self) {
+        self.shown = None;
+    }
+
+    /// the cells of the frame which changed since the last one, all of them after the
+    /// screen was cleared or resized. the rows up to `repainted` are drawn whole
+    pub fn paint<W: io::Write + ?Sized>(
+        &mut self,
+        cells: FrameBuffer,
+        repainted: u16,
+        out: &mut W,
+    ) -> io::Result<()> {
+        self.painted.clear();
+        match &self.shown {
+            Some(shown) if shown.size() == cells.size() => {
+                cells.diff(shown, repainted, &mut self.painted)
+            }
+            _ => cells.diff(&cells, cells.size().1, &mut self.painted),
+        };
+        let written = out.write_all(self.painted.as_bytes());
+        self.shown = Some(cells);
+        written
+    }
+}
+
+/// the inline style of a run, none for the default colors. SVG text is painted with `fill`
+pub fn style(color: Color, intensity: Intensity, svg: bool) -> Option<String> {
+    // the xterm palette
+    let color = match color {
+        Color::Black => Some("#000000"),
+        Color::Red => Some("#cd0000"),
+        Color::Green => Some("#00cd00"),
+        Color::Yellow => Some("#cdcd00"),
+        Color::Blue => Some("#0000ee"),
+        Color::Magenta => Some("#cd00cd"),
+        Color::Cyan => Some("#00cdcd"),
+        Color::White => Some("#e5e5e5"),
+        Color::Default => None,
+    };
+    let intensity = match intensity {
+        Intensity::Dim if svg => Some("fill-opacity:0.6"),
+        Intensity::Dim => Some("opacity:0.6"),
+        Intensity::Normal => None,
+        Intensity::Bold => Some("font-weight:bold"),
+    };
+    let styles: Vec<String> = color
+        .map(|color| format!("{}:{color}", if svg { "fill" } else { "color" }))
+        .into_iter()
+        .chain(intensity.map(str::to_string))
+        .collect();
+    (!styles.is_empty()).then(|| styles.join(";"))
+}
+
+pub fn escape(glyph: &str) -> String {
+    glyph
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
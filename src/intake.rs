@@ -0,0 +1,436 @@
+use crate::{
+    Annotation, Color, ColumnMat, DEMO_HINT, Matrix, RUNNING,
+    args::ColumnSpill,
+    column::TRACE_LAST_GLYPH,
+    control::ControlMessage,
+    detail::Record,
+    format_uptime,
+    sources::{InputLine, Severity},
+    text,
+    workers::Prepared,
+};
+use std::{
+    fs,
+    io::Write,
+    sync::{Arc, atomic::Ordering, mpsc::TryRecvError},
+    time::{Duration, Instant},
+};
+
+// how long the stages of a traced line stay on screen with --trace-overlay
+const TRACE_OVERLAY_DURATION: Duration = Duration::from_secs(3);
+const FILLER_GLYPHS: &[char] = &[
+    'ｱ', 'ｲ', 'ｳ', 'ｴ', 'ｵ', 'ｶ', 'ｷ', 'ｸ', 'ｹ', 'ｺ', 'ｻ', 'ｼ', 'ｽ', 'ｾ', 'ｿ', 'ﾀ', 'ﾁ', 'ﾂ', 'ﾃ',
+    'ﾄ', 'ﾅ', 'ﾆ', 'ﾇ', 'ﾈ', 'ﾉ', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'Z', ':', '.',
+];
+// chance per frame for an idle column to get a filler line in demo mode
+const FILLER_RATE: f32 = 0.03;
+
+impl Matrix {
+    pub(crate) fn update_inputs(&mut self) -> Option<()> {
+        if let Some(replay) = &mut self.replay {
+            if replay.is_over(self.frame) {
+                return None;
+            }
+            for line in replay.lines_at(self.frame) {
+                self.receive(line);
+            }
+        }
+        // with --queue-size the columns take no more lines than the queue holds, the next
+        // ones wait in the queue until they have room
+        let waiting: usize = self
+            .columns
+            .iter()
+            .map(|col| col.invisible_cache.len())
+            .sum();
+        let mut room =
+            (self.opt.queue_size).map_or(usize::MAX, |size| size.saturating_sub(waiting));
+        let mut found_end = false;
+        while !found_end {
+            if room == 0 {
+                found_end = true;
+                break;
+            }
+            match self.input_channel.try_recv() {
+                Ok(key) => {
+                    room -= 1;
+                    self.receive(key)
+                }
+                Err(TryRecvError::Empty) => found_end = true,
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+        self.update_control();
+        self.update_self_report();
+        self.update_idle();
+        self.update_demo();
+        self.update_alerts();
+        // the control socket can still feed lines once stdin is closed
+        // and a replay lasts as long as the captured run
+        if !found_end && self.control_channel.is_none() && self.replay.is_none() {
+            return None;
+        }
+        Some(())
+    }
+
+    pub(crate) fn receive(&mut self, mut line: InputLine) {
+        // the secrets are masked before the text goes anywhere but the files keeping the
+        // lines as they were received
+        let prepared = match line.prepared.take() {
+            Some(prepared) => *prepared,
+            None => self.prepare.prepare(&mut line),
+        };
+        let matches = &prepared.matches;
+        if let Some(received) = &prepared.received {
+            self.record_received(received, matches.match_out);
+        }
+        if self.demo {
+            self.leave_demo();
+        }
+        self.last_input = Instant::now();
+        self.counters.received.fetch_add(1, Ordering::Relaxed);
+        self.stats.record(&line);
+        let severe = matches!(line.severity, Some(Severity::Critical | Severity::Error));
+        if let Some(ticker) = &mut self.ticker
+            && (severe || matches.ticker)
+        {
+            ticker.show(&prepared.text);
+        }
+        if self.opt.pager_key.is_some() {
+            if self.history.len() == self.opt.history {
+                self.history.pop_front();
+            }
+            self.history.push_back(prepared.text.clone());
+            if let Some(pager) = &mut self.pager {
+                pager.appended();
+            }
+        }
+        for (alert, matched) in self.alerts.iter_mut().zip(&matches.alerts) {
+            alert.record(*matched, line.received);
+        }
+        #[cfg(feature = "notify")]
+        if let Some(notifier) = &mut self.notifier
+            && matches.notify
+        {
+            notifier.notify(&line.text);
+        }
+        if let Some(executor) = &mut self.executor {
+            executor.on_line(&line.text, &matches.exec);
+        }
+        if matches.bell
+            && self
+                .last_bell
+                .is_none_or(|last| last.elapsed() >= self.opt.bell_cooldown)
+        {
+            self.last_bell = Some(Instant::now());
+            let _ = write!(self.out, "\x07");
+        }
+        if matches.fail_on {
+            self.failures += 1;
+        }
+        if let Some(report) = &mut self.report {
+            let severe = severe || line.severity == Some(Severity::Warning);
+            report.record_line(&line, severe || matches.alerts.contains(&true));
+        }
+        if let Some(capture) = &mut self.capture {
+            capture.record_line(self.frame, &line);
+        }
+        let trace = match (&mut self.tracer, matches.trace) {
+            (Some(tracer), true) => Some(tracer.start(&line, self.frame)),
+            _ => None,
+        };
+        self.check_sequence(&line, prepared.seq, trace);
+        self.push_line(line, prepared, trace);
+    }
+
+    // --tee and --match-out get the text verbatim
+    fn record_received(&mut self, text: &str, matched: bool) {
+        if let Some(tee) = &mut self.tee {
+            let _ = writeln!(tee, "{text}");
+        }
+        if let Some(out) = &mut self.match_out
+            && matched
+        {
+            let _ = writeln!(out, "{text}");
+        }
+    }
+
+    // a marker falls in place of the lines lost between 2 sequence numbers
+    fn check_sequence(&mut self, line: &InputLine, seq: Option<u64>, trace: Option<u64>) {
+        let Some(seq) = seq else {
+            return;
+        };
+        // a sequence going backward is a restarted producer, not a gap
+        let Some(last) = self.last_seq.insert(line.source.clone(), seq) else {
+            self.trace(trace, || format!("first sequence number {seq}"));
+            return;
+        };
+        // a source can send any number, u64::MAX included
+        if last.checked_add(1).is_some_and(|next| seq > next) {
+            let missing = seq - last - 1;
+            self.counters.missing.fetch_add(missing, Ordering::Relaxed);
+            let marker = format!("missing {missing} lines from {}", line.source);
+            let marker = text::sanitize(&marker, self.opt.tab_width, self.opt.placeholder);
+            self.assign_line(marker, None, 1, Some(Color::Red), f64::INFINITY, None, None);
+        }
+        self.trace(trace, || format!("sequence number {seq} after {last}"));
+    }
+
+    fn push_line(&mut self, mut line: InputLine, prepared: Prepared, trace: Option<u64>) {
+        let (choices, pause_on) = (prepared.choices, prepared.matches.pause_on);
+        let score = self
+            .scorer
+            .as_mut()
+            .map_or(0., |scorer| scorer.score(&line, self.frame));
+        let record = Some(Arc::new(Record::of(&line)));
+        for notification in &prepared.notifications {
+            let _ = write!(self.out, "{}", notification.to_ansi());
+        }
+        if prepared.text != line.text {
+            self.trace(trace, || format!("sanitized into: {}", prepared.text));
+        }
+        line.text = prepared.text;
+        let mut transforms = std::mem::take(&mut self.transforms);
+        let mut transformed = Some(line);
+        for transform in transforms.iter_mut() {
+            let Some(line) = transformed.take() else {
+                break;
+            };
+            let before = trace.map(|_| line.text.clone());
+            transformed = transform.apply(line);
+            match &transformed {
+                None => self.trace(trace, || format!("dropped by {}", transform.name())),
+                Some(line) if before.is_some_and(|before| before != line.text) => self
+                    .trace(trace, || {
+                        format!("{} into: {}", transform.name(), line.text)
+                    }),
+                Some(_) => {}
+            }
+        }
+        self.transforms = transforms;
+        let Some(InputLine {
+            text: line,
+            mut color,
+            column,
+            ..
+        }) = transformed
+        else {
+            return;
+        };
+        if !self.paused
+            && self.pause_on.is_none()
+            && pause_on
+            && let Some(record) = &record
+        {
+            self.pause_on = Some(record.clone());
+            color = Some(self.opt.highlight_color);
+            self.trace(trace, || "pauses the animation once displayed".to_string());
+        }
+        self.trace(trace, || {
+            let color = color.map_or("of the column".to_string(), |color| format!("{color:?}"));
+            format!("{choices} column choices, score {score}, color {color}")
+        });
+        match self.opt.max_line_length {
+            Some(max) if self.opt.split_long_lines => {
+                let chunks = text::split(&line, max);
+                self.trace(trace, || format!("split into {} chunks", chunks.len()));
+                for chunk in chunks {
+                    let record = record.clone();
+                    self.assign_line(chunk, column, choices, color, score, trace, record);
+                }
+            }
+            Some(max) => {
+                let truncated = text::truncate(line.clone(), max, &self.opt.ellipsis);
+                if truncated != line {
+                    self.trace(trace, || format!("truncated to {max} characters"));
+                }
+                self.assign_line(truncated, column, choices, color, score, trace, record)
+            }
+            None => self.assign_line(line, column, choices, color, score, trace, record),
+        }
+    }
+
+    // the least busy of `choices` random columns gets the line
+    #[allow(clippy::too_many_arguments)]
+    fn assign_line(
+        &mut self,
+        line: String,
+        column: Option<usize>,
+        choices: usize,
+        color: Option<Color>,
+        score: f64,
+        trace: Option<u64>,
+        record: Option<Arc<Record>>,
+    ) {
+        let mut w_idx = match column.filter(|column| *column < self.columns.len()) {
+            Some(column) => column,
+            None => (0..choices)
+                .map(|_| self.column_rng.index(self.columns.len()))
+                .min_by_key(|idx| self.columns[*idx].backlog())
+                .unwrap_or(0),
+        };
+        if let Some(cap) = self.opt.column_cache
+            && self.columns[w_idx].invisible_cache.len() as u64 >= cap
+        {
+            match self.spill(w_idx, cap, trace) {
+                Some(column) => w_idx = column,
+                None => return,
+            }
+        }
+        let waiting = self.columns[w_idx].invisible_cache.len();
+        self.trace(trace, || {
+            format!("queued in column {w_idx} behind {waiting} lines")
+        });
+        self.columns[w_idx].add_line(line, color, score, trace, record);
+    }
+
+    // the column the line given to the full `column` goes to, none when it is merged
+    fn spill(&mut self, column: usize, cap: u64, trace: Option<u64>) -> Option<usize> {
+        match self.opt.column_spill {
+            ColumnSpill::Merge => {
+                self.columns[column].merge_newest();
+                self.trace(trace, || {
+                    format!("merged into the newest line of column {column}")
+                });
+                return None;
+            }
+            ColumnSpill::Reassign => {
+                let emptier = (0..self.columns.len())
+                    .min_by_key(|idx| self.columns[*idx].invisible_cache.len())
+                    .filter(|idx| (self.columns[*idx].invisible_cache.len() as u64) < cap);
+                if let Some(emptier) = emptier {
+                    self.trace(trace, || format!("column {column} full, reassigned"));
+                    return Some(emptier);
+                }
+            }
+            ColumnSpill::DropOldest => {}
+        }
+        if let Some(dropped) = self.columns[column].drop_oldest() {
+            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+            self.trace(dropped.trace, || {
+                format!("dropped by the full column {column}")
+            });
+        }
+        Some(column)
+    }
+
+    // one more stage of a traced line, in the debug log and on screen if asked
+    pub(crate) fn trace(&mut self, trace: Option<u64>, stage: impl FnOnce() -> String) {
+        let (Some(id), Some(tracer)) = (trace, self.tracer.as_mut()) else {
+            return;
+        };
+        let stage = stage();
+        tracer.log(id, self.frame, &stage);
+        if self.opt.trace_overlay {
+            self.annotations.push(Annotation {
+                text: format!("#{id} {stage}"),
+                expires: Some(Instant::now() + TRACE_OVERLAY_DURATION),
+            });
+        }
+    }
+
+    // what the columns did with the traced lines during the last tick
+    pub(crate) fn trace_columns(&mut self) {
+        for column in 0..self.columns.len() {
+            let visible = self.columns[column].visible_line.data.len();
+            for (id, event) in std::mem::take(&mut self.columns[column].trace_events) {
+                self.trace(Some(id), || match event {
+                    TRACE_LAST_GLYPH => format!("{event} in column {column}, {visible} ticks left"),
+                    _ => format!("{event} in column {column}"),
+                });
+            }
+        }
+    }
+
+    fn update_self_report(&mut self) {
+        let Some(period) = self.opt.self_report else {
+            return;
+        };
+        if self.last_report.elapsed() < period {
+            return;
+        }
+        self.last_report = Instant::now();
+        let backlog: usize = self.columns.iter().map(ColumnMat::backlog).sum();
+        let report = format!(
+            "logmatrix uptime={} rss={} received={} dropped={} missing={} late={} backlog={backlog}B",
+            format_uptime(self.started.elapsed()),
+            resident_memory().unwrap_or_else(|| "?".to_string()),
+            self.counters.received.load(Ordering::Relaxed),
+            self.counters.dropped.load(Ordering::Relaxed),
+            self.counters.missing.load(Ordering::Relaxed),
+            self.counters.late_frames.load(Ordering::Relaxed),
+        );
+        let mut line = InputLine::new("logmatrix", report);
+        let prepared = self.prepare.prepare(&mut line);
+        self.push_line(line, prepared, None);
+    }
+
+    pub(crate) fn update_demo(&mut self) {
+        if !self.demo {
+            return;
+        }
+        for idx in 0..self.columns.len() {
+            if !self.columns[idx].invisible_cache.is_empty() || !self.filler_rng.chance(FILLER_RATE)
+            {
+                continue;
+            }
+            let length = 5 + self.filler_rng.index(25);
+            let filler: String = (0..length)
+                .map(|_| FILLER_GLYPHS[self.filler_rng.index(FILLER_GLYPHS.len())])
+                .collect();
+            self.columns[idx].add_filler(filler);
+        }
+    }
+
+    // the filler falls again while no line is received, without the hint
+    pub(crate) fn update_idle(&mut self) {
+        if !self.demo
+            && self
+                .opt
+                .idle_after
+                .is_some_and(|period| self.last_input.elapsed() >= period)
+        {
+            self.demo = true;
+        }
+    }
+
+    // the filler and the hint make room for the real lines
+    fn leave_demo(&mut self) {
+        self.demo = false;
+        self.annotations
+            .retain(|annotation| annotation.text != DEMO_HINT);
+        for col in self.columns.iter_mut() {
+            col.drop_fillers();
+        }
+    }
+
+    fn update_control(&mut self) {
+        let messages: Vec<ControlMessage> = match &self.control_channel {
+            Some(control) => control.try_iter().collect(),
+            None => return,
+        };
+        for msg in messages {
+            match msg {
+                ControlMessage::Line(line) => self.receive(InputLine::new("control", line)),
+                // anyone allowed on the socket must not be able to write to the terminal
+                ControlMessage::Sticky { text, duration } => self.annotations.push(Annotation {
+                    text: text::sanitize(&text, self.opt.tab_width, self.opt.placeholder),
+                    expires: Instant::now().checked_add(duration),
+                }),
+                ControlMessage::Quit => RUNNING.store(false, Ordering::SeqCst),
+            }
+        }
+    }
+}
+
+fn resident_memory() -> Option<String> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let kilobytes: u64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(format!("{:.1}MiB", kilobytes as f64 / 1024.))
+}
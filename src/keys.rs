@@ -0,0 +1,99 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read},
+    mem::MaybeUninit,
+    os::{fd::AsRawFd, unix::fs::OpenOptionsExt},
+};
+
+/// keys pressed on the controlling terminal, stdin usually being the piped logs.
+/// the terminal stays line buffered and echoing until the keyboard is dropped
+pub struct Keyboard {
+    tty: File,
+    cooked: libc::termios,
+}
+
+impl Keyboard {
+    pub fn open() -> io::Result<Keyboard> {
+        let tty = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open("/dev/tty")?;
+        let mut cooked = MaybeUninit::<libc::termios>::uninit();
+        // SAFETY: the descriptor is open and the struct is filled on success
+        if unsafe { libc::tcgetattr(tty.as_raw_fd(), cooked.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: initialized by tcgetattr above
+        let cooked = unsafe { cooked.assume_init() };
+        let keyboard = Keyboard { tty, cooked };
+        keyboard.resume()?;
+        Ok(keyboard)
+    }
+
+    /// keys pressed since the last call, never blocks
+    pub fn pressed(&mut self) -> Vec<u8> {
+        let mut keys = vec![];
+        let mut buffer = [0u8; 64];
+        while let Ok(read @ 1..) = self.tty.read(&mut buffer) {
+            keys.extend_from_slice(&buffer[..read]);
+        }
+        keys
+    }
+
+    /// give the terminal back as it was, for a command run in the foreground
+    pub fn suspend(&self) -> io::Result<()> {
+        self.set_mode(&self.cooked)
+    }
+
+    /// keys are read one by one without echo, Ctrl-C still interrupts
+    pub fn resume(&self) -> io::Result<()> {
+        let mut raw = self.cooked;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+        raw.c_cc[libc::VMIN] = 0;
+        raw.c_cc[libc::VTIME] = 0;
+        self.set_mode(&raw)
+    }
+
+    fn set_mode(&self, mode: &libc::termios) -> io::Result<()> {
+        // SAFETY: the descriptor is open and the mode is a valid termios
+        match unsafe { libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSANOW, mode) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+}
+
+impl Drop for Keyboard {
+    fn drop(&mut self) {
+        let _ = self.suspend();
+    }
+}
+
+/// the cells clicked with the left button, counted from 1, taken out of the keys. the
+/// terminal reports them as `ESC [ < button ; column ; row M` in the SGR encoding
+pub fn take_clicks(keys: &mut Vec<u8>) -> Vec<(u16, u16)> {
+    let mut clicks = vec![];
+    let mut rest = vec![];
+    let mut remaining = &keys[..];
+    while let Some(start) = remaining.windows(3).position(|window| window == b"\x1b[<") {
+        rest.extend_from_slice(&remaining[..start]);
+        let report = &remaining[start + 3..];
+        let Some(end) = report.iter().position(|byte| matches!(byte, b'M' | b'm')) else {
+            remaining = &[];
+            break;
+        };
+        let fields: Vec<u16> = String::from_utf8_lossy(&report[..end])
+            .split(';')
+            .filter_map(|field| field.parse().ok())
+            .collect();
+        if let [0, column, row] = fields[..]
+            && report[end] == b'M'
+        {
+            clicks.push((column, row));
+        }
+        remaining = &report[end + 1..];
+    }
+    rest.extend_from_slice(remaining);
+    *keys = rest;
+    clicks
+}
@@ -0,0 +1,1778 @@
+//! the log lines raining down the terminal like in The Matrix, embeddable with [`Config`]
+//! and [`Matrix`]
+
+mod alert;
+mod args;
+mod cast;
+mod cgroup;
+mod clock;
+mod column;
+mod commands;
+mod config;
+mod control;
+mod detail;
+mod effects;
+mod exec;
+mod frame;
+mod intake;
+mod keys;
+#[cfg(feature = "notify")]
+mod notify;
+mod pacing;
+mod pager;
+mod painter;
+#[cfg(feature = "wasm")]
+mod plugin;
+mod realtime;
+mod render;
+mod renderer;
+mod report;
+mod repro;
+mod rng;
+mod scoring;
+#[cfg(feature = "lua")]
+mod script;
+mod serve;
+mod sources;
+#[cfg(feature = "ssh")]
+mod ssh;
+mod stats;
+mod term;
+#[cfg(test)]
+mod testing;
+mod text;
+mod ticker;
+mod tmux;
+mod trace;
+mod transform;
+mod workers;
+
+#[cfg(feature = "ssh")]
+pub use args::SshArgs;
+pub use args::{Args, Color, Direction, RecordArgs, RenderArgs, ReplayArgs, SendArgs, ServeArgs};
+pub use column::ColumnMat;
+pub use commands::{
+    record_command, render_command, replay_command, reset_command, run, send_command,
+};
+pub use config::Config;
+pub use renderer::{MemoryRenderer, Renderer, Terminal};
+pub use serve::serve;
+pub use sources::{
+    FileSource, Generator, InputLine, LineReceiver, LineSender, QueuePolicy, Severity, Source,
+    Sources, Stdin,
+};
+#[cfg(feature = "ssh")]
+pub use ssh::ssh;
+pub use transform::{Filter, Sanitize, Transform};
+
+use alert::RateWindow;
+use args::{
+    SOURCE_PALETTE, SnapshotFormat, SourceColor, SpiralTiles, StatusBar, THEMES, Weight,
+    source_colors,
+};
+use clap::ValueEnum;
+use column::Cell;
+use control::ControlMessage;
+use detail::Record;
+use effects::{HighlightCurve, Intensity};
+use exec::Executor;
+use frame::{FrameBuffer, Screen, Styled};
+use keys::Keyboard;
+#[cfg(feature = "notify")]
+use notify::Notifier;
+use pacing::FramePacer;
+use pager::Pager;
+use painter::Painter;
+use render::Clip;
+use report::SessionReport;
+use repro::{ReproCapture, ReproReplay};
+use rng::{Jitter, RngService};
+use scoring::{Scorer, ScorerKind};
+use serve::Feed;
+use stats::Stats;
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{self, File, OpenOptions},
+    io::{self, IsTerminal, LineWriter, Write},
+    path::{Path, PathBuf},
+    process::{self, Stdio, exit},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::Receiver,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use term::TermMode;
+use ticker::Ticker;
+use trace::Tracer;
+use unicode_width::UnicodeWidthStr;
+use workers::Prepare;
+
+// cleared by the Ctrl-C handler, the main loop stops on the next frame
+static RUNNING: AtomicBool = AtomicBool::new(true);
+// set while a command runs in the foreground, Ctrl-C is then meant for it
+static HANDED_OFF: AtomicBool = AtomicBool::new(false);
+
+// time between 2 screens of characters with --reduced-motion
+const PAGE_PERIOD: Duration = Duration::from_secs(3);
+// how long a failed handoff command stays on screen
+const HANDOFF_FAILURE_DURATION: Duration = Duration::from_secs(5);
+// how long the path of a snapshot stays on screen
+const SNAPSHOT_NOTICE_DURATION: Duration = Duration::from_secs(2);
+// how long the colors picked with the color keys stay on screen
+const COLOR_NOTICE_DURATION: Duration = Duration::from_secs(2);
+// banners over the animation paused by --pause-on
+const PAUSE_PREFIX: &str = "paused on: ";
+const PAUSE_HINT: &str = "press a key to resume";
+// how long the confirmation of a copy stays on screen
+const COPY_NOTICE_DURATION: Duration = Duration::from_secs(2);
+// the screen stays in reverse video when an --alert fires
+const ALERT_FLASH_DURATION: Duration = Duration::from_millis(300);
+// the banner naming the --alert which fired
+const ALERT_NOTICE_DURATION: Duration = Duration::from_secs(5);
+const MATRIX_MODES: [TermMode; 2] = [TermMode::AltScreen, TermMode::HiddenCursor];
+const MOUSE_MODES: [TermMode; 3] = [TermMode::AltScreen, TermMode::HiddenCursor, TermMode::Mouse];
+
+/// the counts of the session, shared with the reader threads
+#[derive(Default)]
+pub struct Counters {
+    received: AtomicU64,
+    dropped: AtomicU64,
+    queue_dropped: AtomicU64, // by --queue-policy
+    missing: AtomicU64,       // gaps in the sequence numbers
+    late_frames: AtomicU64,
+}
+
+struct Annotation {
+    text: String,
+    expires: Option<Instant>, // pinned until removed when none
+}
+
+const DEMO_HINT: &str = "pipe logs into logmatrix, e.g. `journalctl -f | logmatrix`";
+
+pub struct Matrix {
+    width: u16,
+    height: u16,
+    columns: Vec<ColumnMat>,
+    posible_positions: Vec<Vec<(u16, u16)>>, // one list per spiral
+    opt: Args,
+    input_channel: LineReceiver,
+    control_channel: Option<Receiver<ControlMessage>>,
+    annotations: Vec<Annotation>,
+    demo: bool,
+    filler_rng: Jitter,
+    transforms: Vec<Box<dyn Transform>>,
+    prepare: Prepare, // the lines which did not go through the --workers
+    last_seq: HashMap<String, u64>, // per source
+    counters: Arc<Counters>,
+    started: Instant,
+    last_report: Instant,
+    column_rng: Jitter,
+    speed_rng: Jitter,
+    glitch_rng: Jitter,
+    frame: u64,
+    capture: Option<ReproCapture>,
+    report: Option<SessionReport>,
+    tee: Option<LineWriter<File>>,
+    match_out: Option<LineWriter<File>>, // the lines matching `--match`
+    executor: Option<Executor>,
+    last_bell: Option<Instant>,
+    failures: u64, // lines matching --fail-on
+    stats: Stats,
+    stats_shown: bool,
+    ticker: Option<Ticker>,    // of --error-ticker
+    last_input: Instant,       // for --idle-after
+    clips: Vec<Box<dyn Clip>>, // the off-screen renders of `render`
+    buffer: FrameBuffer,       // the cells of the last frame, for the captures
+    screen: Screen,            // the cells as drawn by the last frame
+    annotated_rows: u16,       // covered by banners in the last frame, repainted whole
+    painter: Option<Painter>,  // drawing the frames on a thread of its own
+    on_terminal: bool,         // no renderer was given, the painter is spawned by the loop
+    replay: Option<ReproReplay>,
+    spiral_coef: f32,
+    highlight_curve: Option<HighlightCurve>,
+    cpu_cap: Option<f64>, // cores of quota when the frame rate is lowered to fit in it
+    frame_period: Duration,
+    render_time: Duration, // of the last frame, before the sleep
+    average_period: f64,   // seconds, smoothed over the last frames for the HUD
+    scorer: Option<Box<dyn Scorer>>,
+    tracer: Option<Tracer>,
+    keyboard: Option<Keyboard>,
+    history: VecDeque<String>, // the lines received, for the pager
+    pager: Option<Pager>,      // shown instead of the animation
+    detail: Option<(Arc<Record>, usize)>, // popup of a clicked cell, over its frozen column
+    pause_on: Option<Arc<Record>>, // line of --pause-on the animation pauses after
+    paused: bool,
+    alerts: Vec<RateWindow>,
+    flash_until: Option<Instant>, // the screen is in reverse video until then
+    #[cfg(feature = "notify")]
+    notifier: Option<Notifier>,
+    out: Box<dyn Renderer>,
+}
+
+impl Matrix {
+    pub fn new(opt: Args) -> io::Result<Matrix> {
+        let sources = Sources::from_args(&opt).map_err(context("could not open the inputs"))?;
+        Matrix::with_sources(opt, sources)
+    }
+
+    /// the inputs of the options and the ones added by the caller. stdin is read unless
+    /// other inputs are given in the options or it is turned off. the frames are drawn on
+    /// the terminal unless a renderer is given with `with_renderer`
+    pub fn with_sources(mut opt: Args, sources: Sources) -> io::Result<Matrix> {
+        // drawn once, for the capture to replay the same streams
+        let seed = RngService::new(opt.seed, opt.jitter).seed();
+        opt.seed = Some(seed);
+        let capture = opt
+            .repro
+            .clone()
+            .map(|path| ReproCapture::new(path, seed))
+            .transpose()
+            .map_err(context("could not start the reproduction bundle"))?;
+        // nothing is piped in, fill the screen until the user types lines
+        let demo = sources.reads_stdin_only() && io::stdin().is_terminal();
+        // the palette of the new sources is shared with the workers
+        let prepare = Prepare::new(&opt, sources.colors_new_sources());
+        let counters = Arc::new(Counters::default());
+        let mut input_channel = sources
+            .spawn(&counters)
+            .map_err(context("could not open the inputs"))?;
+        if opt.workers > 0 {
+            let count = opt.workers;
+            input_channel = workers::spawn_pool(
+                input_channel,
+                count,
+                &prepare,
+                opt.queue_size,
+                counters.clone(),
+            );
+        }
+        let control_channel = opt
+            .control_socket
+            .as_ref()
+            .map(|path| control::spawn_control_channel(path))
+            .transpose()
+            .map_err(context("could not open the control socket"))?;
+        let annotations = match demo {
+            true => vec![Annotation {
+                text: DEMO_HINT.to_string(),
+                expires: None,
+            }],
+            false => vec![],
+        };
+        let keys = opt.handoff_cmd.is_some()
+            || opt.snapshot_out.is_some()
+            || opt.snapshot_dir.is_some()
+            || opt.direction_key.is_some()
+            || opt.color_key.is_some()
+            || opt.highlight_key.is_some()
+            || opt.theme_key.is_some()
+            || opt.mouse
+            || opt.pager_key.is_some()
+            || opt.stats_key.is_some()
+            || opt.pause_on.is_some();
+        // the lines of the demo are typed on the terminal the keys would be read from, the
+        // reader of stdin keeps it for itself
+        let keyboard = (keys && !demo)
+            .then(Keyboard::open)
+            .transpose()
+            .map_err(context("could not read the keyboard"))?;
+        let report = opt.report.clone().map(SessionReport::new);
+        let tee = opt
+            .tee
+            .as_ref()
+            .map(|path| append_to(path))
+            .transpose()
+            .map_err(context("could not open the tee file"))?;
+        let match_out = opt
+            .match_out
+            .as_ref()
+            .map(|path| append_to(path))
+            .transpose()
+            .map_err(context("could not open the match output"))?;
+        let tracer = opt
+            .trace_line
+            .as_ref()
+            .map(|_| Tracer::open(&opt.debug_log))
+            .transpose()
+            .map_err(context("could not open the debug log"))?;
+
+        let out = Box::new(Terminal::new().with_mouse(opt.mouse));
+        let mut mat = Matrix::with_output(opt, input_channel, counters, out)?;
+        mat.on_terminal = true;
+        mat.prepare = prepare;
+        mat.capture = capture;
+        mat.control_channel = control_channel;
+        mat.demo = demo;
+        mat.annotations = annotations;
+        mat.keyboard = keyboard;
+        mat.report = report;
+        mat.tee = tee;
+        mat.match_out = match_out;
+        mat.executor = (!mat.opt.exec_on.is_empty())
+            .then(|| Executor::new(&mat.opt.exec_on, mat.opt.exec_max, mat.opt.exec_interval));
+        mat.tracer = tracer;
+        #[cfg(feature = "notify")]
+        {
+            let interval = mat.opt.notify_interval;
+            mat.notifier = mat.opt.notify.as_ref().map(|_| Notifier::spawn(interval));
+        }
+        Ok(mat)
+    }
+
+    // the frames are drawn to a client of `serve` at the size it negotiated
+    fn remote(opt: Args, feed: Feed, out: Box<dyn Renderer>) -> io::Result<Matrix> {
+        let counters = Arc::new(Counters::default());
+        let mut mat = Matrix::with_output(opt, feed.lines, counters, out)?;
+        mat.prepare = Prepare::new(&mat.opt, feed.new_sources);
+        // sized by the client rather than by the local terminal
+        mat.update_mat();
+        Ok(mat)
+    }
+
+    // without the control socket, the keyboard, the captures nor the demo, for the
+    // caller to set up
+    fn with_output(
+        opt: Args,
+        input_channel: LineReceiver,
+        counters: Arc<Counters>,
+        out: Box<dyn Renderer>,
+    ) -> io::Result<Matrix> {
+        let (width, height) = out.size();
+        let columns = Matrix::get_columns(width, height, &opt);
+        let randomness = RngService::new(opt.seed, opt.jitter);
+        let transforms = transform::chain(&opt)?;
+        let prepare = Prepare::new(&opt, false);
+        // in a constrained container, as fast as the quota allows and without the effects
+        let cpu_cap = cgroup::cpu_limit().filter(|cores| !opt.no_cpu_cap && *cores < 1.);
+        let frame_period = Duration::from_millis(opt.frequency)
+            .max(cpu_cap.map_or(Duration::ZERO, cgroup::min_frame_period));
+        let highlight_curve = Matrix::highlight_curve(&opt, cpu_cap);
+        // the one of the script comes with its step of the chain
+        let scorer = match opt.scorer.map(ScorerKind::scorer) {
+            Some(None) => Some(
+                transforms
+                    .iter()
+                    .find_map(|transform| transform.scorer())
+                    .ok_or_else(|| {
+                        io::Error::other("--scorer script: the script has no on_score function")
+                    })?,
+            ),
+            scorer => scorer.flatten(),
+        };
+        let spiral_coef = 100.;
+        let ticker = opt.error_ticker.is_some().then(Ticker::default);
+        let alerts = opt.alerts.iter().cloned().map(RateWindow::new).collect();
+
+        let mut mat = Matrix {
+            width,
+            height,
+            columns,
+            posible_positions: vec![],
+            opt,
+            column_rng: randomness.stream("columns"),
+            speed_rng: randomness.stream("speed"),
+            glitch_rng: randomness.stream("glitches"),
+            input_channel,
+            control_channel: None,
+            annotations: vec![],
+            demo: false,
+            filler_rng: randomness.stream("filler"),
+            transforms,
+            prepare,
+            last_seq: HashMap::new(),
+            counters,
+            started: Instant::now(),
+            last_report: Instant::now(),
+            frame: 0,
+            capture: None,
+            report: None,
+            tee: None,
+            match_out: None,
+            executor: None,
+            last_bell: None,
+            failures: 0,
+            stats: Stats::new(),
+            stats_shown: false,
+            ticker,
+            last_input: Instant::now(),
+            clips: vec![],
+            buffer: FrameBuffer::new(width, height),
+            screen: Screen::default(),
+            annotated_rows: 0,
+            painter: None,
+            on_terminal: false,
+            replay: None,
+            spiral_coef,
+            highlight_curve,
+            cpu_cap,
+            frame_period,
+            render_time: Duration::ZERO,
+            average_period: frame_period.as_secs_f64(),
+            scorer,
+            tracer: None,
+            keyboard: None,
+            history: VecDeque::new(),
+            pager: None,
+            detail: None,
+            pause_on: None,
+            paused: false,
+            alerts,
+            flash_until: None,
+            #[cfg(feature = "notify")]
+            notifier: None,
+            out,
+        };
+        mat.spiral_coord_create();
+        Ok(mat)
+    }
+
+    // input and terminal size come from the bundle instead
+    fn with_replay(mut self, replay: ReproReplay) -> Matrix {
+        self.replay = Some(replay);
+        self
+    }
+
+    /// the step is added at the end of the chain the incoming lines go through
+    pub fn with_transform(mut self, transform: impl Transform + 'static) -> Matrix {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    /// the frames are drawn by the renderer rather than on the terminal
+    pub fn with_renderer(mut self, renderer: Box<dyn Renderer>) -> Matrix {
+        self.out = renderer;
+        self.on_terminal = false;
+        self
+    }
+
+    fn get_spiral_length(height: u16, width: u16) -> usize {
+        ((height + width) * 2) as usize
+    }
+
+    // spirals are as wide as high once the x axis is stretched
+    fn get_spiral_tiles(width: u16, height: u16, tiles: SpiralTiles) -> u16 {
+        match tiles {
+            SpiralTiles::Count(count) => count.min(width.max(1)),
+            SpiralTiles::Auto => (width / (height.max(1) * 2)).max(1),
+        }
+    }
+
+    // left edge and width of a spiral
+    fn get_tile(width: u16, tiles: u16, index: u16) -> (u16, u16) {
+        let edge = |index: u16| (width as u32 * index as u32 / tiles as u32) as u16;
+        (edge(index), edge(index + 1) - edge(index))
+    }
+
+    fn get_columns(width: u16, height: u16, opt: &Args) -> Vec<ColumnMat> {
+        match opt.direction {
+            Direction::SpiralRight => {
+                let tiles = Matrix::get_spiral_tiles(width, height, opt.spiral_tiles);
+                (0..tiles)
+                    .map(|index| {
+                        let (_, tile_width) = Matrix::get_tile(width, tiles, index);
+                        ColumnMat::new(
+                            Matrix::get_spiral_length(height, tile_width),
+                            opt.color,
+                            opt.highlight_color,
+                            opt.highlight_threshold,
+                            opt.placeholder,
+                            true,
+                            opt.glyphs.transform(),
+                        )
+                        .with_drops(opt.drops)
+                        .with_ages(opt.show_age)
+                    })
+                    .collect()
+            }
+            Direction::Top | Direction::Bottom => {
+                vec![ColumnMat::from_args(height, opt); width as usize]
+            }
+            // a column per row
+            Direction::Left | Direction::Right => {
+                vec![ColumnMat::from_args(width, opt); height as usize]
+            }
+        }
+    }
+
+    fn term_size(&self) -> (u16, u16) {
+        if let Some(size) = self
+            .replay
+            .as_ref()
+            .and_then(|replay| replay.size_at(self.frame))
+        {
+            return size;
+        }
+        let (width, height) = self.out.size();
+        let (above, below) = self.reserved_rows();
+        (width, height.saturating_sub(above + below).max(1))
+    }
+
+    // rows above and below the matrix, for the status bar and the error ticker
+    fn reserved_rows(&self) -> (u16, u16) {
+        let bar = |position| u16::from(self.opt.status_bar == Some(position));
+        let ticker = u16::from(self.opt.error_ticker.is_some());
+        (bar(StatusBar::Top), bar(StatusBar::Bottom) + ticker)
+    }
+
+    // the matrix is drawn in the rows left by the status bar and the ticker, the cursor
+    // positions are relative to them in the origin mode
+    fn enter_matrix_rows(&mut self) {
+        let (above, below) = self.reserved_rows();
+        if (above, below) == (0, 0) {
+            return;
+        }
+        let _ = write!(
+            self.out,
+            "\x1b[{};{}r\x1b[?6h",
+            above + 1,
+            above + self.height
+        );
+    }
+
+    fn leave_matrix_rows(&mut self) {
+        if self.reserved_rows() != (0, 0) {
+            let _ = write!(self.out, "\x1b[?6l\x1b[r");
+        }
+    }
+
+    // under the status bar at the bottom
+    fn draw_ticker(&mut self) {
+        let Some(mut ticker) = self.ticker.take() else {
+            return;
+        };
+        let (above, below) = self.reserved_rows();
+        self.leave_matrix_rows();
+        ticker.draw(
+            &mut self.out,
+            above + self.height + below,
+            self.width as usize,
+        );
+        self.ticker = Some(ticker);
+    }
+
+    fn draw_status_bar(&mut self) {
+        let row = match self.opt.status_bar {
+            Some(StatusBar::Top) => 1,
+            Some(StatusBar::Bottom) => self.height + 1,
+            None => return,
+        };
+        // named by the syslog and GELF datagrams among others, which anybody can send
+        let sources = self.stats.active_sources();
+        let sources = match sources.is_empty() {
+            true => "no input".to_string(),
+            false => text::sanitize(&sources.join(", "), 1, self.opt.placeholder),
+        };
+        let direction = self
+            .opt
+            .direction
+            .to_possible_value()
+            .expect("no variant is skipped");
+        let state = match self.paused || self.pager.is_some() {
+            true => "paused",
+            false => "running",
+        };
+        let bar = format!(
+            " {sources} │ {} │ every {}ms │ {state} │ backlog {} ",
+            direction.get_name(),
+            self.opt.frequency,
+            self.stats.backlog()
+        );
+        let bar = text::truncate(bar, self.width as usize, "…");
+        let padding = (self.width as usize).saturating_sub(bar.width());
+        self.leave_matrix_rows();
+        Matrix::place_cursor(&mut self.out, 1, row);
+        let _ = write!(
+            self.out,
+            "{}{esc}[7m{bar}{}{}",
+            self.opt.highlight_color.to_ansi(),
+            " ".repeat(padding),
+            Color::Default.to_ansi(),
+            esc = 27 as char
+        );
+    }
+
+    fn update_mat(&mut self) {
+        let (width, height) = self.term_size();
+        let resized = self.width != width || self.height != height;
+        if let Some(capture) = &mut self.capture
+            && (resized || self.frame == 0)
+        {
+            capture.record_size(self.frame, width, height);
+        }
+        if !resized {
+            return;
+        }
+        let mut columns = Matrix::get_columns(width, height, &self.opt);
+        let count = columns.len();
+        self.trace_moved(count);
+        // the columns left keep their lines, the newest visible cells that still fit and
+        // the lines waiting. the lines waiting in the columns gone are queued in the ones
+        // left like on a change of direction
+        for (index, old) in self.columns.drain(..).enumerate() {
+            match columns.get_mut(index) {
+                Some(column) => {
+                    let size = column.visible_line.data.len();
+                    *column = old;
+                    column.visible_line.resize(size);
+                }
+                None if count > 0 => columns[index % count]
+                    .invisible_cache
+                    .extend(old.invisible_cache),
+                None => {}
+            }
+        }
+        self.columns = columns;
+        self.height = height;
+        self.width = width;
+        self.buffer = FrameBuffer::new(width, height);
+        // the spiral moves with the center of its tile, the rows of the other directions
+        // are all drawn again over the previous ones
+        if matches!(self.opt.direction, Direction::SpiralRight) {
+            self.clean_matrix();
+        }
+        self.spiral_coord_create();
+    }
+
+    // the lines waiting in a column are queued in the column of the same rank of the next
+    // direction, or wrap around when it has fewer
+    fn next_direction(&mut self) {
+        self.opt.direction = self.opt.direction.next();
+        let mut columns = Matrix::get_columns(self.width, self.height, &self.opt);
+        let count = columns.len();
+        for (index, old) in self.columns.drain(..).enumerate() {
+            columns[index % count]
+                .invisible_cache
+                .extend(old.invisible_cache);
+        }
+        self.columns = columns;
+        self.buffer = FrameBuffer::new(self.width, self.height);
+        self.clean_matrix();
+        self.spiral_coord_create();
+    }
+
+    // the traced lines waiting in the columns from `count` on, which a resize leaves out
+    fn trace_moved(&mut self, count: usize) {
+        let moved: Vec<(u64, usize)> = self
+            .columns
+            .iter()
+            .enumerate()
+            .skip(count)
+            .flat_map(|(index, col)| {
+                col.invisible_cache
+                    .iter()
+                    .filter_map(move |line| Some((line.trace?, index)))
+            })
+            .collect();
+        for (id, index) in moved {
+            self.trace(Some(id), || match count {
+                0 => "lost with its column on resize".to_string(),
+                count => format!("moved to column {} on resize", index % count),
+            });
+        }
+    }
+
+    fn spiral_coord_create(&mut self) {
+        let tiles = self.columns.len() as u16;
+        self.posible_positions = (0..tiles)
+            .map(|index| {
+                let (left, tile_width) = Matrix::get_tile(self.width, tiles, index);
+                self.spiral_coords(left, tile_width)
+            })
+            .collect();
+    }
+
+    // positions of the spiral centered in the tile, the columns after `left` up to its width
+    fn spiral_coords(&mut self, left: u16, tile_width: u16) -> Vec<(u16, u16)> {
+        let max = 100000;
+        let (center_x, center_y) = (left + tile_width / 2, self.height / 2);
+        let (mut x_prev, mut y_prev) = (center_x, center_y);
+        let mut positions = vec![];
+        for i in 1..max {
+            let index = i as f32;
+            let x = (self.r(index) * index.cos()).floor() as i16 * 2;
+            let y = (self.r(index) * index.sin()).floor() as i16;
+            let x_abs = center_x as i32 + x as i32;
+            let y_abs = center_y as i32 + y as i32;
+
+            if x_abs <= left as i32
+                || x_abs > (left + tile_width) as i32
+                || y_abs < 0
+                || y_abs > self.height as i32
+            {
+                continue;
+            }
+
+            let x_abs = x_abs as u16;
+            let y_abs = y_abs as u16;
+
+            if x_abs != x_prev || y_abs != y_prev {
+                positions.push((x_abs, y_abs));
+            }
+            x_prev = x_abs;
+            y_prev = y_abs;
+        }
+        positions
+    }
+
+    fn spiral_exec(&mut self) {
+        let glitch_rate = self.glitch_rate();
+        for (tile, (x_abs, y_abs)) in self
+            .posible_positions
+            .iter()
+            .enumerate()
+            .flat_map(|(tile, positions)| positions.iter().map(move |position| (tile, position)))
+        {
+            let (cell, freshness) = self.columns[tile].get_next(&Direction::SpiralRight);
+            let intensity = Matrix::intensity(&self.highlight_curve, self.elapsed(), &cell);
+            let letter = Matrix::shade(&self.opt.shade_ramp, cell.glyph, freshness);
+            let letter = Matrix::glitch(&mut self.glitch_rng, glitch_rate, letter);
+            let styled = Styled {
+                glyph: letter,
+                color: cell.color,
+                intensity,
+            };
+            self.buffer.set(*x_abs, *y_abs, styled);
+        }
+    }
+
+    // only the cells which changed since the last frame are drawn, by the render thread
+    // from a snapshot of them when there is one
+    fn paint(&mut self) {
+        let cells = self.buffer.clone();
+        match &self.painter {
+            Some(painter) => painter.paint(cells, self.annotated_rows),
+            None => {
+                let _ = self.screen.paint(cells, self.annotated_rows, &mut self.out);
+            }
+        }
+    }
+
+    // every cell is drawn again by the next frame
+    fn forget_screen(&mut self) {
+        self.screen.forget();
+        if let Some(painter) = &self.painter {
+            painter.forget();
+        }
+    }
+
+    fn glitch_rate(&self) -> f32 {
+        match self.opt.reduced_motion || self.cpu_cap.is_some() {
+            true => 0.,
+            false => self.opt.glitch_rate,
+        }
+    }
+
+    // swap a visible character for a random one during a single frame
+    fn glitch(rng: &mut Jitter, rate: f32, letter: String) -> String {
+        if letter == " " || !rng.chance(rate) {
+            return letter;
+        }
+        ((b'!' + rng.index(94) as u8) as char).to_string()
+    }
+
+    // the age of a visible character picks its glyph in the shade ramp
+    fn shade(ramp: &Option<String>, letter: String, freshness: f32) -> String {
+        match ramp {
+            Some(ramp) if letter != " " => text::shade(ramp, freshness).to_string(),
+            _ => letter,
+        }
+    }
+
+    // animation time, counted in frames to replay the same way
+    fn elapsed(&self) -> Duration {
+        self.frame_period * self.frame as u32
+    }
+
+    fn page_frames(&self) -> u64 {
+        (PAGE_PERIOD.as_millis() / self.frame_period.as_millis().max(1)).max(1) as u64
+    }
+
+    fn intensity(curve: &Option<HighlightCurve>, elapsed: Duration, cell: &Cell) -> Intensity {
+        if cell.dim {
+            return Intensity::Dim;
+        }
+        match (curve, cell.highlight) {
+            (Some(curve), Some(position)) => curve.intensity(elapsed, position),
+            _ => Intensity::Normal,
+        }
+    }
+
+    fn directional_exec(&mut self) {
+        let elapsed = self.elapsed();
+        let glitch_rate = self.glitch_rate();
+        let horizontal = self.opt.direction.horizontal();
+        let length = if horizontal { self.width } else { self.height };
+        for rank in 1..=length {
+            for (line, col) in (1..).zip(self.columns.iter_mut()) {
+                let (column, row) = if horizontal {
+                    (rank, line)
+                } else {
+                    (line, rank)
+                };
+                let (cell, freshness) = col.get_next(&self.opt.direction);
+                let intensity = Matrix::intensity(&self.highlight_curve, elapsed, &cell);
+                let letter = Matrix::shade(&self.opt.shade_ramp, cell.glyph, freshness);
+                let styled = Styled {
+                    glyph: Matrix::glitch(&mut self.glitch_rng, glitch_rate, letter),
+                    color: cell.color,
+                    intensity,
+                };
+                self.buffer.set(column, row, styled);
+            }
+        }
+    }
+
+    // stacked banners, the oldest annotation stays on the first row
+    fn draw_annotations(&mut self) {
+        let now = Instant::now();
+        self.annotations
+            .retain(|annotation| annotation.expires.is_none_or(|expires| expires > now));
+        for (row, annotation) in self.annotations.iter().enumerate() {
+            if row >= self.height as usize {
+                break;
+            }
+            Matrix::place_cursor(&mut self.out, 1, row as u16 + 1);
+            let _ = write!(
+                self.out,
+                "{}{esc}[7m{}{}",
+                self.opt.highlight_color.to_ansi(),
+                text::center(&annotation.text, self.width as usize),
+                Color::Default.to_ansi(),
+                esc = 27 as char
+            );
+        }
+        self.annotated_rows = self.annotations.len().min(self.height as usize) as u16;
+    }
+
+    // bottom right corner, over the matrix
+    fn draw_hud(&mut self, period: Duration) {
+        self.average_period += (period.as_secs_f64() - self.average_period) / 10.;
+        let mut hud = format!(
+            " {:.0}fps period {:.1}ms render {:.1}ms late {} ",
+            1. / self.average_period,
+            period.as_secs_f64() * 1000.0,
+            self.render_time.as_secs_f64() * 1000.0,
+            self.counters.late_frames.load(Ordering::Relaxed)
+        );
+        if self.render_time > self.frame_period {
+            hud += "over budget ";
+        }
+        if let Some(cores) = self.cpu_cap {
+            hud += &format!(
+                "cap {:.0}fps {cores:.2}cpu ",
+                1. / self.frame_period.as_secs_f64()
+            );
+        }
+        let column = (self.width as usize).saturating_sub(hud.len()) as u16 + 1;
+        Matrix::place_cursor(&mut self.out, column, self.height);
+        let _ = write!(
+            self.out,
+            "{}{esc}[7m{hud}{}",
+            self.opt.highlight_color.to_ansi(),
+            Color::Default.to_ansi(),
+            esc = 27 as char
+        );
+    }
+
+    fn draw_clock(&mut self) {
+        let Some(corner) = self.opt.clock else {
+            return;
+        };
+        let mut clock = format!(" {} ", clock::local_time(SystemTime::now()));
+        let newest = self
+            .columns
+            .iter()
+            .flat_map(|column| column.visible_line.data.iter())
+            .filter_map(|cell| cell.record.as_ref())
+            .max_by_key(|record| record.received);
+        if self.opt.clock_last_line
+            && let Some(record) = newest
+        {
+            let received = SystemTime::now() - record.received.elapsed();
+            clock += &format!("last line {} ", clock::local_time(received));
+        }
+        let (column, row) = corner.position(clock.len() as u16, (self.width, self.height));
+        Matrix::place_cursor(&mut self.out, column, row);
+        let _ = write!(
+            self.out,
+            "{}{esc}[7m{clock}{}",
+            self.opt.highlight_color.to_ansi(),
+            Color::Default.to_ansi(),
+            esc = 27 as char
+        );
+    }
+
+    // top right corner, over the matrix
+    fn draw_stats(&mut self) {
+        let queue_dropped =
+            (self.opt.queue_size).map(|_| self.counters.queue_dropped.load(Ordering::Relaxed));
+        let missing = (self.opt.seq_field)
+            .as_ref()
+            .map(|_| self.counters.missing.load(Ordering::Relaxed));
+        let lines = self.stats.lines(
+            self.counters.dropped.load(Ordering::Relaxed),
+            queue_dropped,
+            missing,
+            self.counters.late_frames.load(Ordering::Relaxed),
+        );
+        let width = lines.iter().map(|line| line.len()).max().unwrap_or(0) + 2;
+        let column = (self.width as usize).saturating_sub(width) as u16 + 1;
+        for (row, line) in (1..self.height).zip(lines) {
+            Matrix::place_cursor(&mut self.out, column, row);
+            let _ = write!(
+                self.out,
+                "{}{esc}[7m {line:<pad$}{}",
+                self.opt.highlight_color.to_ansi(),
+                Color::Default.to_ansi(),
+                pad = width - 1,
+                esc = 27 as char
+            );
+        }
+    }
+
+    // drawn in the buffer first then written at once, as a synchronized update so the
+    // terminal does not show it half drawn either. false once nobody is looking at the
+    // frames anymore
+    fn draw_frame(&mut self, period: Duration) -> bool {
+        let _ = write!(self.out, "{}", term::BEGIN_UPDATE);
+        self.enter_matrix_rows();
+        match self.opt.direction {
+            Direction::SpiralRight => self.spiral_exec(),
+            Direction::Top | Direction::Bottom | Direction::Left | Direction::Right => {
+                self.directional_exec()
+            }
+        };
+        self.paint();
+        if let Some(report) = &mut self.report
+            && report.frame_due()
+        {
+            report.record_frame(self.buffer.text());
+        }
+        for clip in self.clips.iter_mut() {
+            clip.record_frame(&self.buffer);
+        }
+        if let Some((record, _)) = &self.detail {
+            let size = (self.width, self.height);
+            detail::draw(&mut self.out, record, size, self.opt.tab_width);
+        }
+        self.draw_annotations();
+        self.draw_clock();
+        if self.stats_shown {
+            self.draw_stats();
+        }
+        if self.opt.hud {
+            self.draw_hud(period);
+        }
+        self.draw_status_bar();
+        self.draw_ticker();
+        let _ = write!(self.out, "{}", term::END_UPDATE);
+        self.out.flush().is_ok()
+    }
+
+    /// draws the frames until the inputs end or `stop` is called, the terminal is restored
+    /// on the way out. Ctrl-C is left to the program embedding the matrix
+    pub fn main_loop(&mut self) {
+        let delta_t = self.frame_period;
+        // the reader threads are already spawned and keep their normal priority
+        if self.opt.realtime
+            && let Err(err) = realtime::raise_priority()
+        {
+            eprintln!("could not raise the priority: {err}");
+        }
+        // the frames are written by a thread of their own, the lines and the keys are
+        // never held back by a slow terminal
+        if self.on_terminal && self.painter.is_none() {
+            let painter = Painter::spawn(Box::new(Terminal::new().with_mouse(self.opt.mouse)));
+            self.out = Box::new(painter.output());
+            self.painter = Some(painter);
+        }
+        self.enter_matrix();
+        let mut pacer = FramePacer::new(delta_t);
+        let mut previous: Option<Instant> = None;
+        let mut skipped = false;
+        while RUNNING.load(Ordering::SeqCst) && !self.out.closed() {
+            // the time spent in the handoff command is no late frame
+            if self.update_keys() {
+                previous = None;
+                pacer.restart();
+            }
+            // update the size of window dynamically
+            let now = Instant::now();
+            let period = previous.map_or(delta_t, |previous| now - previous);
+            previous = Some(now);
+            self.update_mat();
+            if self.update_inputs().is_none() {
+                break;
+            }
+            // the lines keep being queued behind the pager
+            if self.pager.is_some() {
+                if !self.painter.as_ref().is_some_and(Painter::behind) {
+                    let _ = write!(self.out, "{}", term::BEGIN_UPDATE);
+                    self.enter_matrix_rows();
+                    if let Some(pager) = &self.pager {
+                        pager.draw(&mut self.out, &self.history, (self.width, self.height));
+                    }
+                    self.draw_status_bar();
+                    self.draw_ticker();
+                    let _ = write!(self.out, "{}", term::END_UPDATE);
+                    if self.out.flush().is_err() {
+                        break;
+                    }
+                }
+                pacer.wait();
+                previous = None;
+                continue;
+            }
+
+            self.update_pause();
+            if self.paused {
+                // the same frame is drawn again until a key is pressed
+            } else if self.opt.reduced_motion {
+                // the unchanged screen is still drawn between pages, under the annotations
+                if self.frame.is_multiple_of(self.page_frames()) {
+                    for col in self.columns.iter_mut().filter(|col| !col.frozen) {
+                        col.turn_page(self.opt.spaces);
+                    }
+                }
+            } else {
+                for col in self.columns.iter_mut() {
+                    if self.speed_rng.chance(self.opt.speed_jitter) {
+                        continue;
+                    }
+                    // the spirals read more cells than a column holds, start at the same one
+                    if col.frozen {
+                        col.visible_line.rewind();
+                        continue;
+                    }
+                    let ticks = match self.opt.adaptive_speed {
+                        true => col.adapt_speed(self.opt.min_ticks, self.opt.max_ticks),
+                        false => self.opt.chars_per_tick,
+                    };
+                    for _ in 0..ticks {
+                        col.tick(self.opt.spaces);
+                    }
+                }
+            }
+            if let Some(ttl) = self.opt.char_ttl
+                && !self.paused
+            {
+                for col in self.columns.iter_mut() {
+                    col.visible_line.expire(ttl);
+                }
+            }
+            let backlog = self
+                .columns
+                .iter()
+                .map(|col| col.invisible_cache.len())
+                .sum();
+            self.stats.set_backlog(backlog);
+            self.trace_columns();
+
+            // after a missed deadline the columns move without being drawn, never twice in
+            // a row. nor while the terminal is still busy with the frames before
+            let skip = self.opt.skip_frames && pacer.behind() && !skipped
+                || self.painter.as_ref().is_some_and(Painter::behind);
+            skipped = skip;
+            // a client of `serve` went away
+            if !skip && !self.draw_frame(period) {
+                break;
+            }
+
+            self.frame += 1;
+            self.render_time = now.elapsed();
+            if pacer.wait() {
+                self.counters.late_frames.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.exit_matrix();
+        if let Some(path) = &self.opt.control_socket {
+            let _ = fs::remove_file(path);
+        }
+        for listen in &self.opt.listen {
+            if let sources::Listen::Unix(path) = listen {
+                let _ = fs::remove_file(path);
+            }
+        }
+        if let Some(capture) = self.capture.take()
+            && let Err(err) = capture.finish(self.frame)
+        {
+            eprintln!("could not write the reproduction bundle: {err}");
+        }
+        if let Some(report) = self.report.take()
+            && let Err(err) = report.finish(&self.counters)
+        {
+            eprintln!("could not write the report: {err}");
+        }
+        for clip in self.clips.drain(..) {
+            if let Err(err) = clip.finish() {
+                eprintln!("could not write the clip: {err}");
+            }
+        }
+    }
+
+    /// the count of --fail-on, the process fails when a line matched
+    fn check_failures(&self) {
+        let Some(regex) = &self.opt.fail_on else {
+            return;
+        };
+        eprintln!("{} lines matching {regex}", self.failures);
+        if self.failures > 0 {
+            exit(1);
+        }
+    }
+
+    // true when the handoff command ran
+    fn update_keys(&mut self) -> bool {
+        let Some(keyboard) = self.keyboard.as_mut() else {
+            return false;
+        };
+        let mut pressed = keyboard.pressed();
+        let clicks = keys::take_clicks(&mut pressed);
+        if self.paused {
+            if !pressed.is_empty() {
+                self.resume();
+            }
+            return false;
+        }
+        if let Some(pager) = &mut self.pager {
+            if !pager.handle(&pressed, &self.history, self.height as usize) {
+                self.pager = None;
+                self.clean_matrix();
+            }
+            return false;
+        }
+        for (x, y) in clicks {
+            self.click(x, y);
+        }
+        let keys = String::from_utf8_lossy(&pressed).into_owned();
+        if let Some((record, _)) = &self.detail
+            && keys.contains(self.opt.copy_key)
+        {
+            let record = record.clone();
+            self.copy(&record.text);
+        }
+        if let Some(key) = self.opt.pager_key
+            && let Some(at) = keys.find(key)
+        {
+            // the keys typed right after it are already for the pager
+            let mut pager = Pager::new(key);
+            let rest = &keys.as_bytes()[at + key.len_utf8()..];
+            if pager.handle(rest, &self.history, self.height as usize) {
+                self.pager = Some(pager);
+            }
+            return false;
+        }
+        let snapshots = self.opt.snapshot_out.is_some() || self.opt.snapshot_dir.is_some();
+        if snapshots && keys.contains(self.opt.snapshot_key) {
+            self.snapshot();
+        }
+        if let Some(key) = self.opt.direction_key
+            && keys.contains(key)
+        {
+            self.next_direction();
+        }
+        if let Some(key) = self.opt.stats_key
+            && keys.contains(key)
+        {
+            self.stats_shown = !self.stats_shown;
+            if !self.stats_shown {
+                self.clean_matrix();
+            }
+        }
+        let pressed = |key: Option<char>| key.is_some_and(|key| keys.contains(key));
+        let (mut color, mut highlight) = (self.opt.color, self.opt.highlight_color);
+        if pressed(self.opt.color_key) {
+            color = color.next();
+        }
+        if pressed(self.opt.highlight_key) {
+            highlight = highlight.next();
+        }
+        if pressed(self.opt.theme_key) {
+            (color, highlight) = self.next_theme(color, highlight);
+        }
+        if (color, highlight) != (self.opt.color, self.opt.highlight_color) {
+            self.recolor(color, highlight);
+        }
+        if self.opt.handoff_cmd.is_none() || !keys.contains(self.opt.handoff_key) {
+            return false;
+        }
+        self.handoff();
+        true
+    }
+
+    // the column drawn at the cell clicked stops or starts ticking again, the line of the
+    // cell pops up while it is frozen. a click anywhere closes the popup
+    fn click(&mut self, x: u16, y: u16) {
+        if let Some((_, column)) = self.detail.take() {
+            if let Some(column) = self.columns.get_mut(column) {
+                column.frozen = false;
+            }
+            self.clean_matrix();
+            return;
+        }
+        let Some((column, rank)) = self.cell_at(x, y) else {
+            return;
+        };
+        let direction = self.opt.direction.clone();
+        let col = &mut self.columns[column];
+        col.frozen = !col.frozen;
+        let record = rank.and_then(|(rank, calls)| {
+            col.visible_line
+                .drawn(rank, calls, &direction)
+                .record
+                .clone()
+        });
+        if col.frozen
+            && let Some(record) = record
+        {
+            self.detail = Some((record, column));
+        }
+    }
+
+    // the column drawn at a cell of the screen and, when one of its glyphs is drawn there,
+    // the call to `get_next` which returned it out of the calls of the last frame
+    fn cell_at(&self, x: u16, y: u16) -> Option<(usize, Option<(usize, usize)>)> {
+        if x == 0 || y == 0 || x > self.width || y > self.height {
+            return None;
+        }
+        let height = self.height as usize;
+        let (column, rank) = match self.opt.direction {
+            Direction::Top | Direction::Bottom => {
+                ((x - 1) as usize, Some(((y - 1) as usize, height)))
+            }
+            Direction::Left | Direction::Right => (
+                (y - 1) as usize,
+                Some(((x - 1) as usize, self.width as usize)),
+            ),
+            Direction::SpiralRight => {
+                let tiles = self.columns.len() as u16;
+                let tile = (0..tiles).find(|index| {
+                    let (left, tile_width) = Matrix::get_tile(self.width, tiles, *index);
+                    (left..left + tile_width).contains(&(x - 1))
+                })? as usize;
+                // drawn over by the last turn of the spiral going through the cell
+                let positions = self.posible_positions.get(tile)?;
+                let rank = positions.iter().rposition(|position| *position == (x, y));
+                (tile, rank.map(|rank| (rank, positions.len())))
+            }
+        };
+        (column < self.columns.len()).then_some((column, rank))
+    }
+
+    // the line of --pause-on is displayed once its glyphs are on screen and none of them
+    // is still waiting in its column
+    fn update_pause(&mut self) {
+        let Some(record) = &self.pause_on else {
+            return;
+        };
+        let of_record = |other: &Option<Arc<Record>>| {
+            other
+                .as_ref()
+                .is_some_and(|other| Arc::ptr_eq(other, record))
+        };
+        let displayed = self.columns.iter().any(|column| {
+            column
+                .visible_line
+                .data
+                .iter()
+                .any(|cell| of_record(&cell.record))
+                && !column
+                    .invisible_cache
+                    .front()
+                    .is_some_and(|line| of_record(&line.record))
+        });
+        if !displayed {
+            return;
+        }
+        let line = text::sanitize(&record.text, self.opt.tab_width, self.opt.placeholder);
+        let text = format!("{PAUSE_PREFIX}{line}");
+        self.pause_on = None;
+        self.paused = true;
+        for text in [text, PAUSE_HINT.to_string()] {
+            self.annotations.push(Annotation {
+                text,
+                expires: None,
+            });
+        }
+    }
+
+    // the whole screen flashes when a rule fires, a banner names it
+    fn update_alerts(&mut self) {
+        let now = Instant::now();
+        let fired: Vec<String> = self
+            .alerts
+            .iter_mut()
+            .filter_map(|alert| alert.fired(now).then(|| alert.describe()))
+            .collect();
+        for text in fired {
+            if self.flash_until.is_none() {
+                let _ = write!(self.out, "\x1b[?5h");
+            }
+            self.flash_until = Some(now + ALERT_FLASH_DURATION);
+            self.annotations.push(Annotation {
+                text,
+                expires: Some(now + ALERT_NOTICE_DURATION),
+            });
+        }
+        if self.flash_until.is_some_and(|until| until <= now) {
+            self.flash_until = None;
+            let _ = write!(self.out, "\x1b[?5l");
+        }
+    }
+
+    fn resume(&mut self) {
+        self.paused = false;
+        self.annotations.retain(|annotation| {
+            annotation.text != PAUSE_HINT && !annotation.text.starts_with(PAUSE_PREFIX)
+        });
+    }
+
+    // the terminal sets the clipboard, even over ssh
+    fn copy(&mut self, text: &str) {
+        let _ = write!(self.out, "\x1b]52;c;{}\x07", text::base64(text.as_bytes()));
+        self.annotations.push(Annotation {
+            text: "line copied to the clipboard".to_string(),
+            expires: Some(Instant::now() + COPY_NOTICE_DURATION),
+        });
+    }
+
+    // the lines received from now on are drawn with the new colors
+    // none when the motion is reduced or the frames are cut to fit in a cpu quota
+    fn highlight_curve(opt: &Args, cpu_cap: Option<f64>) -> Option<HighlightCurve> {
+        let easing = opt
+            .highlight_curve
+            .filter(|_| !opt.reduced_motion && cpu_cap.is_none())?;
+        Some(HighlightCurve {
+            easing,
+            period: opt.pulse_period,
+            cells: opt.highlight_threshold,
+        })
+    }
+
+    // the colors of the theme after the one of `color` and `highlight`, its curve is taken
+    // over at once
+    fn next_theme(&mut self, color: Color, highlight: Color) -> (Color, Color) {
+        let current = THEMES
+            .iter()
+            .position(|theme| (theme.color, theme.highlight) == (color, highlight));
+        let theme = &THEMES[current.map_or(0, |index| (index + 1) % THEMES.len())];
+        self.opt.highlight_curve = theme.curve;
+        self.highlight_curve = Matrix::highlight_curve(&self.opt, self.cpu_cap);
+        (theme.color, theme.highlight)
+    }
+
+    fn recolor(&mut self, color: Color, highlight: Color) {
+        self.opt.color = color;
+        self.opt.highlight_color = highlight;
+        for column in &mut self.columns {
+            column.color = color;
+            column.highlight = highlight;
+        }
+        self.annotations.push(Annotation {
+            text: format!("color {color:?}, highlight {highlight:?}").to_lowercase(),
+            expires: Some(Instant::now() + COLOR_NOTICE_DURATION),
+        });
+    }
+
+    // the frame of the last tick, without the banners over it
+    fn snapshot(&mut self) {
+        let path = match (&self.opt.snapshot_out, &self.opt.snapshot_dir) {
+            (Some(path), _) => path.clone(),
+            (None, Some(dir)) => snapshot_path(dir, self.opt.snapshot_format),
+            (None, None) => return,
+        };
+        let text = match self.buffer.export(&path) {
+            Ok(()) => format!("snapshot written to {}", path.display()),
+            Err(err) => format!("could not write {}: {err}", path.display()),
+        };
+        self.annotations.push(Annotation {
+            text,
+            expires: Some(Instant::now() + SNAPSHOT_NOTICE_DURATION),
+        });
+    }
+
+    // the terminal is given back as it was until the command exits, the inputs keep
+    // queuing meanwhile
+    fn handoff(&mut self) {
+        let (Some(_), Some(cmd)) = (&self.keyboard, self.opt.handoff_cmd.clone()) else {
+            return;
+        };
+        self.exit_matrix();
+        let keyboard = self.keyboard.as_ref().expect("checked above");
+        let _ = keyboard.suspend();
+        HANDED_OFF.store(true, Ordering::SeqCst);
+        // stdin is usually the piped logs, the command gets the terminal instead
+        let status = fs::File::open("/dev/tty").and_then(|tty| {
+            process::Command::new("sh")
+                .arg("-c")
+                .arg(&cmd)
+                .stdin(Stdio::from(tty))
+                .status()
+        });
+        HANDED_OFF.store(false, Ordering::SeqCst);
+        let _ = keyboard.resume();
+        self.enter_matrix();
+        self.clean_matrix();
+        let failure = match status {
+            Ok(status) if status.success() => None,
+            Ok(status) => Some(format!("{cmd}: {status}")),
+            Err(err) => Some(format!("{cmd}: {err}")),
+        };
+        if let Some(text) = failure {
+            self.annotations.push(Annotation {
+                text,
+                expires: Some(Instant::now() + HANDOFF_FAILURE_DURATION),
+            });
+        }
+    }
+
+    fn place_cursor(out: &mut dyn Write, x: u16, y: u16) {
+        let _ = write!(out, "{esc}[{y};{x}H", esc = 27 as char);
+    }
+
+    fn clean_matrix(&mut self) {
+        self.forget_screen();
+        let _ = write!(self.out, "{esc}[2J", esc = 27 as char);
+    }
+    fn enter_matrix(&mut self) {
+        self.forget_screen();
+        self.out.enter();
+    }
+    fn exit_matrix(&mut self) {
+        self.leave_matrix_rows();
+        if self.flash_until.take().is_some() {
+            let _ = write!(self.out, "\x1b[?5l");
+        }
+        self.out.leave();
+    }
+
+    // archimean spiral
+    fn r(&mut self, angle: f32) -> f32 {
+        angle / self.spiral_coef
+    }
+}
+
+// prefixes the error with what failed, the way `or_exit` prints it
+fn context(context: &str) -> impl FnOnce(io::Error) -> io::Error + '_ {
+    move |err| io::Error::new(err.kind(), format!("{context}: {err}"))
+}
+
+/// ends the loops of the matrices of the process with their next frame, for the Ctrl-C
+/// handler of the programs embedding them
+pub fn stop() {
+    RUNNING.store(false, Ordering::SeqCst)
+}
+
+// the lines are written as soon as they are received, for a `tail -f` to follow them
+fn append_to(path: &Path) -> io::Result<LineWriter<File>> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    Ok(LineWriter::new(file))
+}
+
+// logmatrix-20240229-134502.ans, in UTC, with a counter when several are taken in a second
+fn snapshot_path(dir: &Path, format: SnapshotFormat) -> PathBuf {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (year, month, day) = clock::civil_date(seconds / 86400);
+    let time = seconds % 86400;
+    let stamp = format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    );
+    let extension = format.extension();
+    let mut path = dir.join(format!("logmatrix-{stamp}.{extension}"));
+    let mut count = 1;
+    while path.exists() {
+        count += 1;
+        path = dir.join(format!("logmatrix-{stamp}-{count}.{extension}"));
+    }
+    path
+}
+
+fn format_uptime(uptime: Duration) -> String {
+    let seconds = uptime.as_secs();
+    format!(
+        "{}h{:02}m{:02}s",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use effects::Easing;
+    use testing::TempPath;
+
+    // 40x10 in memory, the tests hand the lines to `receive` themselves
+    fn matrix(options: &[&str]) -> Matrix {
+        matrix_on(MemoryRenderer::new(40, 10), options)
+    }
+
+    // drawing on a clone of `out`, for the test to read what was written
+    fn matrix_on(out: MemoryRenderer, options: &[&str]) -> Matrix {
+        let argv = ["logmatrix", "--no-stdin", "--seed", "7"];
+        let args = Args::parse_from(argv.iter().chain(options));
+        let (_, input) = sources::unbounded();
+        Matrix::with_output(args, input, Arc::new(Counters::default()), Box::new(out)).unwrap()
+    }
+
+    // the record of the first line queued in the columns
+    fn queued_record(mat: &Matrix) -> Arc<Record> {
+        mat.columns
+            .iter()
+            .flat_map(|column| &column.invisible_cache)
+            .find_map(|queued| queued.record.clone())
+            .unwrap()
+    }
+
+    fn line(text: &str) -> InputLine {
+        InputLine::new("stdin", text.to_string())
+    }
+
+    // the HTML report of a session which received the lines
+    fn report(options: &[&str], lines: impl IntoIterator<Item = InputLine>) -> String {
+        let path = TempPath::new("report.html");
+        let mut mat = matrix(options);
+        mat.report = Some(SessionReport::new(path.to_path_buf()));
+        for line in lines {
+            mat.receive(line);
+        }
+        mat.report.take().unwrap().finish(&mat.counters).unwrap();
+        path.read()
+    }
+
+    // the columns move and are drawn as in the main loop, at the seed of the tests
+    fn draw_frames(mat: &mut Matrix, frames: usize) {
+        for _ in 0..frames {
+            for col in mat.columns.iter_mut() {
+                for _ in 0..mat.opt.chars_per_tick {
+                    col.tick(mat.opt.spaces);
+                }
+            }
+            assert!(mat.draw_frame(Duration::ZERO));
+            mat.frame += 1;
+        }
+    }
+
+    #[test]
+    fn seeded_frame_snapshot() {
+        let out = MemoryRenderer::new(40, 10);
+        let mut mat = matrix_on(out.clone(), &["--glyphs", "plain"]);
+        for text in [
+            "GET /index.html 200",
+            "disk almost full",
+            "worker 3 restarted",
+        ] {
+            mat.receive(line(text));
+        }
+        draw_frames(&mut mat, 12);
+        // the columns and their pace come from the seed, the frame is always the same
+        let frame = [
+            "             r                 T        ",
+            "             s                 h        ",
+            "             e                 .        ",
+            "             r                 x        ",
+            "                               e        ",
+            "             3                 d        ",
+            "                               n        ",
+            "             r                 i        ",
+            "             e                 /        ",
+            "             k                          ",
+        ];
+        assert_eq!(mat.buffer.text(), frame.join("\n"));
+    }
+
+    #[test]
+    fn themes_carry_their_highlight_curve() {
+        let mut mat = matrix(&[]);
+        assert!(mat.highlight_curve.is_none());
+        let (color, highlight) = mat.next_theme(Color::Default, Color::White);
+        assert_eq!((color, highlight), (Color::Green, Color::White));
+        assert!(matches!(
+            mat.highlight_curve,
+            Some(HighlightCurve {
+                easing: Easing::EaseOut,
+                ..
+            })
+        ));
+        mat.next_theme(color, highlight);
+        assert!(mat.highlight_curve.is_none());
+
+        let mut still = matrix(&["--reduced-motion"]);
+        still.next_theme(Color::Default, Color::White);
+        assert!(still.highlight_curve.is_none());
+    }
+
+    #[test]
+    fn workers_leave_the_passed_notifications_out() {
+        let out = MemoryRenderer::new(40, 10);
+        let mut mat = matrix_on(out.clone(), &["--passthrough-notifications"]);
+        let prepared = mat
+            .prepare
+            .apply(line("\x1b]777;notify;disk;full\x07backup done"));
+        mat.receive(prepared);
+        let queued: Vec<_> = mat
+            .columns
+            .iter()
+            .flat_map(|column| &column.invisible_cache)
+            .map(|queued| queued.text.as_str())
+            .collect();
+        assert_eq!(queued, ["backup done"]);
+        let written = String::from_utf8(out.contents()).unwrap();
+        assert!(written.contains("\x1b]777;notify;disk;full\x07"));
+    }
+
+    #[test]
+    fn direction_key_goes_through_every_direction() {
+        let mut direction = Direction::Top;
+        let names: Vec<String> = (0..5)
+            .map(|_| {
+                direction = direction.next();
+                let name = direction.to_possible_value().unwrap();
+                name.get_name().to_string()
+            })
+            .collect();
+        assert_eq!(names, ["bottom", "spiral-right", "left", "right", "top"]);
+    }
+
+    #[test]
+    fn horizontal_rain_goes_along_the_rows() {
+        for (direction, shown) in [("left", "hello"), ("right", "olleh")] {
+            let mut mat = matrix(&["--direction", direction]);
+            assert_eq!(mat.columns.len(), 10);
+            mat.receive(line("hello"));
+            draw_frames(&mut mat, 5);
+            let text = mat.buffer.text();
+            let row = text.lines().find(|row| !row.trim().is_empty()).unwrap();
+            assert!(row.contains(shown), "{direction}: {row:?}");
+        }
+    }
+
+    #[test]
+    fn idle_rain_keeps_the_buffered_lines() {
+        let mut mat = matrix(&["--idle-after", "1s"]);
+        mat.columns[0].frozen = true;
+        mat.columns[0].add_line("buffered".to_string(), None, 0., None, None);
+        mat.last_input = Instant::now() - Duration::from_secs(2);
+        mat.update_idle();
+        for _ in 0..100 {
+            mat.update_demo();
+        }
+        let queued = |mat: &Matrix| -> Vec<(String, bool)> {
+            mat.columns
+                .iter()
+                .flat_map(|column| &column.invisible_cache)
+                .map(|queued| (queued.text.clone(), queued.filler))
+                .collect()
+        };
+        assert!(queued(&mat).iter().any(|(_, filler)| *filler));
+        mat.receive(line("back"));
+        let queued = queued(&mat);
+        assert!(queued.iter().all(|(_, filler)| !filler));
+        assert!(queued.iter().any(|(text, _)| text == "buffered"));
+    }
+
+    #[test]
+    fn tee_keeps_the_secrets() {
+        let tee = TempPath::new("tee.log");
+        let mut mat = matrix(&["--redact", "token=\\w+", "--tee", tee.arg()]);
+        mat.tee = Some(append_to(&tee).unwrap());
+        mat.receive(line("login token=hunter2 ok"));
+        // and the one prepared by the workers
+        let prepared = mat.prepare.apply(line("logout token=hunter2"));
+        mat.receive(prepared);
+        mat.tee = None;
+        assert_eq!(tee.read(), "login token=hunter2 ok\nlogout token=hunter2\n");
+        assert_eq!(queued_record(&mat).text, "login ************* ok");
+    }
+
+    #[test]
+    fn sequence_gaps_are_counted() {
+        let out = MemoryRenderer::new(40, 10);
+        let mut mat = matrix_on(out.clone(), &["--seq-field", "seq"]);
+        for seq in [1, 2, 5, 3, u64::MAX, u64::MAX] {
+            mat.receive(line(&format!("seq={seq}")));
+        }
+        let missing = 2 + (u64::MAX - 3 - 1);
+        assert_eq!(mat.counters.missing.load(Ordering::Relaxed), missing);
+        mat.draw_stats();
+        let drawn = String::from_utf8(out.contents()).unwrap();
+        assert!(drawn.contains(&format!("missing {missing}")));
+    }
+
+    #[test]
+    fn pager_history_is_redacted() {
+        let mut mat = matrix(&["--pager-key", "p", "--redact", "token=\\w+"]);
+        mat.receive(line("login token=hunter2 ok"));
+        assert_eq!(mat.history.back().unwrap(), "login ************* ok");
+    }
+
+    #[test]
+    fn popup_record_is_redacted() {
+        let mut mat = matrix(&["--redact", "token=\\w+"]);
+        mat.receive(line("login token=hunter2 ok"));
+        assert_eq!(queued_record(&mat).text, "login ************* ok");
+    }
+
+    #[test]
+    fn clipboard_copy_is_redacted() {
+        let out = MemoryRenderer::new(40, 10);
+        let mut mat = matrix_on(out.clone(), &["--redact", "token=\\w+"]);
+        mat.receive(line("login token=hunter2 ok"));
+        let record = queued_record(&mat);
+        mat.copy(&record.text);
+        let copied = format!("\x1b]52;c;{}\x07", text::base64(b"login ************* ok"));
+        assert!(String::from_utf8_lossy(&out.contents()).contains(&copied));
+    }
+
+    #[test]
+    fn error_ticker_is_redacted() {
+        let mut mat = matrix(&["--error-ticker", "--redact", "token=\\w+"]);
+        mat.receive(line("error: token=hunter2 rejected"));
+        let mut drawn = vec![];
+        mat.ticker.as_mut().unwrap().draw(&mut drawn, 9, 40);
+        let drawn = String::from_utf8(drawn).unwrap();
+        assert!(drawn.contains("error: ************* rejected"));
+        assert!(!drawn.contains("hunter2"));
+    }
+
+    #[test]
+    fn report_alerts_are_redacted() {
+        let mut alert = line("token=hunter2 rejected");
+        alert.severity = Some(Severity::Error);
+        let html = report(&["--redact", "token=\\w+"], [alert]);
+        assert!(html.contains("************* rejected"));
+        assert!(!html.contains("hunter2"));
+    }
+
+    #[test]
+    fn report_alerts_follow_the_alert_rules() {
+        let lines = [line("worker restarted"), line("disk full")];
+        let html = report(&["--alert", "disk:100/1m"], lines);
+        let (_, alerts) = html.split_once("Top alerts").unwrap();
+        assert!(alerts.contains("disk full"));
+        assert!(!alerts.contains("worker restarted"));
+    }
+
+    #[test]
+    fn status_bar_sanitizes_the_sources() {
+        let out = MemoryRenderer::new(40, 10);
+        let mut mat = matrix_on(out.clone(), &["--status-bar", "top"]);
+        mat.receive(InputLine::new(
+            "host\x1b]52;c;eA==\x07",
+            "hello".to_string(),
+        ));
+        mat.draw_status_bar();
+        let drawn = String::from_utf8_lossy(&out.contents()).into_owned();
+        assert!(drawn.contains("host?]52;c;eA==?"));
+        assert!(!drawn.contains("\x1b]52"));
+    }
+}
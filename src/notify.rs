@@ -0,0 +1,53 @@
+use notify_rust::Notification;
+use std::{
+    sync::mpsc::{self, Sender},
+    thread::spawn,
+    time::{Duration, Instant},
+};
+
+/// raises the desktop notifications of the matching lines, at most one per interval. the
+/// d-bus calls can block so they are made from a thread of their own
+pub struct Notifier {
+    tx: Sender<String>,
+    interval: Duration,
+    last: Option<Instant>,
+    skipped: usize, // lines matching since the last notification
+}
+
+impl Notifier {
+    pub fn spawn(interval: Duration) -> Notifier {
+        let (tx, rx) = mpsc::channel::<String>();
+        spawn(move || {
+            for body in rx {
+                // without a notification daemon the lines are still displayed
+                let _ = Notification::new().summary("logmatrix").body(&body).show();
+            }
+        });
+        Notifier {
+            tx,
+            interval,
+            last: None,
+            skipped: 0,
+        }
+    }
+
+    /// the lines matching within the interval of the last notification are only counted
+    /// in the next one
+    pub fn notify(&mut self, line: &str) {
+        let now = Instant::now();
+        if self
+            .last
+            .is_some_and(|last| now.duration_since(last) < self.interval)
+        {
+            self.skipped += 1;
+            return;
+        }
+        let body = match self.skipped {
+            0 => line.to_string(),
+            skipped => format!("{line}\n(+{skipped} more matching lines)"),
+        };
+        self.last = Some(now);
+        self.skipped = 0;
+        let _ = self.tx.send(body);
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// the deadlines of the frames, a period apart from each other rather than from the end of
+/// the previous frame so the time spent drawing does not add up into a drift. a frame
+/// finishing past its deadline missed it, the next deadlines start over from then instead
+/// of rushing the next frames to catch up
+pub struct FramePacer {
+    period: Duration,
+    deadline: Instant, // of the frame being prepared
+    behind: bool,      // the last frame missed its deadline
+}
+
+impl FramePacer {
+    pub fn new(period: Duration) -> FramePacer {
+        FramePacer {
+            period,
+            deadline: Instant::now() + period,
+            behind: false,
+        }
+    }
+
+    /// the next deadline is a period from now, after a pause of the frames which is no
+    /// missed deadline
+    pub fn restart(&mut self) {
+        self.deadline = Instant::now() + self.period;
+        self.behind = false;
+    }
+
+    /// the previous frame missed its deadline, drawing this one could make it worse
+    pub fn behind(&self) -> bool {
+        self.behind
+    }
+
+    /// sleep until the deadline of the frame, true when it was already missed
+    pub fn wait(&mut self) -> bool {
+        if let Some(pause) = self.advance(Instant::now()) {
+            sleep(pause);
+        }
+        self.behind
+    }
+
+    // the pause until the deadline of the frame at `now`, none when it was missed. the
+    // deadline moves on to the next frame either way
+    fn advance(&mut self, now: Instant) -> Option<Duration> {
+        self.behind = now > self.deadline;
+        match self.behind {
+            true => {
+                self.deadline = now + self.period;
+                None
+            }
+            false => {
+                let pause = self.deadline - now;
+                self.deadline += self.period;
+                Some(pause)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD: Duration = Duration::from_millis(10);
+
+    // the pacer and the time its first period started
+    fn pacer() -> (FramePacer, Instant) {
+        let pacer = FramePacer::new(PERIOD);
+        let start = pacer.deadline - PERIOD;
+        (pacer, start)
+    }
+
+    #[test]
+    fn deadlines_do_not_drift() {
+        let (mut pacer, start) = pacer();
+        let ms = |ms| start + Duration::from_millis(ms);
+        assert_eq!(pacer.advance(ms(4)), Some(Duration::from_millis(6)));
+        // the 3ms spent drawing the frame come out of the next pause
+        assert_eq!(pacer.advance(ms(13)), Some(Duration::from_millis(7)));
+        assert_eq!(pacer.advance(ms(20)), Some(Duration::from_millis(10)));
+        assert!(!pacer.behind());
+    }
+
+    #[test]
+    fn a_late_frame_resets_the_deadline() {
+        let (mut pacer, start) = pacer();
+        let ms = |ms| start + Duration::from_millis(ms);
+        assert_eq!(pacer.advance(ms(55)), None);
+        assert!(pacer.behind());
+        // the next frame is a whole period away rather than due at once to catch up
+        assert_eq!(pacer.advance(ms(56)), Some(Duration::from_millis(9)));
+        assert!(!pacer.behind());
+        assert_eq!(pacer.advance(ms(70)), Some(Duration::from_millis(5)));
+    }
+
+    #[test]
+    fn restart_is_no_missed_deadline() {
+        let (mut pacer, start) = pacer();
+        assert_eq!(pacer.advance(start + Duration::from_millis(30)), None);
+        let restarted = Instant::now();
+        pacer.restart();
+        assert!(!pacer.behind());
+        assert!(pacer.deadline >= restarted + PERIOD);
+    }
+}
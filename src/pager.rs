@@ -0,0 +1,181 @@
+use crate::text;
+use std::{collections::VecDeque, io::Write};
+
+const REVERSE: &str = "\x1b[7m";
+const NO_REVERSE: &str = "\x1b[27m";
+
+/// the received lines as they are, the newest at the bottom. follows the new lines until
+/// scrolled up with the arrows, `j`/`k`, page up/down, `b`/space or `g`/`G`. `/` searches
+/// the older lines for a text, `n` and `N` go to the previous and next match
+pub struct Pager {
+    key: char,      // the one leaving the pager, along with `q`
+    offset: usize,  // lines between the bottom of the view and the newest one
+    search: String, // highlighted in the lines
+    typing: bool,   // the search is being typed after `/`
+}
+
+impl Pager {
+    pub fn new(key: char) -> Pager {
+        Pager {
+            key,
+            offset: 0,
+            search: String::new(),
+            typing: false,
+        }
+    }
+
+    /// a line was received, the view stays on the same lines once scrolled up
+    pub fn appended(&mut self) {
+        if self.offset > 0 {
+            self.offset += 1;
+        }
+    }
+
+    /// false once the pager is left
+    pub fn handle(&mut self, keys: &[u8], history: &VecDeque<String>, rows: usize) -> bool {
+        let keys = String::from_utf8_lossy(keys);
+        let mut keys = keys.as_ref();
+        let page = rows.saturating_sub(2).max(1);
+        while let Some(key) = keys.chars().next() {
+            if self.typing {
+                match key {
+                    '\r' | '\n' => {
+                        self.typing = false;
+                        self.find(history, true, true);
+                    }
+                    '\x1b' => {
+                        self.typing = false;
+                        self.search.clear();
+                    }
+                    '\x7f' | '\x08' => {
+                        self.search.pop();
+                    }
+                    key if !key.is_control() => self.search.push(key),
+                    _ => {}
+                }
+                keys = &keys[key.len_utf8()..];
+                continue;
+            }
+            // the escape sequences of the arrows and of the page keys
+            let sequences = [
+                ("\x1b[A", 1isize),
+                ("\x1b[B", -1),
+                ("\x1b[5~", page as isize),
+                ("\x1b[6~", -(page as isize)),
+            ];
+            if let Some((sequence, lines)) = sequences
+                .iter()
+                .find(|(sequence, _)| keys.starts_with(sequence))
+            {
+                self.scroll(history, *lines);
+                keys = &keys[sequence.len()..];
+                continue;
+            }
+            match key {
+                'q' => return false,
+                key if key == self.key => return false,
+                'k' => self.scroll(history, 1),
+                'j' => self.scroll(history, -1),
+                'b' => self.scroll(history, page as isize),
+                ' ' => self.scroll(history, -(page as isize)),
+                'g' => self.offset = history.len().saturating_sub(1),
+                'G' => self.offset = 0,
+                '/' => {
+                    self.typing = true;
+                    self.search.clear();
+                }
+                'n' => self.find(history, true, false),
+                'N' => self.find(history, false, false),
+                _ => {}
+            }
+            keys = &keys[key.len_utf8()..];
+        }
+        true
+    }
+
+    fn scroll(&mut self, history: &VecDeque<String>, lines: isize) {
+        let max = history.len().saturating_sub(1);
+        self.offset = self.offset.saturating_add_signed(lines).min(max);
+    }
+
+    // the matching line becomes the bottom one, the current bottom line only counts when
+    // the search was just typed
+    fn find(&mut self, history: &VecDeque<String>, older: bool, from_bottom: bool) {
+        if self.search.is_empty() || history.is_empty() {
+            return;
+        }
+        let bottom = history.len() - 1 - self.offset.min(history.len() - 1);
+        let matches = |index: &usize| history[*index].contains(&self.search);
+        let found = match (older, from_bottom) {
+            (true, true) => (0..=bottom).rev().find(matches),
+            (true, false) => (0..bottom).rev().find(matches),
+            (false, _) => (bottom + 1..history.len()).find(matches),
+        };
+        if let Some(index) = found {
+            self.offset = history.len() - 1 - index;
+        }
+    }
+
+    /// the lines over every row but the last one, which shows where the view is
+    pub fn draw(&self, out: &mut dyn Write, history: &VecDeque<String>, size: (u16, u16)) {
+        let (width, height) = (size.0 as usize, size.1 as usize);
+        let rows = height.saturating_sub(1);
+        let end = history.len() - self.offset.min(history.len());
+        let start = end.saturating_sub(rows);
+        let lines = history.range(start..end);
+        let _ = write!(out, "\x1b[0m");
+        for (row, line) in (1..=rows).zip(lines.map(Some).chain(std::iter::repeat(None))) {
+            let _ = write!(out, "\x1b[{row};1H\x1b[2K");
+            let Some(line) = line else {
+                continue;
+            };
+            let line = text::truncate_width(line.clone(), width, "…");
+            match self.search.is_empty() {
+                true => {
+                    let _ = write!(out, "{line}");
+                }
+                false => {
+                    let marked = format!("{REVERSE}{}{NO_REVERSE}", self.search);
+                    let _ = write!(out, "{}", line.replace(&self.search, &marked));
+                }
+            }
+        }
+        let status = match self.typing {
+            true => format!("/{}", self.search),
+            false if history.is_empty() => " no line received yet, q to go back ".to_string(),
+            false => format!(
+                " lines {}-{} of {}, / to search, q to go back ",
+                start + 1,
+                end,
+                history.len()
+            ),
+        };
+        let status = text::truncate_width(status, width, "…");
+        let _ = write!(out, "\x1b[{height};1H\x1b[2K{REVERSE}{status}{NO_REVERSE}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drawn(history: &[&str], size: (u16, u16)) -> String {
+        let history: VecDeque<String> = history.iter().map(|line| line.to_string()).collect();
+        let mut out = vec![];
+        Pager::new('p').draw(&mut out, &history, size);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn wide_lines_fit_their_row() {
+        let out = drawn(&["日本語のログです"], (9, 3));
+        assert!(out.contains("\x1b[1;1H\x1b[2K日本語の…"));
+    }
+
+    #[test]
+    fn empty_history() {
+        let out = drawn(&[], (60, 3));
+        assert!(out.contains("no line received yet"));
+        assert!(!out.contains("lines 1-0"));
+    }
+}
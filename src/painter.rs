@@ -0,0 +1,297 @@
+use crate::{
+    frame::{FrameBuffer, Screen},
+    renderer::Renderer,
+};
+use std::{
+    io::{self, Write},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+        mpsc::{SyncSender, TrySendError, sync_channel},
+    },
+    thread::spawn,
+};
+
+// messages waiting for the render thread, a couple of frames
+const BACKLOG: usize = 16;
+// frames sent and not drawn yet, one being drawn and the next one waiting
+const FRAMES_AHEAD: usize = 2;
+
+enum Message {
+    Bytes(Vec<u8>), // escape sequences written by the matrix, in order with the cells
+    Cells(FrameBuffer, u16),
+    Forget,
+    Flush,
+    Enter,
+    Leave(SyncSender<()>), // told once the screen is restored
+}
+
+// what the render thread tells back, for the matrix to never wait for the renderer
+struct Shared {
+    pending: Mutex<Vec<u8>>, // written since the last message
+    size: AtomicU32,         // of the renderer after the last frame, width in the high bits
+    closed: AtomicBool,
+    failed: AtomicBool,  // a frame could not be written
+    frames: AtomicUsize, // flushed by the matrix and not by the render thread yet
+    repaint: AtomicBool, // cells were dropped, the next ones are drawn whole
+}
+
+fn pack((width, height): (u16, u16)) -> u32 {
+    (width as u32) << 16 | height as u32
+}
+
+/// writes the frames to the terminal on a thread of its own from snapshots of their cells,
+/// so a slow terminal never holds back the lines and the keys. the matrix still composes
+/// them on its loop, only the diffing and the writes move here. the sequences written to
+/// its `output` keep their order with the cells, the frames it has no room for are dropped
+pub struct Painter {
+    shared: Arc<Shared>,
+    messages: SyncSender<Message>,
+}
+
+impl Painter {
+    pub fn spawn(mut renderer: Box<dyn Renderer + Send>) -> Painter {
+        let shared = Arc::new(Shared {
+            pending: Mutex::new(vec![]),
+            size: AtomicU32::new(pack(renderer.size())),
+            closed: AtomicBool::new(renderer.closed()),
+            failed: AtomicBool::new(false),
+            frames: AtomicUsize::new(0),
+            repaint: AtomicBool::new(false),
+        });
+        let (messages, received) = sync_channel(BACKLOG);
+        let state = shared.clone();
+        spawn(move || {
+            let mut screen = Screen::default();
+            for message in received {
+                let written = match message {
+                    Message::Bytes(bytes) => renderer.write_all(&bytes),
+                    Message::Cells(cells, repainted) => {
+                        screen.paint(cells, repainted, &mut renderer)
+                    }
+                    Message::Forget => {
+                        screen.forget();
+                        Ok(())
+                    }
+                    Message::Flush => {
+                        state.size.store(pack(renderer.size()), Ordering::Relaxed);
+                        state.closed.store(renderer.closed(), Ordering::Relaxed);
+                        let flushed = renderer.flush();
+                        state.frames.fetch_sub(1, Ordering::Relaxed);
+                        flushed
+                    }
+                    Message::Enter => {
+                        renderer.enter();
+                        Ok(())
+                    }
+                    Message::Leave(done) => {
+                        renderer.leave();
+                        let _ = done.send(());
+                        Ok(())
+                    }
+                };
+                if written.is_err() {
+                    state.failed.store(true, Ordering::Relaxed);
+                }
+            }
+        });
+        Painter { shared, messages }
+    }
+
+    /// the renderer the matrix writes its sequences to
+    pub fn output(&self) -> PainterOutput {
+        PainterOutput(Painter {
+            shared: self.shared.clone(),
+            messages: self.messages.clone(),
+        })
+    }
+
+    /// true while the render thread is still busy with the frames before, the next one is
+    /// better not drawn at all
+    pub fn behind(&self) -> bool {
+        self.shared.frames.load(Ordering::Relaxed) >= FRAMES_AHEAD
+    }
+
+    // the sequences written so far go first. the matrix never waits for the render thread
+    // but to enter or leave the screen: what it has no room for is dropped, the sequences
+    // are kept for the next message and the cells are drawn whole by the next frame
+    fn send(&self, message: Message) {
+        let mut pending = self.shared.pending.lock().unwrap();
+        let bytes = std::mem::take(&mut *pending);
+        if let Message::Enter | Message::Leave(_) = message {
+            drop(pending);
+            if !bytes.is_empty() {
+                let _ = self.messages.send(Message::Bytes(bytes));
+            }
+            let _ = self.messages.send(message);
+            return;
+        }
+        // counted before the render thread can be done with it
+        if let Message::Flush = message {
+            self.shared.frames.fetch_add(1, Ordering::Relaxed);
+        }
+        if !bytes.is_empty()
+            && let Err(TrySendError::Full(Message::Bytes(bytes))) =
+                self.messages.try_send(Message::Bytes(bytes))
+        {
+            *pending = bytes;
+            self.dropped(message);
+            return;
+        }
+        if let Err(TrySendError::Full(message)) = self.messages.try_send(message) {
+            self.dropped(message);
+        }
+    }
+
+    // the renderer is flushed with the next frame anyway
+    fn dropped(&self, message: Message) {
+        match message {
+            Message::Cells(..) | Message::Forget => {
+                self.shared.repaint.store(true, Ordering::Relaxed);
+            }
+            Message::Flush => {
+                self.shared.frames.fetch_sub(1, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+
+    /// the cells which changed since the last frame are drawn, the rows up to `repainted`
+    /// whole
+    pub fn paint(&self, cells: FrameBuffer, repainted: u16) {
+        let repainted = match self.shared.repaint.swap(false, Ordering::Relaxed) {
+            true => u16::MAX,
+            false => repainted,
+        };
+        self.send(Message::Cells(cells, repainted));
+    }
+
+    /// every cell is drawn again by the next frame
+    pub fn forget(&self) {
+        self.send(Message::Forget);
+    }
+}
+
+/// the renderer of a painter, what is written is drawn on its thread with the next flush
+pub struct PainterOutput(Painter);
+
+impl Write for PainterOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.shared.pending.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.send(Message::Flush);
+        match self.0.shared.failed.load(Ordering::Relaxed) {
+            true => Err(io::Error::other("could not draw the frame")),
+            false => Ok(()),
+        }
+    }
+}
+
+impl Renderer for PainterOutput {
+    // as of the last frame drawn
+    fn size(&self) -> (u16, u16) {
+        let size = self.0.shared.size.load(Ordering::Relaxed);
+        ((size >> 16) as u16, size as u16)
+    }
+
+    fn enter(&mut self) {
+        self.0.send(Message::Enter);
+    }
+
+    // waits for the screen to be restored, before a command takes the terminal over
+    fn leave(&mut self) {
+        let (done, restored) = sync_channel(1);
+        self.0.send(Message::Leave(done));
+        let _ = restored.recv();
+    }
+
+    fn closed(&self) -> bool {
+        self.0.shared.closed.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, effects::Intensity, frame::Styled, renderer::MemoryRenderer};
+
+    // a terminal nobody reads from until the gate is opened
+    struct Stalled {
+        inner: MemoryRenderer,
+        gate: Arc<Mutex<()>>,
+    }
+
+    impl Write for Stalled {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let _open = self.gate.lock().unwrap();
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Renderer for Stalled {
+        fn size(&self) -> (u16, u16) {
+            self.inner.size()
+        }
+
+        fn enter(&mut self) {}
+
+        fn leave(&mut self) {}
+    }
+
+    fn frame(glyph: char) -> FrameBuffer {
+        let mut cells = FrameBuffer::new(4, 2);
+        let cell = Styled {
+            glyph: glyph.to_string(),
+            color: Color::Default,
+            intensity: Intensity::Normal,
+        };
+        cells.set(1, 1, cell);
+        cells
+    }
+
+    #[test]
+    fn a_stalled_terminal_drops_frames() {
+        let memory = MemoryRenderer::new(4, 2);
+        let gate = Arc::new(Mutex::new(()));
+        let closed = gate.lock().unwrap();
+        let painter = Painter::spawn(Box::new(Stalled {
+            inner: memory.clone(),
+            gate: gate.clone(),
+        }));
+        let mut output = painter.output();
+        // the matrix skips the frames while the painter is behind
+        let mut drawn = 0;
+        for glyph in ('a'..='z').cycle().take(1000) {
+            if !painter.behind() {
+                write!(output, "<{glyph}>").unwrap();
+                painter.paint(frame(glyph), 0);
+                output.flush().unwrap();
+                drawn += 1;
+            }
+        }
+        assert_eq!(drawn, FRAMES_AHEAD);
+        // far more cells than the render thread has room for, none of them waits
+        for glyph in ('a'..='z').cycle().take(1000) {
+            painter.paint(frame(glyph), 0);
+        }
+        write!(output, "\x07").unwrap();
+        drop(closed);
+        // the screen is restored once everything before was drawn
+        output.leave();
+        painter.paint(frame('!'), 0);
+        output.leave();
+        let contents = String::from_utf8(memory.contents()).unwrap();
+        assert!(contents.starts_with("<a>"), "{contents:?}");
+        // the sequences are all written, the cells dropped are drawn whole by the next frame
+        let (_, last) = contents.split_once("<b>").unwrap();
+        let (_, last) = last.split_once('\x07').unwrap();
+        assert!(last.contains("!   "), "{contents:?}");
+    }
+}
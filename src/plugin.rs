@@ -0,0 +1,90 @@
+use crate::{sources::InputLine, transform::Transform};
+use std::{fs, io, path::Path};
+use wasmtime::{Engine, Instance, Memory, Module, Store, TypedFunc};
+
+/// a WebAssembly module transforming the lines, given nothing to import so it only sees
+/// the lines. it exports its `memory`, `alloc(len: i32) -> i32` returning where the host
+/// writes the UTF-8 text of a line, and `transform(ptr: i32, len: i32) -> i64` returning
+/// the new text as `ptr << 32 | len`, or a negative value to drop the line
+pub struct WasmPlugin {
+    name: String,
+    store: Store<()>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    transform: TypedFunc<(i32, i32), i64>,
+}
+
+/// the `.wasm` files of the directory, in the order of their names
+pub fn load_dir(dir: &Path) -> io::Result<Vec<WasmPlugin>> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "wasm"));
+    paths.sort();
+    let engine = Engine::default();
+    paths
+        .iter()
+        .map(|path| WasmPlugin::load(&engine, path))
+        .collect()
+}
+
+impl WasmPlugin {
+    pub fn load(engine: &Engine, path: &Path) -> io::Result<WasmPlugin> {
+        let name = path.display().to_string();
+        let context = |err: wasmtime::Error| io::Error::other(format!("{name}: {err}"));
+        let module = Module::from_file(engine, path).map_err(context)?;
+        let mut store = Store::new(engine, ());
+        let instance = Instance::new(&mut store, &module, &[]).map_err(context)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| io::Error::other(format!("{name}: no memory exported")))?;
+        let alloc = instance
+            .get_typed_func(&mut store, "alloc")
+            .map_err(context)?;
+        let transform = instance
+            .get_typed_func(&mut store, "transform")
+            .map_err(context)?;
+        Ok(WasmPlugin {
+            name,
+            store,
+            memory,
+            alloc,
+            transform,
+        })
+    }
+
+    fn call(&mut self, text: &str) -> wasmtime::Result<Option<String>> {
+        let len = i32::try_from(text.len())?;
+        let ptr = self.alloc.call(&mut self.store, len)?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, text.as_bytes())?;
+        let packed = self.transform.call(&mut self.store, (ptr, len))?;
+        if packed < 0 {
+            return Ok(None);
+        }
+        let (ptr, len) = ((packed >> 32) as usize, packed as u32 as usize);
+        let mut out = vec![0; len];
+        self.memory.read(&self.store, ptr, &mut out)?;
+        Ok(Some(String::from_utf8_lossy(&out).into_owned()))
+    }
+}
+
+impl Transform for WasmPlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    // the traps of the plugin show up in place of the lines
+    fn apply(&mut self, mut line: InputLine) -> Option<InputLine> {
+        match self.call(&line.text) {
+            Ok(text) => {
+                line.text = text?;
+                Some(line)
+            }
+            Err(err) => {
+                line.text = format!("{}: {err}", self.name);
+                Some(line)
+            }
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use std::io;
+
+// lowest real-time priority, enough to run ahead of every normal thread
+const FIFO_PRIORITY: libc::c_int = 1;
+const NICE_PRIORITY: libc::c_int = -10;
+
+/// raise the priority of the calling thread, the real-time FIFO policy when permitted
+/// else a lower nice value, tells which one was applied
+pub fn raise_priority() -> io::Result<&'static str> {
+    let param = libc::sched_param {
+        sched_priority: FIFO_PRIORITY,
+    };
+    // SAFETY: plain syscall on the calling thread with a valid parameter
+    if unsafe { libc::sched_setscheduler(0, libc::SCHED_FIFO, &param) } == 0 {
+        return Ok("SCHED_FIFO");
+    }
+    // SAFETY: plain syscall on the calling thread
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, NICE_PRIORITY) } == 0 {
+        return Ok("nice -10");
+    }
+    Err(io::Error::last_os_error())
+}
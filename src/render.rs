@@ -0,0 +1,275 @@
+use crate::{
+    Color,
+    effects::Intensity,
+    frame::{self, FrameBuffer, Styled},
+};
+use font8x8::{
+    BASIC_FONTS, BLOCK_FONTS, BOX_FONTS, GREEK_FONTS, HIRAGANA_FONTS, LATIN_FONTS, UnicodeFonts,
+};
+use gif::{Encoder, Frame, Repeat};
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+// a cell is 8 pixels wide, every line of the 8x8 glyphs is doubled to keep the terminal's
+// proportions
+const CELL_WIDTH: usize = 8;
+const CELL_HEIGHT: usize = 16;
+const COLORS: [Color; 9] = [
+    Color::Default,
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+];
+const INTENSITIES: [Intensity; 3] = [Intensity::Normal, Intensity::Dim, Intensity::Bold];
+// size of an SVG cell in pixels, the glyphs are scaled along
+const SVG_FONT_SIZE: f64 = 14.;
+const SVG_CELL_WIDTH: f64 = SVG_FONT_SIZE * 0.6;
+const SVG_CELL_HEIGHT: f64 = SVG_FONT_SIZE * 1.2;
+
+/// a clip of the animation drawn off-screen, a frame at a time
+pub trait Clip {
+    fn record_frame(&mut self, buffer: &FrameBuffer);
+
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+/// the frames drawn off-screen, encoded into an animated GIF looping forever
+pub struct GifRenderer {
+    encoder: Encoder<BufWriter<File>>,
+    // in hundredths of a second, the unit of GIF delays
+    delay: u16,
+    width: u16,
+    height: u16,
+    error: Option<io::Error>,
+}
+
+impl GifRenderer {
+    pub fn create(
+        path: &Path,
+        (cols, rows): (u16, u16),
+        period: Duration,
+    ) -> io::Result<GifRenderer> {
+        // the dimensions of a GIF are 16 bits
+        let pixels = |cells: u16, cell: usize| cells.checked_mul(cell as u16);
+        let (Some(width), Some(height)) = (pixels(cols, CELL_WIDTH), pixels(rows, CELL_HEIGHT))
+        else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{cols}x{rows} cells do not fit in a GIF"),
+            ));
+        };
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder =
+            Encoder::new(file, width, height, &palette()).map_err(io::Error::other)?;
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(io::Error::other)?;
+        Ok(GifRenderer {
+            encoder,
+            delay: (period.as_millis() / 10).clamp(2, u16::MAX as u128) as u16,
+            width,
+            height,
+            error: None,
+        })
+    }
+}
+
+impl Clip for GifRenderer {
+    // the frame is cropped or padded to the size of the GIF, the glyphs missing from the
+    // font are left blank
+    fn record_frame(&mut self, buffer: &FrameBuffer) {
+        if self.error.is_some() {
+            return;
+        }
+        let (width, height) = (self.width as usize, self.height as usize);
+        let mut pixels = vec![0; width * height];
+        for (y, row) in buffer.rows().iter().enumerate().take(height / CELL_HEIGHT) {
+            for (x, cell) in row.iter().enumerate().take(width / CELL_WIDTH) {
+                let Some(bitmap) = cell.glyph.chars().next().and_then(bitmap) else {
+                    continue;
+                };
+                let ink = ink(cell);
+                for (line, bits) in bitmap.iter().enumerate() {
+                    for bit in (0..CELL_WIDTH).filter(|bit| bits & 1 << bit != 0) {
+                        let at = (y * CELL_HEIGHT + line * 2) * width + x * CELL_WIDTH + bit;
+                        pixels[at] = ink;
+                        pixels[at + width] = ink;
+                    }
+                }
+            }
+        }
+        let mut frame = Frame::from_indexed_pixels(self.width, self.height, pixels, None);
+        frame.delay = self.delay;
+        if let Err(err) = self.encoder.write_frame(&frame) {
+            self.error = Some(io::Error::other(err));
+        }
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        self.encoder
+            .into_inner()?
+            .into_inner()
+            .map_err(io::Error::other)?
+            .sync_all()
+    }
+}
+
+/// the frames as groups of text elements, each one shown in turn by a CSS animation
+pub struct SvgRenderer {
+    path: PathBuf,
+    size: (u16, u16),
+    period: Duration,
+    frames: Vec<String>,
+}
+
+impl SvgRenderer {
+    pub fn new(path: &Path, size: (u16, u16), period: Duration) -> SvgRenderer {
+        SvgRenderer {
+            path: path.to_path_buf(),
+            size,
+            period,
+            frames: vec![],
+        }
+    }
+}
+
+impl Clip for SvgRenderer {
+    // a text element per row, a tspan per run of cells of the same colors
+    fn record_frame(&mut self, buffer: &FrameBuffer) {
+        let mut group = String::new();
+        let delay = self.period.as_secs_f64() * self.frames.len() as f64;
+        let _ = write!(
+            group,
+            "<g class=\"f\" style=\"animation-delay:{delay:.3}s\">"
+        );
+        for (y, row) in buffer.rows().iter().enumerate().take(self.size.1 as usize) {
+            let row = &row[..row.len().min(self.size.0 as usize)];
+            if row.iter().all(|cell| cell.glyph.trim().is_empty()) {
+                continue;
+            }
+            let baseline = (y as f64 + 1.) * SVG_CELL_HEIGHT - SVG_FONT_SIZE * 0.3;
+            let _ = write!(group, "<text y=\"{baseline:.1}\">");
+            let mut x = 0;
+            for run in row.chunk_by(|a, b| (a.color, a.intensity) == (b.color, b.intensity)) {
+                let text: String = run.iter().map(|cell| frame::escape(&cell.glyph)).collect();
+                let at = x as f64 * SVG_CELL_WIDTH;
+                let _ = match frame::style(run[0].color, run[0].intensity, true) {
+                    Some(style) => write!(
+                        group,
+                        "<tspan x=\"{at:.1}\" style=\"{style}\">{text}</tspan>"
+                    ),
+                    None => write!(group, "<tspan x=\"{at:.1}\">{text}</tspan>"),
+                };
+                x += run.len();
+            }
+            group += "</text>";
+        }
+        group += "</g>\n";
+        self.frames.push(group);
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        let (width, height) = (
+            self.size.0 as f64 * SVG_CELL_WIDTH,
+            self.size.1 as f64 * SVG_CELL_HEIGHT,
+        );
+        let frames = self.frames.len().max(1);
+        let duration = self.period.as_secs_f64() * frames as f64;
+        // every frame is visible for its share of the loop, then hidden until its next turn
+        let share = 100. / frames as f64;
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {width:.1} {height:.1}\" \
+             width=\"{width:.1}\" height=\"{height:.1}\">\n<style>\n\
+             text {{ font-family: monospace; font-size: {SVG_FONT_SIZE}px; fill: #ccc; \
+             white-space: pre; }}\n\
+             .f {{ visibility: hidden; animation: show {duration:.3}s step-end infinite; }}\n\
+             @keyframes show {{ 0% {{ visibility: visible; }} {share:.4}% \
+             {{ visibility: hidden; }} }}\n</style>\n\
+             <rect width=\"100%\" height=\"100%\" fill=\"#000\"/>\n"
+        );
+        for frame in &self.frames {
+            svg += frame;
+        }
+        svg += "</svg>\n";
+        fs::write(&self.path, svg)
+    }
+}
+
+fn bitmap(glyph: char) -> Option<[u8; 8]> {
+    BASIC_FONTS
+        .get(glyph)
+        .or_else(|| LATIN_FONTS.get(glyph))
+        .or_else(|| BOX_FONTS.get(glyph))
+        .or_else(|| BLOCK_FONTS.get(glyph))
+        .or_else(|| GREEK_FONTS.get(glyph))
+        .or_else(|| HIRAGANA_FONTS.get(glyph))
+}
+
+// index 0 is the black background, then every color at every intensity
+fn ink(cell: &Styled) -> u8 {
+    let color = COLORS
+        .iter()
+        .position(|color| *color == cell.color)
+        .unwrap_or(0);
+    let intensity = INTENSITIES
+        .iter()
+        .position(|i| *i == cell.intensity)
+        .unwrap_or(0);
+    (1 + color * INTENSITIES.len() + intensity) as u8
+}
+
+// the xterm palette, the dim colors at half their brightness and the bold ones brighter
+fn palette() -> Vec<u8> {
+    let mut palette = vec![0, 0, 0];
+    for color in COLORS {
+        let rgb: [u8; 3] = match color {
+            Color::Default => [0xcc, 0xcc, 0xcc],
+            Color::Black => [0x4d, 0x4d, 0x4d],
+            Color::Red => [0xcd, 0x00, 0x00],
+            Color::Green => [0x00, 0xcd, 0x00],
+            Color::Yellow => [0xcd, 0xcd, 0x00],
+            Color::Blue => [0x00, 0x00, 0xee],
+            Color::Magenta => [0xcd, 0x00, 0xcd],
+            Color::Cyan => [0x00, 0xcd, 0xcd],
+            Color::White => [0xe5, 0xe5, 0xe5],
+        };
+        for intensity in INTENSITIES {
+            palette.extend(rgb.map(|channel| match intensity {
+                Intensity::Normal => channel,
+                Intensity::Dim => channel / 2,
+                Intensity::Bold => channel.saturating_add(0x32),
+            }));
+        }
+    }
+    palette
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempPath;
+
+    #[test]
+    fn oversized_gif_is_refused() {
+        let path = TempPath::new("oversized.gif");
+        let period = Duration::from_millis(100);
+        let err = GifRenderer::create(&path, (9000, 10), period)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+    }
+}
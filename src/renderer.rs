@@ -0,0 +1,227 @@
+use crate::{
+    MATRIX_MODES, MOUSE_MODES,
+    serve::{self, Viewer},
+    term::{self, TermMode},
+};
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
+use terminal_size::{Height, Width, terminal_size};
+
+/// where the frames are drawn: the terminal, a client of `serve`, a buffer in memory...
+pub trait Renderer: Write {
+    /// columns and rows the frames are drawn at
+    fn size(&self) -> (u16, u16);
+
+    /// switch to the screen the frames are drawn on, and back
+    fn enter(&mut self);
+
+    fn leave(&mut self);
+
+    /// true once nobody is looking at the frames anymore
+    fn closed(&self) -> bool {
+        false
+    }
+}
+
+// bytes of a frame the renderers allocate upfront, enough for most
+const FRAME_CAPACITY: usize = 64 * 1024;
+
+/// the terminal the program runs in, its modes are recorded for `reset` to restore them.
+/// what is written is kept until the frame is flushed in a single write
+#[derive(Default)]
+pub struct Terminal {
+    mouse: bool,
+    pending: Vec<u8>,
+}
+
+impl Terminal {
+    pub fn new() -> Terminal {
+        Terminal {
+            pending: Vec::with_capacity(FRAME_CAPACITY),
+            ..Terminal::default()
+        }
+    }
+
+    /// the clicks are reported along with the keys
+    pub fn with_mouse(mut self, mouse: bool) -> Terminal {
+        self.mouse = mouse;
+        self
+    }
+
+    fn modes(&self) -> &'static [TermMode] {
+        match self.mouse {
+            true => &MOUSE_MODES,
+            false => &MATRIX_MODES,
+        }
+    }
+}
+
+impl Write for Terminal {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        let written = stdout.write_all(&self.pending);
+        self.pending.clear();
+        written.and_then(|()| stdout.flush())
+    }
+}
+
+impl Renderer for Terminal {
+    fn size(&self) -> (u16, u16) {
+        terminal_size_or_default()
+    }
+
+    fn enter(&mut self) {
+        term::enter(self.modes());
+    }
+
+    fn leave(&mut self) {
+        let _ = self.flush();
+        term::leave(self.modes());
+    }
+}
+
+pub fn terminal_size_or_default() -> (u16, u16) {
+    match terminal_size() {
+        Some((Width(width), Height(height))) => (width, height),
+        None => serve::DEFAULT_SIZE,
+    }
+}
+
+/// a client of `serve`, at the size it negotiated until it leaves. the frames are sent in
+/// a single write like to the terminal
+pub struct Remote<W: Write> {
+    out: W,
+    viewer: Arc<Viewer>,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> Remote<W> {
+    pub fn new(out: W, viewer: Arc<Viewer>) -> Remote<W> {
+        Remote {
+            out,
+            viewer,
+            pending: Vec::with_capacity(FRAME_CAPACITY),
+        }
+    }
+}
+
+impl<W: Write> Write for Remote<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let written = self.out.write_all(&self.pending);
+        self.pending.clear();
+        written.and_then(|()| self.out.flush())
+    }
+}
+
+impl<W: Write> Renderer for Remote<W> {
+    fn size(&self) -> (u16, u16) {
+        self.viewer.size()
+    }
+
+    // the state file is about the local terminal only
+    fn enter(&mut self) {
+        for mode in MATRIX_MODES {
+            let _ = write!(self.out, "{}", mode.enter_sequence());
+        }
+    }
+
+    fn leave(&mut self) {
+        let _ = self.flush();
+        for mode in MATRIX_MODES.iter().rev() {
+            let _ = write!(self.out, "{}", mode.exit_sequence());
+        }
+        let _ = self.out.flush();
+    }
+
+    fn closed(&self) -> bool {
+        self.viewer.left()
+    }
+}
+
+/// the frames kept in memory at a fixed size, every clone shares the same bytes. the same
+/// seed and the same lines draw the same bytes, for the tests to compare them
+#[derive(Clone)]
+pub struct MemoryRenderer {
+    size: (u16, u16),
+    bytes: Arc<Mutex<Vec<u8>>>,
+}
+
+impl MemoryRenderer {
+    pub fn new(width: u16, height: u16) -> MemoryRenderer {
+        MemoryRenderer {
+            size: (width, height),
+            bytes: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// everything drawn so far, escape sequences included
+    pub fn contents(&self) -> Vec<u8> {
+        self.bytes.lock().unwrap().clone()
+    }
+}
+
+impl Write for MemoryRenderer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Renderer for MemoryRenderer {
+    fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    fn enter(&mut self) {}
+
+    fn leave(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_renderer_clones_share_the_bytes() {
+        let out = MemoryRenderer::new(40, 10);
+        out.clone().write_all(b"frame").unwrap();
+        assert_eq!(out.size(), (40, 10));
+        assert_eq!(out.contents(), b"frame");
+    }
+
+    #[test]
+    fn remote_frames_come_between_the_modes() {
+        let viewer = Arc::new(Viewer::new());
+        let mut remote = Remote::new(vec![], viewer.clone());
+        remote.enter();
+        remote.write_all(b"frame").unwrap();
+        remote.leave();
+        let enter: String = MATRIX_MODES
+            .iter()
+            .map(|mode| mode.enter_sequence())
+            .collect();
+        let leave: String = (MATRIX_MODES.iter().rev())
+            .map(|mode| mode.exit_sequence())
+            .collect();
+        assert_eq!(remote.out, format!("{enter}frame{leave}").into_bytes());
+        assert!(!remote.closed());
+        viewer.leave();
+        assert!(remote.closed());
+    }
+}
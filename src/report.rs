@@ -0,0 +1,171 @@
+use crate::{Counters, format_uptime, sources::InputLine, text};
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs, io,
+    path::PathBuf,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+// screens kept, every other one is dropped and the period doubles once they are all taken
+const MAX_FRAMES: usize = 6;
+const FIRST_FRAME_PERIOD: Duration = Duration::from_secs(10);
+// bars of the rate chart, the seconds are summed into as many buckets
+const CHART_BARS: usize = 120;
+const TOP_ALERTS: usize = 10;
+// alert templates remembered before the least seen are forgotten
+const MAX_ALERTS: usize = 1000;
+
+/// what happened during the session, written as a self-contained HTML page on exit
+pub struct SessionReport {
+    path: PathBuf,
+    started: Instant,
+    rates: Vec<u64>, // lines received during every second
+    sources: HashMap<String, u64>,
+    alerts: HashMap<String, u64>, // per template
+    frames: Vec<(Duration, String)>,
+    frame_period: Duration,
+}
+
+impl SessionReport {
+    pub fn new(path: PathBuf) -> SessionReport {
+        SessionReport {
+            path,
+            started: Instant::now(),
+            rates: vec![],
+            sources: HashMap::new(),
+            alerts: HashMap::new(),
+            frames: vec![],
+            frame_period: FIRST_FRAME_PERIOD,
+        }
+    }
+
+    pub fn record_line(&mut self, line: &InputLine, alert: bool) {
+        let second = self.started.elapsed().as_secs() as usize;
+        if self.rates.len() <= second {
+            self.rates.resize(second + 1, 0);
+        }
+        self.rates[second] += 1;
+        *self.sources.entry(line.source.clone()).or_insert(0) += 1;
+        if alert {
+            if self.alerts.len() >= MAX_ALERTS {
+                self.alerts.retain(|_, count| *count > 1);
+            }
+            *self.alerts.entry(text::template(&line.text)).or_insert(0) += 1;
+        }
+    }
+
+    /// a screen is wanted for this frame
+    pub fn frame_due(&self) -> bool {
+        let captured = self.frames.len() as u32;
+        self.started.elapsed() >= self.frame_period * (captured + 1)
+    }
+
+    pub fn record_frame(&mut self, screen: String) {
+        self.frames.push((self.started.elapsed(), screen));
+        if self.frames.len() == MAX_FRAMES {
+            // the frames taken every other period are kept
+            let mut index = 0;
+            self.frames.retain(|_| {
+                index += 1;
+                index % 2 == 0
+            });
+            self.frame_period *= 2;
+        }
+    }
+
+    pub fn finish(self, counters: &Counters) -> io::Result<()> {
+        let mut html = String::new();
+        let uptime = format_uptime(self.started.elapsed());
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>logmatrix session</title>\
+             <style>{STYLE}</style></head><body>\n<h1>logmatrix session, {uptime}</h1>\n"
+        );
+        let _ = writeln!(
+            html,
+            "<p>{} lines received, {} dropped, {} missing, {} late frames</p>",
+            counters.received.load(Ordering::Relaxed),
+            counters.dropped.load(Ordering::Relaxed),
+            counters.missing.load(Ordering::Relaxed),
+            counters.late_frames.load(Ordering::Relaxed),
+        );
+
+        html += "<h2>Lines per second</h2>\n";
+        html += &rate_chart(&self.rates);
+
+        html += "<h2>Sources</h2>\n<table><tr><th>source</th><th>lines</th></tr>\n";
+        for (source, count) in sorted(&self.sources, usize::MAX) {
+            let _ = writeln!(html, "<tr><td>{}</td><td>{count}</td></tr>", escape(source));
+        }
+        html += "</table>\n";
+
+        html += "<h2>Top alerts</h2>\n";
+        if self.alerts.is_empty() {
+            html += "<p>none</p>\n";
+        } else {
+            html += "<table><tr><th>line, numbers zeroed</th><th>count</th></tr>\n";
+            for (alert, count) in sorted(&self.alerts, TOP_ALERTS) {
+                let _ = writeln!(html, "<tr><td>{}</td><td>{count}</td></tr>", escape(alert));
+            }
+            html += "</table>\n";
+        }
+
+        html += "<h2>Frames</h2>\n";
+        for (at, screen) in &self.frames {
+            let _ = writeln!(
+                html,
+                "<h3>after {}</h3>\n<pre>{}</pre>",
+                format_uptime(*at),
+                escape(screen)
+            );
+        }
+        html += "</body></html>\n";
+        fs::write(&self.path, html)
+    }
+}
+
+const STYLE: &str = "body{background:#000;color:#0f0;font-family:monospace;margin:2em}\
+    table{border-collapse:collapse}td,th{border:1px solid #060;padding:2px 8px;text-align:left}\
+    pre{border:1px solid #060;padding:4px;display:inline-block;line-height:1}rect{fill:#0f0}";
+
+// the most common first, the ties by name
+fn sorted(counts: &HashMap<String, u64>, limit: usize) -> Vec<(&String, &u64)> {
+    let mut sorted: Vec<_> = counts.iter().collect();
+    sorted.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    sorted.truncate(limit);
+    sorted
+}
+
+// bar chart of the rate, several seconds per bar on a long session
+fn rate_chart(rates: &[u64]) -> String {
+    let (width, height) = (CHART_BARS * 5, 100);
+    let per_bar = rates.len().div_ceil(CHART_BARS).max(1);
+    let bars: Vec<f64> = rates
+        .chunks(per_bar)
+        .map(|chunk| chunk.iter().sum::<u64>() as f64 / chunk.len() as f64)
+        .collect();
+    let max = bars.iter().cloned().fold(0., f64::max).max(1.);
+    let mut svg = format!(
+        "<p>up to {max:.1} lines per second, {per_bar} s per bar</p>\n\
+         <svg width=\"{width}\" height=\"{height}\" xmlns=\"http://www.w3.org/2000/svg\">"
+    );
+    for (index, rate) in bars.iter().enumerate() {
+        let bar = (rate / max * height as f64).round();
+        let _ = write!(
+            svg,
+            "<rect x=\"{}\" y=\"{}\" width=\"4\" height=\"{bar}\"/>",
+            index * 5,
+            height as f64 - bar
+        );
+    }
+    svg + "</svg>\n"
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
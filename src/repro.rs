@@ -0,0 +1,284 @@
+use crate::sources::InputLine;
+use std::{
+    collections::VecDeque,
+    env,
+    fs::{self, DirBuilder, File},
+    io::{self, BufWriter, Read, Write},
+    os::unix::fs::DirBuilderExt,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+const BUNDLE_VERSION: u32 = 1;
+// captures started by the process, for each one to get a directory of its own
+static CAPTURES: AtomicU32 = AtomicU32::new(0);
+
+/// records what makes a run reproducible: arguments, seed, input and terminal size per frame.
+/// the input and the sizes are written to a private directory as they come, and put in the
+/// bundle by `finish`
+pub struct ReproCapture {
+    path: PathBuf,
+    seed: u64,
+    args: Vec<String>,
+    dir: PathBuf,
+    input: BufWriter<File>,
+    sizes: BufWriter<File>,
+    failed: Option<io::Error>, // the first write which failed, reported by `finish`
+}
+
+impl ReproCapture {
+    pub fn new(path: PathBuf, seed: u64) -> io::Result<ReproCapture> {
+        let capture = CAPTURES.fetch_add(1, Ordering::Relaxed);
+        let dir = env::temp_dir().join(format!("logmatrix-repro-{}-{capture}", process::id()));
+        // the lines can be anything, nobody else reads them
+        DirBuilder::new().recursive(true).mode(0o700).create(&dir)?;
+        Ok(ReproCapture {
+            path,
+            seed,
+            args: env::args().collect(),
+            input: BufWriter::new(File::create(dir.join("input.tsv"))?),
+            sizes: BufWriter::new(File::create(dir.join("sizes.tsv"))?),
+            dir,
+            failed: None,
+        })
+    }
+
+    pub fn record_line(&mut self, frame: u64, line: &InputLine) {
+        let written = writeln!(
+            self.input,
+            "{frame}\t{}\t{}",
+            escape(&line.source),
+            escape(&line.text)
+        );
+        if let Err(err) = written {
+            self.failed.get_or_insert(err);
+        }
+    }
+
+    pub fn record_size(&mut self, frame: u64, width: u16, height: u16) {
+        let written = writeln!(self.sizes, "{frame}\t{width}\t{height}");
+        if let Err(err) = written {
+            self.failed.get_or_insert(err);
+        }
+    }
+
+    /// write the bundle, a plain tar archive
+    pub fn finish(mut self, frames: u64) -> io::Result<()> {
+        if let Some(err) = self.failed.take() {
+            return Err(err);
+        }
+        self.input.flush()?;
+        self.sizes.flush()?;
+        let mut meta = format!(
+            "version {BUNDLE_VERSION}\nseed {}\nframes {frames}\n",
+            self.seed
+        );
+        for arg in &self.args {
+            meta += &format!("arg {}\n", escape(arg));
+        }
+
+        let mut bundle = tar::Builder::new(File::create(&self.path)?);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(meta.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        bundle.append_data(&mut header, "meta", meta.as_bytes())?;
+        for name in ["input.tsv", "sizes.tsv"] {
+            let mut file = File::open(self.dir.join(name))?;
+            let mut header = tar::Header::new_gnu();
+            header.set_size(file.metadata()?.len());
+            header.set_mode(0o644);
+            header.set_cksum();
+            bundle.append_data(&mut header, name, &mut file)?;
+        }
+        bundle.finish()
+    }
+}
+
+// gone with the bundle written or not
+impl Drop for ReproCapture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// feeds a captured run back frame by frame
+pub struct ReproReplay {
+    pub args: Vec<String>,
+    pub seed: u64,
+    frames: u64,
+    input: VecDeque<(u64, InputLine)>,
+    sizes: Vec<(u64, u16, u16)>,
+}
+
+impl ReproReplay {
+    pub fn open(path: &Path) -> io::Result<ReproReplay> {
+        let mut replay = ReproReplay {
+            args: vec![],
+            seed: 0,
+            frames: 0,
+            input: VecDeque::new(),
+            sizes: vec![],
+        };
+        let mut bundle = tar::Archive::new(File::open(path)?);
+        for entry in bundle.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.to_string_lossy().into_owned();
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            match name.as_str() {
+                "meta" => replay.parse_meta(&content)?,
+                "input.tsv" => {
+                    for fields in content
+                        .lines()
+                        .map(|line| line.splitn(3, '\t').collect::<Vec<_>>())
+                    {
+                        let [frame, source, text] = fields[..] else {
+                            return Err(invalid("malformed input.tsv"));
+                        };
+                        let line = InputLine::new(&unescape(source), unescape(text));
+                        replay.input.push_back((parse(frame)?, line));
+                    }
+                }
+                "sizes.tsv" => {
+                    for fields in content
+                        .lines()
+                        .map(|line| line.split('\t').collect::<Vec<_>>())
+                    {
+                        let [frame, width, height] = fields[..] else {
+                            return Err(invalid("malformed sizes.tsv"));
+                        };
+                        replay
+                            .sizes
+                            .push((parse(frame)?, parse(width)?, parse(height)?));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(replay)
+    }
+
+    fn parse_meta(&mut self, meta: &str) -> io::Result<()> {
+        for line in meta.lines() {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "version" if parse::<u32>(value)? > BUNDLE_VERSION => {
+                    return Err(invalid("bundle made by a newer logmatrix"));
+                }
+                "seed" => self.seed = parse(value)?,
+                "frames" => self.frames = parse(value)?,
+                "arg" => self.args.push(unescape(value)),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn lines_at(&mut self, frame: u64) -> Vec<InputLine> {
+        let mut lines = vec![];
+        while self.input.front().is_some_and(|(at, _)| *at <= frame) {
+            lines.extend(self.input.pop_front().map(|(_, line)| line));
+        }
+        lines
+    }
+
+    /// size of the captured terminal, the last change at or before the frame
+    pub fn size_at(&self, frame: u64) -> Option<(u16, u16)> {
+        self.sizes
+            .iter()
+            .take_while(|(at, _, _)| *at <= frame)
+            .last()
+            .map(|(_, width, height)| (*width, *height))
+    }
+
+    pub fn is_over(&self, frame: u64) -> bool {
+        frame >= self.frames
+    }
+}
+
+fn escape(raw: &str) -> String {
+    raw.replace('\\', r"\\")
+        .replace('\t', r"\t")
+        .replace('\n', r"\n")
+}
+
+fn unescape(escaped: &str) -> String {
+    let mut raw = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            raw.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => raw.push('\t'),
+            Some('n') => raw.push('\n'),
+            Some(other) => raw.push(other),
+            None => {}
+        }
+    }
+    raw
+}
+
+fn parse<T: std::str::FromStr>(raw: &str) -> io::Result<T> {
+    raw.parse()
+        .map_err(|_| invalid(&format!("invalid number `{raw}` in bundle")))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempPath;
+
+    #[test]
+    fn bundle_round_trip() {
+        let path = TempPath::new("bundle.tar");
+        let mut capture = ReproCapture::new(path.to_path_buf(), 42).unwrap();
+        let dir = capture.dir.clone();
+        capture.record_size(0, 80, 24);
+        capture.record_line(0, &InputLine::new("app\tone", "first\tline\\n".to_string()));
+        capture.record_line(3, &InputLine::new("stdin", "second\nline".to_string()));
+        capture.record_size(5, 120, 40);
+        capture.finish(9).unwrap();
+        assert!(!dir.exists());
+
+        let mut replay = ReproReplay::open(&path).unwrap();
+        assert_eq!(replay.seed, 42);
+        let lines = replay.lines_at(0);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].source, "app\tone");
+        assert_eq!(lines[0].text, "first\tline\\n");
+        assert!(replay.lines_at(2).is_empty());
+        assert_eq!(replay.lines_at(3)[0].text, "second\nline");
+        assert_eq!(replay.size_at(4), Some((80, 24)));
+        assert_eq!(replay.size_at(5), Some((120, 40)));
+        assert!(!replay.is_over(8));
+        assert!(replay.is_over(9));
+    }
+
+    #[test]
+    fn escape_round_trip() {
+        for raw in [
+            "plain",
+            "a\tb",
+            "a\nb",
+            r"back\slash",
+            r"\t is no tab",
+            "\\\t\n",
+            "",
+        ] {
+            let escaped = escape(raw);
+            assert!(!escaped.contains(['\t', '\n']));
+            assert_eq!(unescape(&escaped), raw);
+        }
+        assert_eq!(escape("a\tb\\"), r"a\tb\\");
+        // a dangling backslash of a truncated line is dropped
+        assert_eq!(unescape(r"end\"), "end");
+    }
+}
@@ -0,0 +1,120 @@
+use clap::ValueEnum;
+use rand::{SeedableRng, prelude::*, rngs::StdRng};
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[clap(rename_all = "kebab_case")]
+pub enum JitterProfile {
+    /// every draw is independent
+    Uniform,
+    /// draws repeat in bursts before jumping elsewhere
+    Bursty,
+    /// draws drift smoothly along a 1D gradient noise
+    Perlin,
+}
+
+// probability for a bursty draw to stay close to the previous one
+const BURST_STICKINESS: f32 = 0.85;
+// distance travelled on the noise line between two perlin draws
+const PERLIN_STEP: f32 = 0.13;
+
+/// hands out independent and reproducible random streams derived from one seed
+#[derive(Clone)]
+pub struct RngService {
+    seed: u64,
+    profile: JitterProfile,
+}
+
+impl RngService {
+    pub fn new(seed: Option<u64>, profile: JitterProfile) -> RngService {
+        RngService {
+            seed: seed.unwrap_or_else(|| rand::rng().random()),
+            profile,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// the same seed and name always replay the same draws
+    pub fn stream(&self, name: &str) -> Jitter {
+        let seed = self.seed ^ fnv1a(name.as_bytes());
+        Jitter {
+            rng: StdRng::seed_from_u64(seed),
+            profile: self.profile,
+            seed,
+            last: 0.,
+            noise_pos: 0.,
+        }
+    }
+}
+
+/// random stream whose texture depends on the jitter profile
+pub struct Jitter {
+    rng: StdRng,
+    profile: JitterProfile,
+    seed: u64,
+    last: f32,
+    noise_pos: f32,
+}
+
+impl Jitter {
+    /// next draw in [0, 1)
+    pub fn sample(&mut self) -> f32 {
+        let value = match self.profile {
+            JitterProfile::Uniform => self.rng.random::<f32>(),
+            JitterProfile::Bursty => {
+                if self.rng.random::<f32>() < BURST_STICKINESS {
+                    self.last
+                } else {
+                    self.rng.random::<f32>()
+                }
+            }
+            JitterProfile::Perlin => {
+                self.noise_pos += PERLIN_STEP;
+                (self.noise(self.noise_pos) + 0.5).clamp(0., 0.999_999)
+            }
+        };
+        self.last = value;
+        value
+    }
+
+    /// index in [0, len)
+    pub fn index(&mut self, len: usize) -> usize {
+        ((self.sample() * len as f32) as usize).min(len.saturating_sub(1))
+    }
+
+    pub fn chance(&mut self, probability: f32) -> bool {
+        probability > 0. && self.sample() < probability
+    }
+
+    // 1D gradient noise in [-0.5, 0.5]
+    fn noise(&self, x: f32) -> f32 {
+        let cell = x.floor();
+        let t = x - cell;
+        let g0 = self.gradient(cell as i64);
+        let g1 = self.gradient(cell as i64 + 1);
+        let fade = t * t * t * (t * (t * 6. - 15.) + 10.);
+        let n0 = g0 * t;
+        let n1 = g1 * (t - 1.);
+        n0 + (n1 - n0) * fade
+    }
+
+    fn gradient(&self, cell: i64) -> f32 {
+        let hash = splitmix64(self.seed ^ cell as u64);
+        (hash >> 40) as f32 / (1u64 << 24) as f32 * 2. - 1.
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
@@ -0,0 +1,84 @@
+use crate::{
+    sources::{InputLine, Severity},
+    text,
+};
+use clap::ValueEnum;
+use std::collections::HashMap;
+
+// templates remembered by the rarity scorer before starting over
+const MAX_TEMPLATES: usize = 10000;
+
+/// decides which of the lines queued in a saturated column is displayed first, the
+/// highest score wins and the oldest one among equals
+pub trait Scorer {
+    /// called once per line, when it is queued
+    fn score(&mut self, line: &InputLine, frame: u64) -> f64;
+}
+
+/// built-in scorers, or the one of the script
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[clap(rename_all = "kebab_case")]
+pub enum ScorerKind {
+    Level,
+    Recency,
+    Rarity,
+    /// the `on_score` function of the --script
+    #[cfg(feature = "lua")]
+    Script,
+}
+
+impl ScorerKind {
+    /// the scorer of the script is brought by its step of the chain, none for the others
+    pub fn scorer(self) -> Option<Box<dyn Scorer>> {
+        match self {
+            ScorerKind::Level => Some(Box::new(LevelScorer)),
+            ScorerKind::Recency => Some(Box::new(RecencyScorer)),
+            ScorerKind::Rarity => Some(Box::new(RarityScorer::default())),
+            #[cfg(feature = "lua")]
+            ScorerKind::Script => None,
+        }
+    }
+}
+
+/// the most severe lines first, the lines without a severity count as informational
+struct LevelScorer;
+
+impl Scorer for LevelScorer {
+    fn score(&mut self, line: &InputLine, _frame: u64) -> f64 {
+        match line.severity.unwrap_or(Severity::Info) {
+            Severity::Critical => 5.,
+            Severity::Error => 4.,
+            Severity::Warning => 3.,
+            Severity::Notice => 2.,
+            Severity::Info => 1.,
+            Severity::Debug => 0.,
+        }
+    }
+}
+
+/// the newest lines first
+struct RecencyScorer;
+
+impl Scorer for RecencyScorer {
+    fn score(&mut self, _line: &InputLine, frame: u64) -> f64 {
+        frame as f64
+    }
+}
+
+/// the lines unlike the ones seen so far first, lines only differing by their numbers
+/// share the same template
+#[derive(Default)]
+struct RarityScorer {
+    seen: HashMap<String, u64>,
+}
+
+impl Scorer for RarityScorer {
+    fn score(&mut self, line: &InputLine, _frame: u64) -> f64 {
+        if self.seen.len() >= MAX_TEMPLATES {
+            self.seen.clear();
+        }
+        let seen = self.seen.entry(text::template(&line.text)).or_insert(0);
+        *seen += 1;
+        1. / *seen as f64
+    }
+}
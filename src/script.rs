@@ -0,0 +1,155 @@
+use crate::{Color, scoring::Scorer, sources::InputLine, transform::Transform};
+use clap::ValueEnum;
+use mlua::{Function, Lua, Table, Value};
+use std::{fs, io, path::Path};
+
+/// the `on_line` function of a Lua script, called with a table holding the `text`, the
+/// `source` and the `severity` of every line. it returns nil to drop the line, a string to
+/// replace its text, or a table of `text`, `color` and `column_hint`, the number of the
+/// column counted from 1 the line goes to. its `on_score` function, when it has one, is
+/// the scorer of `--scorer script`
+pub struct LuaScript {
+    name: String,
+    lua: Lua,
+    on_line: Function,
+    on_score: Option<Function>,
+}
+
+impl LuaScript {
+    pub fn load(path: &Path) -> io::Result<LuaScript> {
+        let name = path.display().to_string();
+        let code = fs::read_to_string(path)?;
+        let lua = Lua::new();
+        let (on_line, on_score) = lua
+            .load(code)
+            .set_name(format!("@{name}"))
+            .exec()
+            .and_then(|()| {
+                let globals = lua.globals();
+                Ok((globals.get("on_line")?, globals.get("on_score")?))
+            })
+            .map_err(|err| io::Error::other(format!("{name}: {err}")))?;
+        Ok(LuaScript {
+            name,
+            lua,
+            on_line,
+            on_score,
+        })
+    }
+
+    fn call(&self, line: &mut InputLine) -> mlua::Result<bool> {
+        let arg = table(&self.lua, line)?;
+        match self.on_line.call::<Value>(arg)? {
+            Value::Nil | Value::Boolean(false) => return Ok(false),
+            Value::String(text) => line.text = text.to_str()?.to_string(),
+            Value::Table(table) => update(line, &table)?,
+            other => {
+                return Err(mlua::Error::runtime(format!(
+                    "on_line returned a {}, not a string or a table",
+                    other.type_name()
+                )));
+            }
+        }
+        Ok(true)
+    }
+}
+
+// what the functions of the script are called with
+fn table(lua: &Lua, line: &InputLine) -> mlua::Result<Table> {
+    let arg = lua.create_table()?;
+    arg.set("text", line.text.as_str())?;
+    arg.set("source", line.source.as_str())?;
+    if let Some(severity) = line.severity {
+        arg.set("severity", format!("{severity:?}").to_lowercase())?;
+    }
+    Ok(arg)
+}
+
+fn update(line: &mut InputLine, table: &Table) -> mlua::Result<()> {
+    if let Some(text) = table.get::<Option<String>>("text")? {
+        line.text = text;
+    }
+    if let Some(color) = table.get::<Option<String>>("color")? {
+        line.color = Some(Color::from_str(&color, true).map_err(mlua::Error::runtime)?);
+    }
+    if let Some(column) = table.get::<Option<usize>>("column_hint")? {
+        line.column = column.checked_sub(1);
+    }
+    Ok(())
+}
+
+impl Transform for LuaScript {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    // the errors of the script show up in place of the lines
+    fn apply(&mut self, mut line: InputLine) -> Option<InputLine> {
+        match self.call(&mut line) {
+            Ok(keep) => keep.then_some(line),
+            Err(err) => {
+                line.text = format!("{}: {err}", self.name);
+                Some(line)
+            }
+        }
+    }
+
+    fn scorer(&self) -> Option<Box<dyn Scorer>> {
+        let on_score = self.on_score.clone()?;
+        Some(Box::new(LuaScorer {
+            lua: self.lua.clone(),
+            on_score,
+        }))
+    }
+}
+
+/// `on_score` is called with the line as `on_line` got it and the number of the frame, it
+/// returns a number. the lines it fails on score 0
+struct LuaScorer {
+    lua: Lua,
+    on_score: Function,
+}
+
+impl Scorer for LuaScorer {
+    fn score(&mut self, line: &InputLine, frame: u64) -> f64 {
+        table(&self.lua, line)
+            .and_then(|arg| self.on_score.call::<f64>((arg, frame)))
+            .unwrap_or(0.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempPath;
+
+    #[test]
+    fn on_score_shares_the_state_of_on_line() {
+        let path = TempPath::with(
+            "score.lua",
+            "seen = 0
+             function on_line(line) seen = seen + 1 return line.text end
+             function on_score(line, frame)
+                 if line.severity == 'error' then return 100 end
+                 return seen * 10 + frame
+             end",
+        );
+        let mut script = LuaScript::load(&path).unwrap();
+        let mut scorer = script.scorer().unwrap();
+        let line = InputLine::new("stdin", "hello".to_string());
+        assert_eq!(scorer.score(&line, 3), 3.);
+        script.apply(line.clone()).unwrap();
+        script.apply(line.clone()).unwrap();
+        assert_eq!(scorer.score(&line, 3), 23.);
+        let mut error = line;
+        error.severity = Some(crate::sources::Severity::Error);
+        assert_eq!(scorer.score(&error, 3), 100.);
+    }
+
+    #[test]
+    fn on_score_is_optional() {
+        let path = TempPath::with("noscore.lua", "function on_line(line) return line.text end");
+        let script = LuaScript::load(&path).unwrap();
+        assert!(script.scorer().is_none());
+    }
+}
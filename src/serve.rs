@@ -0,0 +1,361 @@
+use crate::{
+    Args, Counters, Matrix, RUNNING, ServeArgs,
+    commands::or_exit,
+    renderer::{Remote, Renderer},
+    sources::{self, Sources},
+};
+use sources::{LineReceiver, LineSender};
+use std::{
+    io::{self, BufRead, BufWriter, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{JoinHandle, sleep, spawn},
+    time::Duration,
+};
+
+/// size of the clients that don't negotiate theirs, `nc` among others
+pub const DEFAULT_SIZE: (u16, u16) = (80, 24);
+pub const MAX_CLIENTS: usize = 64;
+// the largest window a client is drawn at, every cell of its matrix is allocated
+const MAX_SIZE: (u16, u16) = (1000, 500);
+const ACCEPT_PERIOD: Duration = Duration::from_millis(100);
+// a client that doesn't read its frames is dropped rather than stalling its thread
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// telnet commands and options
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const IP: u8 = 244;
+const ECHO: u8 = 1;
+const SGA: u8 = 3;
+const NAWS: u8 = 31;
+
+/// what a client of `serve` tells of itself: its window size and whether it left
+pub struct Viewer {
+    size: Mutex<(u16, u16)>,
+    left: AtomicBool,
+}
+
+impl Viewer {
+    pub fn new() -> Viewer {
+        Viewer {
+            size: Mutex::new(DEFAULT_SIZE),
+            left: AtomicBool::new(false),
+        }
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        *self.size.lock().unwrap()
+    }
+
+    pub fn left(&self) -> bool {
+        self.left.load(Ordering::SeqCst)
+    }
+
+    /// at most `MAX_SIZE`, whatever the client claims
+    pub fn resize(&self, width: u16, height: u16) {
+        // 0 when the client doesn't know
+        if width > 0 && height > 0 {
+            *self.size.lock().unwrap() = (width.min(MAX_SIZE.0), height.min(MAX_SIZE.1));
+        }
+    }
+
+    pub fn leave(&self) {
+        self.left.store(true, Ordering::SeqCst)
+    }
+}
+
+/// the lines of a matrix drawn away from the local terminal
+pub struct Feed {
+    pub lines: LineReceiver,
+    pub new_sources: bool, // the sources name their lines as they arrive
+}
+
+impl Feed {
+    /// the inputs of the options, read once for every matrix drawn from them
+    pub fn spawn(args: &Args, counters: &Arc<Counters>) -> io::Result<Feed> {
+        let sources = Sources::from_args(args)?;
+        let new_sources = sources.colors_new_sources();
+        Ok(Feed {
+            lines: sources.spawn(counters)?,
+            new_sources,
+        })
+    }
+}
+
+/// the senders of the clients, none once the inputs are exhausted
+#[derive(Clone)]
+pub struct Clients {
+    senders: Arc<Mutex<Option<Vec<LineSender>>>>,
+    new_sources: bool,
+}
+
+impl Clients {
+    /// the inputs are read once and every line is sent to all the clients. like the local
+    /// matrix, the show ends with its inputs
+    pub fn spawn(args: &Args) -> Clients {
+        let counters = Arc::new(Counters::default());
+        let feed = or_exit(Feed::spawn(args, &counters), "could not open the inputs");
+        let clients = Clients {
+            senders: Arc::new(Mutex::new(Some(vec![]))),
+            new_sources: feed.new_sources,
+        };
+        let hub = clients.senders.clone();
+        spawn(move || {
+            for line in feed.lines {
+                if let Some(senders) = hub.lock().unwrap().as_mut() {
+                    senders.retain(|tx| tx.send(line.clone()).is_ok());
+                }
+            }
+            *hub.lock().unwrap() = None;
+            RUNNING.store(false, Ordering::SeqCst);
+        });
+        clients
+    }
+
+    /// the lines read from now on
+    pub fn subscribe(&self) -> Feed {
+        let (tx, rx) = sources::unbounded();
+        if let Some(senders) = self.senders.lock().unwrap().as_mut() {
+            senders.push(tx);
+        }
+        Feed {
+            lines: rx,
+            new_sources: self.new_sources,
+        }
+    }
+}
+
+/// every client draws the same rain, without the local terminal and files of the server
+pub fn viewer_args(mut args: Args) -> Args {
+    args.control_socket = None;
+    args.listen = vec![];
+    args.realtime = false;
+    args
+}
+
+/// the matrix of a client, drawn until the client leaves or the inputs end
+pub fn show(args: Args, feed: Feed, out: Box<dyn Renderer>) {
+    match Matrix::remote(args, feed, out) {
+        Ok(mut mat) => mat.main_loop(),
+        Err(err) => eprintln!("could not start a viewer: {err}"),
+    }
+}
+
+/// every client draws its own matrix at the size its telnet client negotiated
+pub fn serve(opts: Box<ServeArgs>) {
+    let ServeArgs { port, bind, args } = *opts;
+    let busy = b"too many viewers, try again later\r\n";
+    accept(&format!("{bind}:{port}"), args, busy, connect);
+}
+
+/// `curl HOST:PORT` shows the animation at the virtual size, or at the size the
+/// `?cols=N&rows=N` of its request asks for, at most 1000x500 like the telnet viewers
+pub fn serve_http(mut args: Args) {
+    let Some(addr) = args.serve_http.take() else {
+        return;
+    };
+    // `:8080` listens on every interface
+    let addr = match addr.starts_with(':') {
+        true => format!("0.0.0.0{addr}"),
+        false => addr,
+    };
+    let busy = b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n";
+    accept(&addr, args, busy, stream_http);
+}
+
+type Connect = fn(TcpStream, &Args, Feed) -> io::Result<JoinHandle<()>>;
+
+// every client gets its own thread until the inputs end or logmatrix is stopped
+fn accept(addr: &str, args: Args, busy: &[u8], connect: Connect) {
+    let listener = or_exit(
+        TcpListener::bind(addr).and_then(|listener| {
+            listener.set_nonblocking(true)?;
+            Ok(listener)
+        }),
+        &format!("could not listen on {addr}"),
+    );
+    let clients = Clients::spawn(&args);
+    ctrlc::set_handler(|| RUNNING.store(false, Ordering::SeqCst))
+        .expect("Error setting Ctrl-C handler");
+    let client_args = viewer_args(args);
+
+    let mut renders: Vec<JoinHandle<()>> = vec![];
+    while RUNNING.load(Ordering::SeqCst) {
+        let mut stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            // nobody is connecting, or too many files are open and the next accept would fail
+            // the same
+            Err(_) => {
+                sleep(ACCEPT_PERIOD);
+                continue;
+            }
+        };
+        renders.retain(|render| !render.is_finished());
+        if renders.len() >= MAX_CLIENTS {
+            let _ = stream.write_all(busy);
+            continue;
+        }
+        if let Ok(render) = connect(stream, &client_args, clients.subscribe()) {
+            renders.push(render);
+        }
+    }
+    for render in renders {
+        let _ = render.join();
+    }
+}
+
+fn connect(mut stream: TcpStream, args: &Args, feed: Feed) -> io::Result<JoinHandle<()>> {
+    stream.set_nonblocking(false)?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    // the client echoes nothing, sends every key at once and tells its window size
+    stream.write_all(&[IAC, WILL, ECHO, IAC, WILL, SGA, IAC, DO, NAWS])?;
+    let reader = stream.try_clone()?;
+    let closer = stream.try_clone()?;
+    let viewer = Arc::new(Viewer::new());
+    let negotiated = viewer.clone();
+    spawn(move || negotiate(reader, &negotiated));
+    let args = args.clone();
+    Ok(spawn(move || {
+        let out = Box::new(Remote::new(BufWriter::new(Crlf(stream)), viewer));
+        show(args, feed, out);
+        // stops the reader
+        let _ = closer.shutdown(Shutdown::Both);
+    }))
+}
+
+// the response streams the frames until the client goes away
+fn stream_http(stream: TcpStream, args: &Args, feed: Feed) -> io::Result<JoinHandle<()>> {
+    stream.set_nonblocking(false)?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let args = args.clone();
+    Ok(spawn(move || {
+        let (Ok(reader), Ok(body)) = (stream.try_clone(), stream.try_clone()) else {
+            return;
+        };
+        let mut stream = stream;
+        let mut lines = io::BufReader::new(reader).lines().map_while(Result::ok);
+        let request = lines.next().unwrap_or_default();
+        // the headers are of no use
+        for header in lines.by_ref() {
+            if header.is_empty() {
+                break;
+            }
+        }
+        let mut parts = request.split_whitespace();
+        let (method, target) = (
+            parts.next().unwrap_or_default(),
+            parts.next().unwrap_or("/"),
+        );
+        if method != "GET" {
+            let refused = "HTTP/1.1 405 Method Not Allowed\r\nAllow: GET\r\n\
+                           Content-Length: 0\r\n\r\n";
+            let _ = stream.write_all(refused.as_bytes());
+            return;
+        }
+        let (mut width, mut height) = args.virtual_size;
+        let query = target.split_once('?').map_or("", |(_, query)| query);
+        for (key, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+            match (key, value.parse()) {
+                ("cols", Ok(cols)) => width = cols,
+                ("rows", Ok(rows)) => height = rows,
+                _ => {}
+            }
+        }
+        // capped like the telnet windows, the query is no more trusted than NAWS
+        let viewer = Arc::new(Viewer::new());
+        viewer.resize(width, height);
+        let headers = "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\n\
+                       Cache-Control: no-cache\r\nTransfer-Encoding: chunked\r\n\r\n";
+        if stream.write_all(headers.as_bytes()).is_err() {
+            return;
+        }
+        let out = Box::new(Remote::new(BufWriter::new(Chunked(body)), viewer));
+        show(args, feed, out);
+        // the last chunk
+        let _ = stream.write_all(b"0\r\n\r\n");
+    }))
+}
+
+// the window sizes and keys sent by the client, until it quits or goes away
+fn negotiate(stream: TcpStream, viewer: &Viewer) {
+    let mut bytes = io::BufReader::new(stream).bytes().map_while(Result::ok);
+    while let Some(byte) = bytes.next() {
+        match byte {
+            IAC => match bytes.next() {
+                Some(SB) => {
+                    let mut option = vec![];
+                    // up to IAC SE, a 255 of the values being doubled
+                    while let Some(byte) = bytes.next() {
+                        if byte != IAC {
+                            option.push(byte);
+                        } else if bytes.next() == Some(IAC) {
+                            option.push(IAC);
+                        } else {
+                            break;
+                        }
+                    }
+                    if let [NAWS, w1, w2, h1, h2] = option[..] {
+                        viewer.resize(u16::from_be_bytes([w1, w2]), u16::from_be_bytes([h1, h2]));
+                    }
+                }
+                Some(DO | DONT | WILL | WONT) => {
+                    bytes.next();
+                }
+                Some(IP) | None => break,
+                Some(_) => {}
+            },
+            // Ctrl-C, Ctrl-D and q
+            3 | 4 | b'q' => break,
+            _ => {}
+        }
+    }
+    viewer.leave();
+}
+
+/// a chunk of the HTTP response per write, the frames go through a BufWriter
+struct Chunked<W>(W);
+
+impl<W: Write> Write for Chunked<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        write!(self.0, "{:x}\r\n", buf.len())?;
+        self.0.write_all(buf)?;
+        self.0.write_all(b"\r\n")?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// telnet and the ssh clients, without a pty on the server, end their lines with CR LF
+pub struct Crlf<W>(pub W);
+
+impl<W: Write> Write for Crlf<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut lines = buf.split(|byte| *byte == b'\n');
+        if let Some(first) = lines.next() {
+            self.0.write_all(first)?;
+        }
+        for line in lines {
+            self.0.write_all(b"\r\n")?;
+            self.0.write_all(line)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
@@ -0,0 +1,372 @@
+use super::{InputLine, LineSender};
+use crate::{Counters, clock};
+use hmac::{Hmac, Mac};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashSet,
+    env,
+    fmt::Write as _,
+    fs, io,
+    sync::{Arc, atomic::Ordering},
+    thread::{sleep, spawn},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use ureq::Agent;
+
+const POLL_PERIOD: Duration = Duration::from_secs(2);
+// the period doubles with every poll which failed, up to this
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// events are ingested late, the ones stamped this long before the last one seen are
+// asked for again
+const LOOKBACK: Duration = Duration::from_secs(30);
+const TARGET: &str = "Logs_20140328.FilterLogEvents";
+
+/// `GROUP[:STREAM]`, the streams of a group can't hold a colon
+#[derive(Clone)]
+pub struct LogGroup {
+    group: String,
+    stream: Option<String>,
+}
+
+pub fn parse_log_group(raw: &str) -> Result<LogGroup, String> {
+    let (group, stream) = match raw.split_once(':') {
+        Some((group, stream)) => (group, Some(stream.to_string())),
+        None => (raw, None),
+    };
+    if group.is_empty() || stream.as_ref().is_some_and(String::is_empty) {
+        return Err(format!("expected `GROUP[:STREAM]`, got `{raw}`"));
+    }
+    Ok(LogGroup {
+        group: group.to_string(),
+        stream,
+    })
+}
+
+/// access keys of the environment, else of the profile in `~/.aws/credentials`
+#[derive(Clone)]
+struct Credentials {
+    access_key: String,
+    secret_key: String,
+    token: Option<String>,
+    region: String,
+}
+
+impl Credentials {
+    fn load(region: Option<&str>) -> io::Result<Credentials> {
+        let profile = env::var("AWS_PROFILE").unwrap_or_else(|_| "default".to_string());
+        let home = env::var("HOME").unwrap_or_default();
+        let credentials = profile_entries(&format!("{home}/.aws/credentials"), &profile);
+        // the sections of the config file are named `profile NAME` but the default one
+        let section = match profile.as_str() {
+            "default" => profile.clone(),
+            _ => format!("profile {profile}"),
+        };
+        let config = profile_entries(&format!("{home}/.aws/config"), &section);
+        let lookup = |var: &str, key: &str, entries: &[(String, String)]| {
+            env::var(var).ok().or_else(|| {
+                entries
+                    .iter()
+                    .find(|(name, _)| name == key)
+                    .map(|(_, value)| value.clone())
+            })
+        };
+        let missing = |what: &str| {
+            let message = format!("cloudwatch: no {what}, set {}", what.to_uppercase());
+            io::Error::new(io::ErrorKind::NotFound, message)
+        };
+        let region = region
+            .map(str::to_string)
+            .or_else(|| lookup("AWS_REGION", "region", &config))
+            .or_else(|| lookup("AWS_DEFAULT_REGION", "region", &config))
+            .ok_or_else(|| missing("aws_region"))?;
+        Ok(Credentials {
+            access_key: lookup("AWS_ACCESS_KEY_ID", "aws_access_key_id", &credentials)
+                .ok_or_else(|| missing("aws_access_key_id"))?,
+            secret_key: lookup(
+                "AWS_SECRET_ACCESS_KEY",
+                "aws_secret_access_key",
+                &credentials,
+            )
+            .ok_or_else(|| missing("aws_secret_access_key"))?,
+            token: lookup("AWS_SESSION_TOKEN", "aws_session_token", &credentials),
+            region,
+        })
+    }
+}
+
+// `key = value` lines of an ini section, none when the file or the section is missing
+fn profile_entries(path: &str, section: &str) -> Vec<(String, String)> {
+    let Ok(ini) = fs::read_to_string(path) else {
+        return vec![];
+    };
+    let mut current = None;
+    let mut entries = vec![];
+    for line in ini.lines().map(str::trim) {
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            current = Some(name.trim().to_string());
+        } else if current.as_deref() == Some(section)
+            && let Some((key, value)) = line.split_once('=')
+        {
+            entries.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    entries
+}
+
+/// poll the new events of the log groups, the lines are named after their stream
+pub fn spawn_cloudwatch(
+    groups: &[LogGroup],
+    region: Option<&str>,
+    tx: LineSender,
+    counters: Arc<Counters>,
+) -> io::Result<()> {
+    let credentials = Credentials::load(region)?;
+    let agent: Agent = Agent::config_builder()
+        .http_status_as_error(false)
+        .build()
+        .into();
+    let start = epoch_millis(SystemTime::now());
+    for group in groups {
+        // fail early on a wrong group or credentials rather than retrying forever
+        filter(&agent, &credentials, group, start, None)?;
+    }
+    for group in groups.iter().cloned() {
+        let (agent, credentials, tx, counters) = (
+            agent.clone(),
+            credentials.clone(),
+            tx.clone(),
+            counters.clone(),
+        );
+        spawn(move || {
+            let mut last = start;
+            // ids of the events since `last - LOOKBACK`, with their timestamp
+            let mut seen: HashSet<(u64, String)> = HashSet::new();
+            let mut period = POLL_PERIOD;
+            loop {
+                let since = last.saturating_sub(LOOKBACK.as_millis() as u64);
+                let mut token = None;
+                // the pages of a single poll. one which failed is counted as dropped, its
+                // events are asked for again after a longer wait
+                loop {
+                    let page = match filter(&agent, &credentials, &group, since, token.as_deref()) {
+                        Ok(page) => page,
+                        Err(_) => {
+                            counters.dropped.fetch_add(1, Ordering::Relaxed);
+                            period = (period * 2).min(MAX_BACKOFF);
+                            break;
+                        }
+                    };
+                    for event in page["events"].as_array().into_iter().flatten() {
+                        let (Some(at), Some(id), Some(message)) = (
+                            event["timestamp"].as_u64(),
+                            event["eventId"].as_str(),
+                            event["message"].as_str(),
+                        ) else {
+                            counters.dropped.fetch_add(1, Ordering::Relaxed);
+                            continue;
+                        };
+                        if !seen.insert((at, id.to_string())) {
+                            continue;
+                        }
+                        last = last.max(at);
+                        let stream = event["logStreamName"].as_str().unwrap_or(&group.group);
+                        for line in message.lines() {
+                            if tx.send(InputLine::new(stream, line.to_string())).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    token = page["nextToken"].as_str().map(str::to_string);
+                    if token.is_none() {
+                        period = POLL_PERIOD;
+                        break;
+                    }
+                }
+                let since = last.saturating_sub(LOOKBACK.as_millis() as u64);
+                seen.retain(|(at, _)| *at >= since);
+                sleep(period);
+            }
+        });
+    }
+    Ok(())
+}
+
+// one page of the events of the group since `start`, in milliseconds
+fn filter(
+    agent: &Agent,
+    credentials: &Credentials,
+    group: &LogGroup,
+    start: u64,
+    token: Option<&str>,
+) -> io::Result<Value> {
+    let mut body = json!({ "logGroupName": group.group, "startTime": start });
+    if let Some(stream) = &group.stream {
+        body["logStreamNames"] = json!([stream]);
+    }
+    if let Some(token) = token {
+        body["nextToken"] = json!(token);
+    }
+    let body = body.to_string();
+    let host = format!("logs.{}.amazonaws.com", credentials.region);
+    let mut request = agent
+        .post(format!("https://{host}/"))
+        .header("Content-Type", "application/x-amz-json-1.1")
+        .header("X-Amz-Target", TARGET);
+    let headers = [
+        ("content-type", "application/x-amz-json-1.1"),
+        ("x-amz-target", TARGET),
+    ];
+    for (name, value) in sign(
+        credentials,
+        "logs",
+        &host,
+        &headers,
+        &body,
+        SystemTime::now(),
+    ) {
+        request = request.header(name, value);
+    }
+    let context = |err: String| io::Error::other(format!("cloudwatch {}: {err}", group.group));
+    let mut response = request
+        .send(&body)
+        .map_err(|err| context(err.to_string()))?;
+    let status = response.status();
+    let reply: Value = serde_json::from_reader(response.body_mut().as_reader())
+        .map_err(|err| context(err.to_string()))?;
+    if !status.is_success() {
+        let message = reply["message"]
+            .as_str()
+            .or(reply["Message"].as_str())
+            .unwrap_or_default();
+        return Err(context(format!("{status} {message}")));
+    }
+    Ok(reply)
+}
+
+// headers of the Signature Version 4 of a POST to `/` of the service, `headers` are signed
+// along with the host and the date
+fn sign(
+    credentials: &Credentials,
+    service: &str,
+    host: &str,
+    headers: &[(&'static str, &str)],
+    body: &str,
+    now: SystemTime,
+) -> Vec<(&'static str, String)> {
+    let seconds = epoch_millis(now) / 1000;
+    let (year, month, day) = clock::civil_date(seconds / 86400);
+    let date = format!("{year:04}{month:02}{day:02}");
+    let time = seconds % 86400;
+    let stamp = format!(
+        "{date}T{:02}{:02}{:02}Z",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    );
+
+    let mut headers: Vec<(&str, String)> = headers
+        .iter()
+        .map(|(name, value)| (*name, value.to_string()))
+        .collect();
+    headers.push(("host", host.to_string()));
+    headers.push(("x-amz-date", stamp.clone()));
+    if let Some(token) = &credentials.token {
+        headers.push(("x-amz-security-token", token.clone()));
+    }
+    // the signed headers are sorted by name
+    headers.sort_unstable();
+    let signed: Vec<&str> = headers.iter().map(|(name, _)| *name).collect();
+    let signed = signed.join(";");
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{name}:{value}\n"))
+        .collect();
+    let canonical = format!(
+        "POST\n/\n\n{canonical_headers}\n{signed}\n{}",
+        hex(&Sha256::digest(body))
+    );
+    let scope = format!("{date}/{}/{service}/aws4_request", credentials.region);
+    let to_sign = format!(
+        "AWS4-HMAC-SHA256\n{stamp}\n{scope}\n{}",
+        hex(&Sha256::digest(canonical))
+    );
+    let key = [credentials.region.as_str(), service, "aws4_request"]
+        .iter()
+        .fold(
+            hmac(format!("AWS4{}", credentials.secret_key).as_bytes(), &date),
+            |key, part| hmac(&key, part),
+        );
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed}, Signature={}",
+        credentials.access_key,
+        hex(&hmac(&key, &to_sign))
+    );
+
+    let mut sent = vec![("X-Amz-Date", stamp), ("Authorization", authorization)];
+    if let Some(token) = &credentials.token {
+        sent.push(("X-Amz-Security-Token", token.clone()));
+    }
+    sent
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+fn epoch_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // post-vanilla of the AWS Signature Version 4 test suite
+    #[test]
+    fn signature_of_the_test_suite() {
+        let credentials = Credentials {
+            access_key: "AKIDEXAMPLE".to_string(),
+            secret_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            token: None,
+            region: "us-east-1".to_string(),
+        };
+        let now = UNIX_EPOCH + Duration::from_secs(1_440_938_160);
+        let headers = sign(
+            &credentials,
+            "service",
+            "example.amazonaws.com",
+            &[],
+            "",
+            now,
+        );
+        assert_eq!(
+            headers,
+            [
+                ("X-Amz-Date", "20150830T123600Z".to_string()),
+                (
+                    "Authorization",
+                    "AWS4-HMAC-SHA256 \
+                     Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+                     SignedHeaders=host;x-amz-date, \
+                     Signature=5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use super::{InputLine, LineSender};
+use crate::Counters;
+use serde_json::Value;
+use std::{
+    env,
+    io::{self, BufRead, BufReader, Read, Write},
+    mem,
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    sync::{Arc, atomic::Ordering},
+    thread::spawn,
+};
+
+const DEFAULT_SOCKET: &str = "/var/run/docker.sock";
+
+struct Container {
+    id: String,
+    name: String,
+    tty: bool, // the logs of a container without tty are multiplexed
+}
+
+/// stream the logs of the containers, or of every running one, from the Docker socket,
+/// the lines are named after their container
+pub fn spawn_docker(
+    containers: &[String],
+    all: bool,
+    tx: LineSender,
+    counters: Arc<Counters>,
+) -> io::Result<()> {
+    let mut ids = containers.to_vec();
+    if all {
+        let running = get_json("/containers/json")?;
+        ids.extend(
+            running
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|container| container.get("Id")?.as_str().map(str::to_string)),
+        );
+    }
+    for id in ids {
+        let container = inspect(&id)?;
+        let tx = tx.clone();
+        let counters = counters.clone();
+        spawn(move || {
+            let _ = stream_logs(&container, &tx, &counters);
+        });
+    }
+    Ok(())
+}
+
+// the unix socket of DOCKER_HOST when it names one
+fn socket_path() -> PathBuf {
+    env::var("DOCKER_HOST")
+        .ok()
+        .and_then(|host| host.strip_prefix("unix://").map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_SOCKET))
+}
+
+// HTTP/1.0 keeps the body out of the chunked encoding, it ends with the connection
+fn request(path: &str) -> io::Result<BufReader<UnixStream>> {
+    let socket = socket_path();
+    let mut stream = UnixStream::connect(&socket).map_err(|err| {
+        io::Error::new(err.kind(), format!("docker: {}: {err}", socket.display()))
+    })?;
+    stream.write_all(format!("GET {path} HTTP/1.0\r\nHost: docker\r\n\r\n").as_bytes())?;
+
+    let mut reader = BufReader::new(stream);
+    let mut status = String::new();
+    reader.read_line(&mut status)?;
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    if status.split_whitespace().nth(1) != Some("200") {
+        let mut body = String::new();
+        let _ = reader.read_to_string(&mut body);
+        let message = serde_json::from_str::<Value>(&body)
+            .ok()
+            .and_then(|body| body.get("message")?.as_str().map(str::to_string))
+            .unwrap_or_else(|| status.trim().to_string());
+        return Err(io::Error::other(format!("docker: {message}")));
+    }
+    Ok(reader)
+}
+
+fn get_json(path: &str) -> io::Result<Value> {
+    let mut body = String::new();
+    request(path)?.read_to_string(&mut body)?;
+    serde_json::from_str(&body).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn inspect(id: &str) -> io::Result<Container> {
+    let details = get_json(&format!("/containers/{id}/json"))?;
+    let name = details.get("Name").and_then(Value::as_str).unwrap_or(id);
+    Ok(Container {
+        id: id.to_string(),
+        name: name.trim_start_matches('/').to_string(),
+        tty: details
+            .pointer("/Config/Tty")
+            .and_then(Value::as_bool)
+            .unwrap_or(false),
+    })
+}
+
+fn stream_logs(container: &Container, tx: &LineSender, counters: &Counters) -> io::Result<()> {
+    let mut logs = request(&format!(
+        "/containers/{}/logs?follow=1&stdout=1&stderr=1&tail=0",
+        container.id
+    ))?;
+    let send = |line: Vec<u8>| match String::from_utf8(line) {
+        Ok(text) => tx
+            .send(InputLine::new(
+                &container.name,
+                text.trim_end_matches('\r').to_string(),
+            ))
+            .map_err(io::Error::other),
+        Err(_) => {
+            counters.dropped.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+    };
+
+    if container.tty {
+        let mut line = Vec::new();
+        while logs.read_until(b'\n', &mut line)? > 0 {
+            if line.pop_if(|last| *last == b'\n').is_some() {
+                send(mem::take(&mut line))?;
+            }
+        }
+        return Ok(());
+    }
+
+    demux(&mut logs, send)
+}
+
+// every frame starts with the stream, 3 zero bytes and the big endian payload size, a line
+// can be split over several frames of its stream. the lines are handed to `send` without
+// their line feed
+fn demux(logs: &mut impl Read, mut send: impl FnMut(Vec<u8>) -> io::Result<()>) -> io::Result<()> {
+    let mut pending = [Vec::new(), Vec::new(), Vec::new()];
+    let mut header = [0u8; 8];
+    while logs.read_exact(&mut header).is_ok() {
+        let size = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let mut payload = vec![0u8; size];
+        logs.read_exact(&mut payload)?;
+        let buffer = &mut pending[(header[0] as usize).min(2)];
+        buffer.extend(payload);
+        while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+            let mut line: Vec<u8> = buffer.drain(..=end).collect();
+            line.pop();
+            send(line)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(stream: u8, payload: &[u8]) -> Vec<u8> {
+        let size = (payload.len() as u32).to_be_bytes();
+        [&[stream, 0, 0, 0][..], &size, payload].concat()
+    }
+
+    // the result of the demux and the lines it sent
+    fn run(logs: &[u8]) -> (io::Result<()>, Vec<String>) {
+        let mut lines = vec![];
+        let demuxed = demux(&mut &logs[..], |line| {
+            lines.push(String::from_utf8(line).unwrap());
+            Ok(())
+        });
+        (demuxed, lines)
+    }
+
+    #[test]
+    fn lines_split_over_frames() {
+        let logs = [
+            frame(1, b"GET / 200\nGET /lo"),
+            frame(2, b"warning: disk "),
+            frame(1, b"gin 302\n"),
+            frame(2, b"almost full\n"),
+            frame(1, b"\n"),
+        ]
+        .concat();
+        let (demuxed, lines) = run(&logs);
+        assert!(demuxed.is_ok());
+        assert_eq!(
+            lines,
+            [
+                "GET / 200",
+                "GET /login 302",
+                "warning: disk almost full",
+                ""
+            ]
+        );
+    }
+
+    #[test]
+    fn unfinished_lines_and_frames() {
+        // the line without its line feed is not sent, nor a header cut short
+        let logs = [frame(1, b"done\nhalf"), vec![1, 0, 0]].concat();
+        let (demuxed, lines) = run(&logs);
+        assert!(demuxed.is_ok());
+        assert_eq!(lines, ["done"]);
+        // a payload cut short is an error
+        let logs = &frame(1, b"cut\n")[..10];
+        let (demuxed, lines) = run(logs);
+        assert!(demuxed.is_err());
+        assert!(lines.is_empty());
+    }
+}
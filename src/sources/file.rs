@@ -0,0 +1,87 @@
+use super::{InputLine, LineSender, source_name};
+use crate::Counters;
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Seek, SeekFrom},
+    mem,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::{Arc, atomic::Ordering},
+    thread::{sleep, spawn},
+    time::Duration,
+};
+
+const FOLLOW_POLL: Duration = Duration::from_millis(250);
+
+/// read the file from the start then follow what is appended, like `tail -F`:
+/// a truncated file is read again from the start and a rotated one is reopened
+pub fn spawn_follower(path: PathBuf, tx: LineSender, counters: Arc<Counters>) -> io::Result<()> {
+    let file = File::open(&path)
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", path.display())))?;
+    let source = source_name(&path);
+    spawn(move || {
+        let mut reader = BufReader::new(file);
+        let mut pending = Vec::new();
+        loop {
+            match reader.read_until(b'\n', &mut pending) {
+                Ok(_) if pending.ends_with(b"\n") => {
+                    pending.pop();
+                    match String::from_utf8(mem::take(&mut pending)) {
+                        Ok(text) => {
+                            if tx.send(InputLine::new(&source, text)).is_err() {
+                                return;
+                            }
+                        }
+                        Err(_) => {
+                            counters.dropped.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+                // end of file or partial line, wait for the writer
+                Ok(_) => {
+                    sleep(FOLLOW_POLL);
+                    match check_rotation(&path, &reader) {
+                        Rotation::None => {}
+                        Rotation::Truncated => {
+                            pending.clear();
+                            if reader.seek(SeekFrom::Start(0)).is_err() {
+                                return;
+                            }
+                        }
+                        // everything written to the old file was read already
+                        Rotation::Replaced(file) => {
+                            pending.clear();
+                            reader = BufReader::new(file);
+                        }
+                    }
+                }
+                Err(_) => return,
+            }
+        }
+    });
+    Ok(())
+}
+
+enum Rotation {
+    None,
+    Truncated,
+    Replaced(File),
+}
+
+fn check_rotation(path: &Path, reader: &BufReader<File>) -> Rotation {
+    let (Ok(current), Ok(on_disk)) = (reader.get_ref().metadata(), fs::metadata(path)) else {
+        // the path is gone until the new file is created
+        return Rotation::None;
+    };
+    if (current.dev(), current.ino()) != (on_disk.dev(), on_disk.ino()) {
+        return match File::open(path) {
+            Ok(file) => Rotation::Replaced(file),
+            Err(_) => Rotation::None,
+        };
+    }
+    let position = reader.get_ref().stream_position().unwrap_or(0);
+    match on_disk.len() < position {
+        true => Rotation::Truncated,
+        false => Rotation::None,
+    }
+}
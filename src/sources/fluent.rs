@@ -0,0 +1,413 @@
+use super::{InputLine, LineSender, Severity, clients};
+use crate::Counters;
+use flate2::read::MultiGzDecoder;
+use serde_json::{Map, Value};
+use std::{
+    io::{self, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, atomic::Ordering},
+    thread::spawn,
+};
+
+// a string or a collection announcing more, or nested deeper, is a broken or hostile peer
+const MAX_LEN: usize = 64 << 20;
+const MAX_DEPTH: usize = 64;
+// keys holding the line in the records of the usual inputs, the whole record otherwise
+const MESSAGE_KEYS: [&str; 3] = ["log", "message", "msg"];
+const LEVEL_KEYS: [&str; 3] = ["level", "severity", "log.level"];
+
+/// receive the events sent with the forward protocol of Fluentd and Fluent Bit, the
+/// lines are named after their tag. the chunks asking for it are acknowledged
+pub fn spawn_listener(addr: &str, tx: LineSender, counters: Arc<Counters>) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)
+        .map_err(|err| io::Error::new(err.kind(), format!("fluent {addr}: {err}")))?;
+    spawn(move || {
+        for stream in clients(listener.incoming()) {
+            let (tx, counters) = (tx.clone(), counters.clone());
+            spawn(move || receive(stream, &tx, &counters));
+        }
+    });
+    Ok(())
+}
+
+// until the connection is closed or the matrix is gone
+fn receive(stream: TcpStream, tx: &LineSender, counters: &Counters) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let mut reader = BufReader::new(stream);
+    while let Ok(message) = Msg::read(&mut reader, 0) {
+        let Some((tag, records, option)) = events(message) else {
+            counters.dropped.fetch_add(1, Ordering::Relaxed);
+            continue;
+        };
+        for record in records {
+            for line in record_lines(&tag, record) {
+                if tx.send(line).is_err() {
+                    return;
+                }
+            }
+        }
+        if let Some(Msg::Str(chunk)) = option.as_ref().and_then(|option| option.get("chunk")) {
+            let mut ack = vec![0x81];
+            write_str(&mut ack, "ack");
+            write_str(&mut ack, chunk);
+            if writer.write_all(&ack).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+// tag, records and options
+type Events = (String, Vec<Msg>, Option<Msg>);
+
+// tag, records and options of the 3 modes of the protocol:
+// [tag, time, record, option?], [tag, [[time, record], ..], option?] and
+// [tag, packed [time, record] entries, option?]
+fn events(message: Msg) -> Option<Events> {
+    let Msg::Array(fields) = message else {
+        return None;
+    };
+    let mut fields = fields.into_iter();
+    let (Some(Msg::Str(tag)), Some(second)) = (fields.next(), fields.next()) else {
+        return None;
+    };
+    let records = match second {
+        Msg::Array(entries) => entries.into_iter().filter_map(Msg::into_record).collect(),
+        Msg::Bin(packed) => return Some(unpack(tag, packed, fields.next())),
+        Msg::Str(packed) => return Some(unpack(tag, packed.into_bytes(), fields.next())),
+        // the time of a single event
+        _ => fields.next().into_iter().collect(),
+    };
+    Some((tag, records, fields.next()))
+}
+
+// the entries packed one after the other, compressed when the option tells it
+fn unpack(tag: String, packed: Vec<u8>, option: Option<Msg>) -> Events {
+    let gzip = matches!(
+        option.as_ref().and_then(|option| option.get("compressed")),
+        Some(Msg::Str(compressed)) if compressed == "gzip"
+    );
+    let mut entries: Box<dyn Read> = match gzip {
+        true => Box::new(MultiGzDecoder::new(packed.as_slice())),
+        false => Box::new(packed.as_slice()),
+    };
+    let mut records = vec![];
+    while let Ok(entry) = Msg::read(&mut entries, 0) {
+        records.extend(entry.into_record());
+    }
+    (tag, records, option)
+}
+
+fn record_lines(tag: &str, record: Msg) -> Vec<InputLine> {
+    let record = record.into_json();
+    let severity = LEVEL_KEYS
+        .iter()
+        .find_map(|key| record[key].as_str())
+        .and_then(Severity::from_name);
+    let text = match MESSAGE_KEYS.iter().find_map(|key| record[key].as_str()) {
+        Some(text) => text.to_string(),
+        None => record.to_string(),
+    };
+    text.lines()
+        .map(|text| {
+            let mut line = InputLine::new(tag, text.to_string());
+            line.severity = severity;
+            line
+        })
+        .collect()
+}
+
+fn write_str(buf: &mut Vec<u8>, text: &str) {
+    match text.len() {
+        len @ 0..32 => buf.push(0xa0 | len as u8),
+        len @ 32..256 => buf.extend([0xd9, len as u8]),
+        len @ 256..65536 => {
+            buf.push(0xda);
+            buf.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            buf.push(0xdb);
+            buf.extend((len as u32).to_be_bytes());
+        }
+    }
+    buf.extend(text.as_bytes());
+}
+
+/// a MessagePack value
+enum Msg {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Uint(u64),
+    Float(f64),
+    Str(String),
+    Bin(Vec<u8>), // the strings that aren't UTF-8 too
+    Array(Vec<Msg>),
+    Map(Vec<(Msg, Msg)>),
+    Ext, // skipped, the EventTime of the timestamps among others
+}
+
+impl Msg {
+    fn read(reader: &mut impl Read, depth: usize) -> io::Result<Msg> {
+        if depth > MAX_DEPTH {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        let marker = read_uint(reader, 1)? as u8;
+        Ok(match marker {
+            0x00..=0x7f => Msg::Uint(marker.into()),
+            0x80..=0x8f => Msg::read_map(reader, (marker & 0x0f).into(), depth)?,
+            0x90..=0x9f => Msg::read_array(reader, (marker & 0x0f).into(), depth)?,
+            0xa0..=0xbf => Msg::read_str(reader, (marker & 0x1f).into())?,
+            0xc0 => Msg::Nil,
+            0xc2 => Msg::Bool(false),
+            0xc3 => Msg::Bool(true),
+            0xc4..=0xc6 => {
+                let len = read_len(reader, 1 << (marker - 0xc4))?;
+                Msg::Bin(read_bytes(reader, len)?)
+            }
+            0xc7..=0xc9 => {
+                let len = read_len(reader, 1 << (marker - 0xc7))?;
+                read_bytes(reader, len + 1)?;
+                Msg::Ext
+            }
+            0xca => Msg::Float(f32::from_bits(read_uint(reader, 4)? as u32).into()),
+            0xcb => Msg::Float(f64::from_bits(read_uint(reader, 8)?)),
+            0xcc..=0xcf => Msg::Uint(read_uint(reader, 1 << (marker - 0xcc))?),
+            0xd0..=0xd3 => {
+                let size = 1 << (marker - 0xd0);
+                let unsigned = read_uint(reader, size)?;
+                // sign extension of the narrower integers
+                let shift = 64 - 8 * size as u32;
+                Msg::Int(((unsigned << shift) as i64) >> shift)
+            }
+            0xd4..=0xd8 => {
+                read_bytes(reader, 1 + (1 << (marker - 0xd4)))?;
+                Msg::Ext
+            }
+            0xd9..=0xdb => {
+                let len = read_len(reader, 1 << (marker - 0xd9))?;
+                Msg::read_str(reader, len)?
+            }
+            0xdc | 0xdd => {
+                let len = read_len(reader, 2 << (marker - 0xdc))?;
+                Msg::read_array(reader, len, depth)?
+            }
+            0xde | 0xdf => {
+                let len = read_len(reader, 2 << (marker - 0xde))?;
+                Msg::read_map(reader, len, depth)?
+            }
+            0xe0..=0xff => Msg::Int((marker as i8).into()),
+            // 0xc1 is never used
+            _ => return Err(io::ErrorKind::InvalidData.into()),
+        })
+    }
+
+    fn read_str(reader: &mut impl Read, len: usize) -> io::Result<Msg> {
+        let bytes = read_bytes(reader, len)?;
+        Ok(match String::from_utf8(bytes) {
+            Ok(text) => Msg::Str(text),
+            Err(err) => Msg::Bin(err.into_bytes()),
+        })
+    }
+
+    // grown as the items arrive, the announced length can't be trusted
+    fn read_array(reader: &mut impl Read, len: usize, depth: usize) -> io::Result<Msg> {
+        let mut items = vec![];
+        for _ in 0..len {
+            items.push(Msg::read(reader, depth + 1)?);
+        }
+        Ok(Msg::Array(items))
+    }
+
+    fn read_map(reader: &mut impl Read, len: usize, depth: usize) -> io::Result<Msg> {
+        let mut pairs = vec![];
+        for _ in 0..len {
+            pairs.push((Msg::read(reader, depth + 1)?, Msg::read(reader, depth + 1)?));
+        }
+        Ok(Msg::Map(pairs))
+    }
+
+    fn get(&self, key: &str) -> Option<&Msg> {
+        let Msg::Map(pairs) = self else {
+            return None;
+        };
+        pairs
+            .iter()
+            .find(|(name, _)| matches!(name, Msg::Str(name) if name == key))
+            .map(|(_, value)| value)
+    }
+
+    // the record of a `[time, record]` entry
+    fn into_record(self) -> Option<Msg> {
+        match self {
+            Msg::Array(entry) => entry.into_iter().nth(1),
+            _ => None,
+        }
+    }
+
+    fn into_json(self) -> Value {
+        match self {
+            Msg::Nil | Msg::Ext => Value::Null,
+            Msg::Bool(flag) => Value::Bool(flag),
+            Msg::Int(int) => int.into(),
+            Msg::Uint(int) => int.into(),
+            Msg::Float(float) => float.into(),
+            Msg::Str(text) => Value::String(text),
+            Msg::Bin(bytes) => Value::String(String::from_utf8_lossy(&bytes).into_owned()),
+            Msg::Array(items) => Value::Array(items.into_iter().map(Msg::into_json).collect()),
+            Msg::Map(pairs) => Value::Object(
+                pairs
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let key = match key.into_json() {
+                            Value::String(key) => key,
+                            key => key.to_string(),
+                        };
+                        (key, value.into_json())
+                    })
+                    .collect::<Map<_, _>>(),
+            ),
+        }
+    }
+}
+
+// big endian
+fn read_uint(reader: &mut impl Read, size: usize) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes[8 - size..])?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+fn read_len(reader: &mut impl Read, size: usize) -> io::Result<usize> {
+    match read_uint(reader, size)? as usize {
+        len if len > MAX_LEN => Err(io::ErrorKind::InvalidData.into()),
+        len => Ok(len),
+    }
+}
+
+fn read_bytes(reader: &mut impl Read, len: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    reader.by_ref().take(len as u64).read_to_end(&mut bytes)?;
+    match bytes.len() == len {
+        true => Ok(bytes),
+        false => Err(io::ErrorKind::UnexpectedEof.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(mut bytes: &[u8]) -> Value {
+        let value = Msg::read(&mut bytes, 0).unwrap().into_json();
+        assert!(bytes.is_empty());
+        value
+    }
+
+    // source and text of the lines of a message
+    fn lines(mut bytes: &[u8]) -> Vec<(String, String, Option<Severity>)> {
+        let (tag, records, _) = events(Msg::read(&mut bytes, 0).unwrap()).unwrap();
+        let lines = records
+            .into_iter()
+            .flat_map(|record| record_lines(&tag, record));
+        lines
+            .map(|line| (line.source, line.text, line.severity))
+            .collect()
+    }
+
+    // {"log": text, "level": level}
+    fn record(text: &str, level: &str) -> Vec<u8> {
+        let mut record = vec![0x82];
+        for field in ["log", text, "level", level] {
+            write_str(&mut record, field);
+        }
+        record
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(json(&[0xc0]), Value::Null);
+        assert_eq!(json(&[0xc3]), Value::Bool(true));
+        assert_eq!(json(&[0x7f]), 127);
+        assert_eq!(json(&[0xe0]), -32);
+        assert_eq!(json(&[0xcd, 0x01, 0x00]), 256);
+        assert_eq!(json(&[0xd0, 0xff]), -1);
+        assert_eq!(json(&[0xd1, 0x80, 0x00]), -32768);
+        assert_eq!(json(&[0xcb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0]), 1.5);
+        assert_eq!(json(&[0xc4, 0x02, b'o', b'k']), "ok");
+        assert_eq!(json(&[0xd6, 0x00, 1, 2, 3, 4]), Value::Null);
+        let mut long = vec![];
+        write_str(&mut long, &"x".repeat(300));
+        assert_eq!(json(&long), "x".repeat(300).as_str());
+        let mut map = vec![0x82, 0x01, 0x92, 0xc2, 0xa1, b'a'];
+        write_str(&mut map, "k");
+        map.push(0xc0);
+        assert_eq!(
+            json(&map),
+            serde_json::json!({"1": [false, "a"], "k": null})
+        );
+    }
+
+    #[test]
+    fn broken_or_hostile_input() {
+        let deep = vec![0x91; MAX_DEPTH + 2];
+        for bytes in [
+            &[0xc1][..],
+            &[0xa5, b'a'],
+            &[0xdd, 0xff, 0xff, 0xff, 0xff],
+            &deep,
+        ] {
+            assert!(Msg::read(&mut &bytes[..], 0).is_err());
+        }
+    }
+
+    #[test]
+    fn the_modes_of_the_forward_protocol() {
+        // [tag, time, record]
+        let mut message = vec![0x93];
+        write_str(&mut message, "app");
+        message.extend([0xce, 0x65, 0, 0, 0]);
+        message.extend(record("one\ntwo", "warn"));
+        let warning = Some(Severity::Warning);
+        assert_eq!(
+            lines(&message),
+            [
+                ("app".into(), "one".into(), warning),
+                ("app".into(), "two".into(), warning)
+            ]
+        );
+
+        // [tag, [[time, record], ..]]
+        let mut message = vec![0x92];
+        write_str(&mut message, "web");
+        message.extend([0x92, 0x92, 0x01]);
+        message.extend(record("first", "error"));
+        message.extend([0x92, 0x02, 0x81]);
+        write_str(&mut message, "code");
+        message.push(0x05);
+        assert_eq!(
+            lines(&message),
+            [
+                ("web".into(), "first".into(), Some(Severity::Error)),
+                ("web".into(), "{\"code\":5}".into(), None)
+            ]
+        );
+
+        // [tag, packed entries, option]
+        let mut packed = vec![0x92, 0x01];
+        packed.extend(record("packed", "info"));
+        let mut message = vec![0x93];
+        write_str(&mut message, "db");
+        message.extend([0xc4, packed.len() as u8]);
+        message.extend(packed);
+        message.push(0x81);
+        write_str(&mut message, "chunk");
+        write_str(&mut message, "abc");
+        assert_eq!(
+            lines(&message),
+            [("db".into(), "packed".into(), Some(Severity::Info))]
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use super::{InputLine, LineSender, Severity};
+use crate::Counters;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+    net::UdpSocket,
+    sync::{Arc, atomic::Ordering},
+    thread::spawn,
+    time::{Duration, Instant},
+};
+
+const MAX_DATAGRAM: usize = 65536;
+const CHUNK_MAGIC: [u8; 2] = [0x1e, 0x0f];
+const CHUNK_HEADER: usize = 12;
+// limits set by the GELF specification
+const MAX_CHUNKS: usize = 128;
+const CHUNK_TIMEOUT: Duration = Duration::from_secs(5);
+// messages being reassembled at once, the oldest is dropped to make room for a new one
+const MAX_PARTIALS: usize = 1024;
+// decompressed, the larger ones are dropped rather than inflated
+const MAX_MESSAGE: usize = 1 << 20;
+
+// parts of a chunked message received so far
+struct Partial {
+    chunks: Vec<Option<Vec<u8>>>,
+    started: Instant,
+}
+
+/// receive GELF messages over UDP, chunked and compressed ones included
+pub fn spawn_listener(addr: &str, tx: LineSender, counters: Arc<Counters>) -> io::Result<()> {
+    let socket = UdpSocket::bind(addr)
+        .map_err(|err| io::Error::new(err.kind(), format!("gelf {addr}: {err}")))?;
+    spawn(move || {
+        let mut datagram = vec![0u8; MAX_DATAGRAM];
+        let mut partials: HashMap<[u8; 8], Partial> = HashMap::new();
+        while let Ok(size) = socket.recv(&mut datagram) {
+            partials.retain(|_, partial| partial.started.elapsed() < CHUNK_TIMEOUT);
+            let payload = match datagram[..size].strip_prefix(&CHUNK_MAGIC) {
+                Some(chunk) => match reassemble(&mut partials, chunk, &counters) {
+                    Some(payload) => payload,
+                    None => continue,
+                },
+                None => datagram[..size].to_vec(),
+            };
+            let line = decompress(&payload).and_then(|message| parse_message(&message));
+            match line {
+                Some(line) => {
+                    if tx.send(line).is_err() {
+                        break;
+                    }
+                }
+                None => {
+                    counters.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+// message id, sequence number and count then the data, the whole message once
+// its last chunk arrived. the chunks disagreeing with the count of the first one are dropped
+fn reassemble(
+    partials: &mut HashMap<[u8; 8], Partial>,
+    chunk: &[u8],
+    counters: &Counters,
+) -> Option<Vec<u8>> {
+    if chunk.len() < CHUNK_HEADER - CHUNK_MAGIC.len() {
+        return None;
+    }
+    let id: [u8; 8] = chunk[..8].try_into().ok()?;
+    let (sequence, count) = (chunk[8] as usize, chunk[9] as usize);
+    if count == 0 || count > MAX_CHUNKS || sequence >= count {
+        return None;
+    }
+    if !partials.contains_key(&id)
+        && partials.len() >= MAX_PARTIALS
+        && let Some(oldest) = partials
+            .iter()
+            .min_by_key(|(_, partial)| partial.started)
+            .map(|(id, _)| *id)
+    {
+        partials.remove(&oldest);
+        counters.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+    let partial = partials.entry(id).or_insert_with(|| Partial {
+        chunks: vec![None; count],
+        started: Instant::now(),
+    });
+    if partial.chunks.len() != count {
+        return None;
+    }
+    partial.chunks[sequence] = Some(chunk[10..].to_vec());
+    if partial.chunks.iter().any(Option::is_none) {
+        return None;
+    }
+    let partial = partials.remove(&id)?;
+    Some(partial.chunks.into_iter().flatten().flatten().collect())
+}
+
+// told apart by their magic bytes, gzip, zlib or plain JSON
+fn decompress(payload: &[u8]) -> Option<String> {
+    let decoder: Box<dyn Read> = match payload {
+        [0x1f, 0x8b, ..] => Box::new(GzDecoder::new(payload)),
+        [0x78, ..] => Box::new(ZlibDecoder::new(payload)),
+        _ => return String::from_utf8(payload.to_vec()).ok(),
+    };
+    let mut message = String::new();
+    decoder
+        .take(MAX_MESSAGE as u64 + 1)
+        .read_to_string(&mut message)
+        .ok()?;
+    (message.len() <= MAX_MESSAGE).then_some(message)
+}
+
+fn parse_message(message: &str) -> Option<InputLine> {
+    let message: Value = serde_json::from_str(message).ok()?;
+    let text = message.get("short_message")?.as_str()?;
+    let host = message
+        .get("host")
+        .and_then(Value::as_str)
+        .unwrap_or("gelf");
+    let mut line = InputLine::new(host, text.to_string());
+    // syslog levels, informational when missing
+    line.severity = Some(Severity::from_priority(
+        message
+            .get("level")
+            .and_then(Value::as_u64)
+            .unwrap_or(6)
+            .min(7) as u8,
+    ));
+    Some(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compression, write::GzEncoder, write::ZlibEncoder};
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(vec![], Compression::best());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn decompresses_gzip_and_zlib() {
+        let message = r#"{"short_message":"hello"}"#;
+        let mut zlib = ZlibEncoder::new(vec![], Compression::default());
+        zlib.write_all(message.as_bytes()).unwrap();
+        let zlib = zlib.finish().unwrap();
+        assert_eq!(
+            decompress(&gzip(message.as_bytes())).as_deref(),
+            Some(message)
+        );
+        assert_eq!(decompress(&zlib).as_deref(), Some(message));
+        assert_eq!(decompress(message.as_bytes()).as_deref(), Some(message));
+    }
+
+    #[test]
+    fn drops_decompression_bombs() {
+        let bomb = gzip(&vec![b' '; 64 << 20]);
+        assert!(bomb.len() < MAX_DATAGRAM);
+        assert_eq!(decompress(&bomb), None);
+        assert!(decompress(&gzip(&vec![b' '; MAX_MESSAGE])).is_some());
+    }
+
+    // a chunk without its magic bytes
+    fn chunk(id: u8, sequence: u8, count: u8, data: &[u8]) -> Vec<u8> {
+        [&[id; 8][..], &[sequence, count], data].concat()
+    }
+
+    #[test]
+    fn reassembles_chunks_in_any_order() {
+        let (mut partials, counters) = (HashMap::new(), Counters::default());
+        assert_eq!(
+            reassemble(&mut partials, &chunk(1, 2, 3, b"llo"), &counters),
+            None
+        );
+        assert_eq!(
+            reassemble(&mut partials, &chunk(2, 0, 2, b"ot"), &counters),
+            None
+        );
+        assert_eq!(
+            reassemble(&mut partials, &chunk(1, 0, 3, b"h"), &counters),
+            None
+        );
+        // a repeated chunk replaces the first one
+        assert_eq!(
+            reassemble(&mut partials, &chunk(1, 0, 3, b"h"), &counters),
+            None
+        );
+        assert_eq!(
+            reassemble(&mut partials, &chunk(1, 1, 3, b"e"), &counters).unwrap(),
+            b"hello"
+        );
+        assert_eq!(
+            reassemble(&mut partials, &chunk(2, 1, 2, b"her"), &counters).unwrap(),
+            b"other"
+        );
+        assert!(partials.is_empty());
+        assert_eq!(
+            reassemble(&mut partials, &chunk(3, 0, 1, b""), &counters).unwrap(),
+            b""
+        );
+    }
+
+    #[test]
+    fn drops_broken_chunks() {
+        let (mut partials, counters) = (HashMap::new(), Counters::default());
+        for broken in [
+            &chunk(1, 0, 0, b"x")[..],
+            &chunk(1, 2, 2, b"x"),
+            &chunk(1, 0, MAX_CHUNKS as u8 + 1, b"x"),
+            &[1; 9],
+        ] {
+            assert_eq!(reassemble(&mut partials, broken, &counters), None);
+        }
+        assert!(partials.is_empty());
+        // a count changing midway can't overflow the first one nor complete it
+        reassemble(&mut partials, &chunk(1, 0, 2, b"a"), &counters);
+        assert_eq!(
+            reassemble(&mut partials, &chunk(1, 5, 6, b"b"), &counters),
+            None
+        );
+        assert_eq!(
+            reassemble(&mut partials, &chunk(1, 1, 3, b"b"), &counters),
+            None
+        );
+        assert_eq!(
+            reassemble(&mut partials, &chunk(1, 1, 2, b"b"), &counters).unwrap(),
+            b"ab"
+        );
+    }
+
+    #[test]
+    fn drops_the_oldest_partial_messages() {
+        let (mut partials, counters) = (HashMap::new(), Counters::default());
+        for id in 0..=MAX_PARTIALS as u64 {
+            let chunk = [&id.to_be_bytes()[..], &[0, 2], b"x"].concat();
+            assert_eq!(reassemble(&mut partials, &chunk, &counters), None);
+        }
+        assert_eq!(partials.len(), MAX_PARTIALS);
+        assert_eq!(counters.dropped.load(Ordering::Relaxed), 1);
+        // the chunks of a message being reassembled make no room
+        let last = MAX_PARTIALS as u64;
+        let chunk = [&last.to_be_bytes()[..], &[1, 2], b"x"].concat();
+        assert_eq!(reassemble(&mut partials, &chunk, &counters).unwrap(), b"xx");
+        assert_eq!(counters.dropped.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn parses_messages() {
+        let line = parse_message(r#"{"host":"db","short_message":"slow query","level":4}"#);
+        let line = line.unwrap();
+        assert_eq!(
+            (line.source.as_str(), line.text.as_str()),
+            ("db", "slow query")
+        );
+        assert_eq!(line.severity, Some(Severity::Warning));
+        let line = parse_message(r#"{"short_message":"up","level":99}"#).unwrap();
+        assert_eq!(
+            (line.source.as_str(), line.severity),
+            ("gelf", Some(Severity::Debug))
+        );
+        let line = parse_message(r#"{"short_message":"up"}"#).unwrap();
+        assert_eq!(line.severity, Some(Severity::Info));
+        for broken in [
+            r#"{"full_message":"x"}"#,
+            r#"{"short_message":1}"#,
+            "[]",
+            "nope",
+        ] {
+            assert!(parse_message(broken).is_none());
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use super::{InputLine, LineSender, Severity};
+use crate::Counters;
+use serde_json::Value;
+use std::{
+    io::{self, BufRead, BufReader},
+    process::{Command, Stdio},
+    sync::{Arc, atomic::Ordering},
+    thread::spawn,
+};
+
+/// follow the journal through `journalctl -f -o json`, of a single unit when given,
+/// the lines are named after their unit or syslog identifier and keep their priority
+pub fn spawn_journal(
+    unit: Option<&str>,
+    tx: LineSender,
+    counters: Arc<Counters>,
+) -> io::Result<()> {
+    let mut journalctl = Command::new("journalctl");
+    journalctl.args(["--follow", "--output", "json"]);
+    if let Some(unit) = unit {
+        journalctl.args(["--unit", unit]);
+    }
+    let mut child = journalctl
+        .stdout(Stdio::piped())
+        .stdin(Stdio::null())
+        .spawn()
+        .map_err(|err| io::Error::new(err.kind(), format!("journalctl: {err}")))?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+
+    spawn(move || {
+        for entry in BufReader::new(stdout).lines() {
+            let Ok(entry) = entry else {
+                counters.dropped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            };
+            let Some(line) = parse_entry(&entry) else {
+                counters.dropped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            };
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+    });
+    Ok(())
+}
+
+fn parse_entry(entry: &str) -> Option<InputLine> {
+    let entry: Value = serde_json::from_str(entry).ok()?;
+    let message = text_of(entry.get("MESSAGE")?)?;
+    let source = ["_SYSTEMD_UNIT", "SYSLOG_IDENTIFIER", "_COMM"]
+        .iter()
+        .find_map(|key| entry.get(key).and_then(Value::as_str))
+        .unwrap_or("journal");
+    let mut line = InputLine::new(source, message);
+    line.severity = entry
+        .get("PRIORITY")
+        .and_then(Value::as_str)
+        .and_then(|priority| priority.parse().ok())
+        .map(Severity::from_priority);
+    Some(line)
+}
+
+// journald exports the fields that are not valid UTF-8 as an array of bytes
+fn text_of(field: &Value) -> Option<String> {
+    match field {
+        Value::String(text) => Some(text.clone()),
+        Value::Array(bytes) => {
+            let bytes: Vec<u8> = bytes
+                .iter()
+                .filter_map(|byte| byte.as_u64().map(|byte| byte as u8))
+                .collect();
+            Some(String::from_utf8_lossy(&bytes).into_owned())
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,66 @@
+use super::{InputLine, LineSender, runtime};
+use crate::Counters;
+use ::kafka::consumer::{Consumer, FetchOffset, GroupOffsetStorage};
+use std::{
+    io,
+    sync::{Arc, atomic::Ordering},
+    thread::sleep,
+    time::Duration,
+};
+
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// consume the topics from the brokers, the offsets are committed for the group so a
+/// restarted instance goes on where it stopped, a new group starts with the new messages.
+/// the consumer blocks, it is polled on the blocking threads of the sources runtime
+pub fn spawn_kafka(
+    brokers: &[String],
+    topics: &[String],
+    group: &str,
+    offsets: bool,
+    tx: LineSender,
+    counters: Arc<Counters>,
+) -> io::Result<()> {
+    let mut builder = Consumer::from_hosts(brokers.to_vec())
+        .with_group(group.to_string())
+        .with_fallback_offset(FetchOffset::Latest)
+        .with_offset_storage(Some(GroupOffsetStorage::Kafka))
+        .with_client_id("logmatrix".to_string());
+    for topic in topics {
+        builder = builder.with_topic(topic.clone());
+    }
+    let mut consumer = builder
+        .create()
+        .map_err(|err| io::Error::other(format!("kafka: {err}")))?;
+    let tx = runtime::forward(tx);
+    runtime::spawn_blocking(move || {
+        loop {
+            let sets = match consumer.poll() {
+                Ok(sets) => sets,
+                Err(_) => {
+                    sleep(RETRY_DELAY);
+                    continue;
+                }
+            };
+            for set in sets.iter() {
+                for message in set.messages() {
+                    let Ok(text) = std::str::from_utf8(message.value) else {
+                        counters.dropped.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    };
+                    for line in text.lines() {
+                        let line = match offsets {
+                            true => format!("{}@{} {line}", set.partition(), message.offset),
+                            false => line.to_string(),
+                        };
+                        if tx.blocking_send(InputLine::new(set.topic(), line)).is_err() {
+                            return;
+                        }
+                    }
+                }
+                let _ = consumer.consume_messageset(set);
+            }
+            let _ = consumer.commit_consumed();
+        }
+    })
+}
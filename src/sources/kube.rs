@@ -0,0 +1,239 @@
+use super::{InputLine, LineSender};
+use crate::Counters;
+use serde_json::Value;
+use std::{
+    collections::HashSet,
+    env, fs,
+    io::{self, BufRead, BufReader},
+    sync::{Arc, atomic::Ordering},
+    thread::{sleep, spawn},
+    time::{Duration, Instant},
+};
+use ureq::{
+    Agent,
+    tls::{Certificate, RootCerts, TlsConfig},
+};
+
+const SERVICE_ACCOUNT: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+// where `kubectl proxy` listens by default
+const PROXY_API: &str = "http://127.0.0.1:8001";
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+const SELECTOR_RESCAN: Duration = Duration::from_secs(5);
+
+/// pods to stream the logs of, `namespace/pod[:container]` or `namespace/label=value,..`
+#[derive(Clone)]
+pub struct KubeTarget {
+    namespace: String,
+    pods: PodSelector,
+    container: Option<String>,
+}
+
+#[derive(Clone)]
+enum PodSelector {
+    Name(String),
+    Labels(String),
+}
+
+pub fn parse_kube_target(raw: &str) -> Result<KubeTarget, String> {
+    let (namespace, pods) = raw
+        .split_once('/')
+        .ok_or(format!("missing `NAMESPACE/` in `{raw}`"))?;
+    // a pod name never holds `=`, a label selector always does
+    let (pods, container) = match pods.contains('=') {
+        true => (PodSelector::Labels(pods.to_string()), None),
+        false => match pods.split_once(':') {
+            Some((pod, container)) => (
+                PodSelector::Name(pod.to_string()),
+                Some(container.to_string()),
+            ),
+            None => (PodSelector::Name(pods.to_string()), None),
+        },
+    };
+    if namespace.is_empty() || matches!(&pods, PodSelector::Name(pod) if pod.is_empty()) {
+        return Err(format!(
+            "expected `NAMESPACE/POD[:CONTAINER]` or `NAMESPACE/LABELS`, got `{raw}`"
+        ));
+    }
+    Ok(KubeTarget {
+        namespace: namespace.to_string(),
+        pods,
+        container,
+    })
+}
+
+/// the API server seen from inside the cluster with the service account of the pod,
+/// else the one of `kubectl proxy` unless an other one is given
+#[derive(Clone)]
+struct Api {
+    agent: Agent,
+    base: String,
+    in_cluster: bool,
+}
+
+impl Api {
+    fn new(base: Option<&str>) -> io::Result<Api> {
+        let cluster = env::var("KUBERNETES_SERVICE_HOST")
+            .ok()
+            .zip(env::var("KUBERNETES_SERVICE_PORT").ok());
+        let (base, in_cluster) = match (base, cluster) {
+            (Some(base), _) => (base.trim_end_matches('/').to_string(), false),
+            (None, Some((host, port))) => (format!("https://{host}:{port}"), true),
+            (None, None) => (PROXY_API.to_string(), false),
+        };
+        let mut config = Agent::config_builder();
+        if in_cluster {
+            let ca = fs::read(format!("{SERVICE_ACCOUNT}/ca.crt"))?;
+            let ca = Certificate::from_pem(&ca).map_err(io::Error::other)?;
+            config = config.tls_config(
+                TlsConfig::builder()
+                    .root_certs(RootCerts::Specific(Arc::new(vec![ca])))
+                    .build(),
+            );
+        }
+        Ok(Api {
+            agent: config.build().into(),
+            base,
+            in_cluster,
+        })
+    }
+
+    fn get(&self, path: &str) -> io::Result<ureq::Body> {
+        let mut request = self.agent.get(format!("{}{path}", self.base));
+        // the projected token is rotated, read it again for every request
+        if self.in_cluster {
+            let token = fs::read_to_string(format!("{SERVICE_ACCOUNT}/token"))?;
+            request = request.header("Authorization", format!("Bearer {}", token.trim()));
+        }
+        match request.call() {
+            Ok(response) => Ok(response.into_body()),
+            Err(ureq::Error::StatusCode(404)) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("kube: {path} not found"),
+            )),
+            Err(err) => Err(io::Error::other(format!("kube: {err}"))),
+        }
+    }
+
+    fn get_json(&self, path: &str) -> io::Result<Value> {
+        serde_json::from_reader(self.get(path)?.into_reader())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("kube: {err}")))
+    }
+
+    fn running_pods(&self, namespace: &str, labels: &str) -> io::Result<Vec<String>> {
+        let pods = self.get_json(&format!(
+            "/api/v1/namespaces/{namespace}/pods?labelSelector={}",
+            encode(labels)
+        ))?;
+        Ok(pods
+            .get("items")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter(|pod| pod.pointer("/status/phase").and_then(Value::as_str) == Some("Running"))
+            .filter_map(|pod| pod.pointer("/metadata/name")?.as_str().map(str::to_string))
+            .collect())
+    }
+}
+
+/// stream the logs of the pods, reconnecting when they restart, the pods matching a
+/// label selector are looked for again every few seconds
+pub fn spawn_kube(
+    targets: &[KubeTarget],
+    api: Option<&str>,
+    tx: LineSender,
+    counters: Arc<Counters>,
+) -> io::Result<()> {
+    let api = Api::new(api)?;
+    for target in targets {
+        let namespace = &target.namespace;
+        match &target.pods {
+            PodSelector::Name(pod) => {
+                // fail early on a typo rather than retrying forever
+                api.get_json(&format!("/api/v1/namespaces/{namespace}/pods/{pod}"))?;
+                let (api, target, pod) = (api.clone(), target.clone(), pod.clone());
+                let (tx, counters) = (tx.clone(), counters.clone());
+                spawn(move || follow_pod(&api, &target, &pod, &tx, &counters));
+            }
+            PodSelector::Labels(labels) => {
+                let pods = api.running_pods(namespace, labels)?;
+                let (api, target, labels) = (api.clone(), target.clone(), labels.clone());
+                let (tx, counters) = (tx.clone(), counters.clone());
+                spawn(move || {
+                    let mut followed = HashSet::new();
+                    let mut pods = pods;
+                    loop {
+                        for pod in pods.into_iter().filter(|pod| followed.insert(pod.clone())) {
+                            let (api, target) = (api.clone(), target.clone());
+                            let (tx, counters) = (tx.clone(), counters.clone());
+                            spawn(move || follow_pod(&api, &target, &pod, &tx, &counters));
+                        }
+                        sleep(SELECTOR_RESCAN);
+                        pods = api
+                            .running_pods(&target.namespace, &labels)
+                            .unwrap_or_default();
+                    }
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+// the logs of a pod until it is deleted, lines written while reconnecting are asked again
+fn follow_pod(api: &Api, target: &KubeTarget, pod: &str, tx: &LineSender, counters: &Counters) {
+    let source = match &target.container {
+        Some(container) => format!("{pod}/{container}"),
+        None => pod.to_string(),
+    };
+    let mut path = format!(
+        "/api/v1/namespaces/{}/pods/{pod}/log?follow=true",
+        target.namespace
+    );
+    if let Some(container) = &target.container {
+        path += &format!("&container={}", encode(container));
+    }
+
+    let mut disconnected: Option<Instant> = None;
+    loop {
+        let since = match disconnected {
+            Some(at) => format!("&sinceSeconds={}", at.elapsed().as_secs() + 1),
+            None => "&tailLines=0".to_string(),
+        };
+        match api.get(&format!("{path}{since}")) {
+            Ok(body) => {
+                for line in BufReader::new(body.into_reader()).split(b'\n') {
+                    let Ok(line) = line else { break };
+                    match String::from_utf8(line) {
+                        Ok(text) => {
+                            if tx.send(InputLine::new(&source, text)).is_err() {
+                                return;
+                            }
+                        }
+                        Err(_) => {
+                            counters.dropped.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+                disconnected = Some(Instant::now());
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return,
+            // still down since the first failed attempt
+            Err(_) => {
+                disconnected.get_or_insert_with(Instant::now);
+            }
+        }
+        sleep(RECONNECT_DELAY);
+    }
+}
+
+// percent encoding of a query value
+fn encode(raw: &str) -> String {
+    raw.bytes()
+        .map(|byte| match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
@@ -0,0 +1,222 @@
+use super::{ACCEPT_BACKOFF, InputLine, LineSender, runtime, source_name};
+use crate::Counters;
+use std::{
+    fs, io,
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    sync::{Arc, atomic::Ordering},
+};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader},
+    sync::mpsc,
+};
+
+// bytes of a line, the longer ones are dropped rather than buffered whole
+const MAX_LINE: usize = 1 << 20;
+
+/// address to receive lines of text on, `tcp://HOST:PORT` or `unix:///PATH`
+#[derive(Clone)]
+pub enum Listen {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+pub fn parse_listen(raw: &str) -> Result<Listen, String> {
+    match raw.split_once("://") {
+        Some(("tcp", addr)) if !addr.is_empty() => Ok(Listen::Tcp(addr.to_string())),
+        Some(("unix", path)) if !path.is_empty() => Ok(Listen::Unix(PathBuf::from(path))),
+        _ => Err(format!(
+            "expected `tcp://HOST:PORT` or `unix:///PATH`, got `{raw}`"
+        )),
+    }
+}
+
+fn bind_unix(path: &PathBuf) -> io::Result<UnixListener> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                "another process is listening on this socket",
+            ));
+        }
+        // stale socket left by a killed instance
+        fs::remove_file(path)?;
+    }
+    UnixListener::bind(path)
+}
+
+fn trim_end(mut line: Vec<u8>) -> Vec<u8> {
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    line
+}
+
+/// bind the socket right away and read the lines of every client in a task of the sources
+/// runtime
+pub fn spawn_listener(listen: &Listen, tx: LineSender, counters: Arc<Counters>) -> io::Result<()> {
+    let runtime = runtime::runtime()?;
+    let tx = runtime::forward(tx);
+    match listen {
+        Listen::Tcp(addr) => {
+            let context =
+                |err: io::Error| io::Error::new(err.kind(), format!("listen {addr}: {err}"));
+            // bound before the task is spawned, for the error to reach the caller
+            let listener = std::net::TcpListener::bind(addr).map_err(context)?;
+            listener.set_nonblocking(true).map_err(context)?;
+            let listener = {
+                let _runtime = runtime.enter();
+                tokio::net::TcpListener::from_std(listener).map_err(context)?
+            };
+            runtime::spawn_task(async move {
+                loop {
+                    // every client is a source of its own, named after its address
+                    let Some((stream, peer)) = accepted(listener.accept().await).await else {
+                        continue;
+                    };
+                    let (tx, counters) = (tx.clone(), counters.clone());
+                    tokio::spawn(receive_lines(stream, peer.to_string(), tx, counters));
+                }
+            })
+        }
+        Listen::Unix(path) => {
+            let context = |err: io::Error| {
+                io::Error::new(err.kind(), format!("listen {}: {err}", path.display()))
+            };
+            let listener = bind_unix(path).map_err(context)?;
+            listener.set_nonblocking(true).map_err(context)?;
+            let listener = {
+                let _runtime = runtime.enter();
+                tokio::net::UnixListener::from_std(listener).map_err(context)?
+            };
+            let source = source_name(path);
+            runtime::spawn_task(async move {
+                loop {
+                    let Some((stream, _)) = accepted(listener.accept().await).await else {
+                        continue;
+                    };
+                    let (source, tx, counters) = (source.clone(), tx.clone(), counters.clone());
+                    tokio::spawn(receive_lines(stream, source, tx, counters));
+                }
+            })
+        }
+    }
+}
+
+/// the client of an accept, none after a pause when it failed, e.g. with too many files open,
+/// so the next one is not tried in a busy loop
+async fn accepted<T>(accept: io::Result<T>) -> Option<T> {
+    if accept.is_err() {
+        tokio::time::sleep(ACCEPT_BACKOFF).await;
+    }
+    accept.ok()
+}
+
+async fn receive_lines(
+    stream: impl AsyncRead + Unpin,
+    source: String,
+    tx: mpsc::Sender<InputLine>,
+    counters: Arc<Counters>,
+) {
+    let mut reader = BufReader::new(stream);
+    while let Ok(Some(line)) = next_line(&mut reader).await {
+        match line.map(String::from_utf8) {
+            Some(Ok(text)) => {
+                if tx.send(InputLine::new(&source, text)).await.is_err() {
+                    return;
+                }
+            }
+            _ => {
+                counters.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+// the next line without its end, none at the end of the stream. the lines over MAX_LINE
+// are skipped up to their end and come out as none
+async fn next_line(
+    reader: &mut (impl AsyncBufRead + Unpin),
+) -> io::Result<Option<Option<Vec<u8>>>> {
+    let mut line = vec![];
+    let limit = MAX_LINE as u64 + 1;
+    if (&mut *reader)
+        .take(limit)
+        .read_until(b'\n', &mut line)
+        .await?
+        == 0
+    {
+        return Ok(None);
+    }
+    if line.last() != Some(&b'\n') && line.len() > MAX_LINE {
+        loop {
+            let buffer = reader.fill_buf().await?;
+            let (skipped, end) = match buffer.iter().position(|byte| *byte == b'\n') {
+                Some(end) => (end + 1, true),
+                None => (buffer.len(), buffer.is_empty()),
+            };
+            reader.consume(skipped);
+            if end {
+                return Ok(Some(None));
+            }
+        }
+    }
+    Ok(Some(Some(trim_end(line))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sources::unbounded, testing::TempPath};
+    use std::{io::Write, time::Instant};
+
+    // a short line, one over the limit and a last one without its end
+    fn stream() -> Vec<u8> {
+        [&b"a\r\n"[..], &vec![b'x'; MAX_LINE + 5], b"\nb"].concat()
+    }
+
+    #[test]
+    fn overlong_lines_are_skipped() {
+        let stream = stream();
+        let mut reader = &stream[..];
+        let mut lines = vec![];
+        runtime::runtime().unwrap().block_on(async {
+            while let Some(line) = next_line(&mut reader).await.unwrap() {
+                lines.push(line);
+            }
+        });
+        assert_eq!(lines, [Some(b"a".to_vec()), None, Some(b"b".to_vec())]);
+    }
+
+    #[test]
+    fn failed_accepts_pause_on_the_runtime() {
+        let runtime = runtime::runtime().unwrap();
+        let started = Instant::now();
+        let failed = io::Error::from_raw_os_error(libc::EMFILE);
+        assert!(runtime.block_on(accepted::<()>(Err(failed))).is_none());
+        assert!(started.elapsed() >= ACCEPT_BACKOFF);
+        assert_eq!(runtime.block_on(accepted(Ok(3))), Some(3));
+    }
+
+    #[test]
+    fn listener_starts_from_a_runtime() {
+        let path = TempPath::new("listen.sock");
+        let listen = Listen::Unix(path.to_path_buf());
+        let (tx, rx) = unbounded();
+        let counters = Arc::new(Counters::default());
+        let started = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async { spawn_listener(&listen, tx, counters) });
+        started.unwrap();
+        UnixStream::connect(&path)
+            .unwrap()
+            .write_all(b"hello\n")
+            .unwrap();
+        let line = rx.into_iter().next().unwrap();
+        assert_eq!(line.text, "hello");
+    }
+}
@@ -0,0 +1,156 @@
+use super::{InputLine, LineSender, Severity, runtime};
+use crate::Counters;
+use futures_util::StreamExt;
+use serde_json::Value;
+use std::{
+    fmt::Write as _,
+    io,
+    sync::{Arc, atomic::Ordering},
+    time::Duration,
+};
+use tokio::{net::TcpStream, sync::mpsc, time::sleep};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungstenite::Message};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// tail the entries matching a LogQL query. the lines are named after the value of the
+/// label when given, after their whole label set otherwise, and colored after their
+/// `level` label. the tail resumes after the last entry received when the connection drops
+pub fn spawn_loki(
+    url: &str,
+    query: &str,
+    label: Option<&str>,
+    tx: LineSender,
+    counters: Arc<Counters>,
+) -> io::Result<()> {
+    let tail = tail_url(url, query);
+    // fail early on a wrong url or query rather than retrying forever
+    let first = tail.clone();
+    let socket = runtime::wait(async move { open(&first, None).await })??;
+    let label = label.map(str::to_string);
+    let tx = runtime::forward(tx);
+    runtime::spawn_task(async move {
+        let mut socket = Some(socket);
+        // nanoseconds timestamp of the last entry received
+        let mut last = None;
+        loop {
+            let connected = match socket.take() {
+                Some(socket) => Ok(socket),
+                None => open(&tail, last).await,
+            };
+            if let Ok(mut socket) = connected
+                && !receive(&mut socket, label.as_deref(), &mut last, &tx, &counters).await
+            {
+                return;
+            }
+            sleep(RECONNECT_DELAY).await;
+        }
+    })
+}
+
+// `http://host:3100` becomes `ws://host:3100/loki/api/v1/tail?query=..`
+fn tail_url(url: &str, query: &str) -> String {
+    let url = url.trim_end_matches('/');
+    let url = match url.split_once("://") {
+        Some(("https", rest)) => format!("wss://{rest}"),
+        Some(("http", rest)) => format!("ws://{rest}"),
+        Some(_) => url.to_string(),
+        None => format!("ws://{url}"),
+    };
+    format!("{url}/loki/api/v1/tail?query={}", encode(query))
+}
+
+async fn open(tail: &str, after: Option<u128>) -> io::Result<Socket> {
+    let url = match after {
+        Some(last) => format!("{tail}&start={}", last + 1),
+        None => tail.to_string(),
+    };
+    connect_async(url)
+        .await
+        .map(|(socket, _response)| socket)
+        .map_err(|err| io::Error::other(format!("loki {tail}: {err}")))
+}
+
+// false once the matrix is gone
+async fn receive(
+    socket: &mut Socket,
+    label: Option<&str>,
+    last: &mut Option<u128>,
+    tx: &mpsc::Sender<InputLine>,
+    counters: &Counters,
+) -> bool {
+    while let Some(message) = socket.next().await {
+        let frame = match message {
+            Ok(Message::Text(frame)) => frame,
+            Ok(_) => continue,
+            Err(_) => return true,
+        };
+        // {"streams": [{"stream": {LABELS}, "values": [["NANOSECONDS", "LINE"], ..]}, ..],
+        //  "dropped_entries": [..]}
+        let Ok(tail) = serde_json::from_str::<Value>(frame.as_str()) else {
+            counters.dropped.fetch_add(1, Ordering::Relaxed);
+            continue;
+        };
+        if let Some(dropped) = tail["dropped_entries"].as_array() {
+            counters
+                .dropped
+                .fetch_add(dropped.len() as u64, Ordering::Relaxed);
+        }
+        for stream in tail["streams"].as_array().into_iter().flatten() {
+            let labels = &stream["stream"];
+            let source = source(labels, label);
+            let severity = labels["level"].as_str().and_then(Severity::from_name);
+            for entry in stream["values"].as_array().into_iter().flatten() {
+                if let Some(at) = entry[0].as_str().and_then(|at| at.parse().ok()) {
+                    *last = (*last).max(Some(at));
+                }
+                let Some(text) = entry[1].as_str() else {
+                    counters.dropped.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                };
+                for line in text.lines() {
+                    let mut line = InputLine::new(&source, line.to_string());
+                    line.severity = severity;
+                    if tx.send(line).await.is_err() {
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+    true
+}
+
+// value of the label, or `{app="api",pod="api-1"}` when it is missing or not asked for
+fn source(labels: &Value, label: Option<&str>) -> String {
+    if let Some(value) = label.and_then(|label| labels[label].as_str()) {
+        return value.to_string();
+    }
+    let Some(labels) = labels.as_object() else {
+        return "loki".to_string();
+    };
+    // the JSON strings come quoted
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+// percent-encoding of everything but the unreserved characters
+fn encode(query: &str) -> String {
+    let mut encoded = String::new();
+    for byte in query.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+        }
+    }
+    encoded
+}
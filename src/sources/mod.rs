@@ -0,0 +1,470 @@
+#[cfg(feature = "cloudwatch")]
+mod cloudwatch;
+mod docker;
+mod file;
+mod fluent;
+mod gelf;
+mod journal;
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "kube")]
+mod kube;
+mod listen;
+#[cfg(feature = "loki")]
+mod loki;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(feature = "nats")]
+mod nats;
+mod otlp;
+mod pipe;
+mod queue;
+mod runtime;
+#[cfg(feature = "sse")]
+mod sse;
+mod syslog;
+mod timed;
+#[cfg(feature = "ws")]
+mod ws;
+
+use crate::{Args, Color, Counters, workers::Prepared};
+#[cfg(feature = "cloudwatch")]
+pub use cloudwatch::{LogGroup, parse_log_group};
+use glob::Pattern;
+#[cfg(feature = "kube")]
+pub use kube::{KubeTarget, parse_kube_target};
+pub use listen::{Listen, parse_listen};
+pub use queue::{LineReceiver, LineSender, QueuePolicy, channel, unbounded};
+use std::{
+    collections::HashSet,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, atomic::Ordering},
+    thread::{sleep, spawn},
+    time::{Duration, Instant},
+};
+pub use syslog::{SyslogListen, parse_syslog_listen};
+pub use timed::TimedReplay;
+
+/// line read by one of the sources
+#[derive(Clone)]
+pub struct InputLine {
+    pub source: String,
+    pub text: String,
+    pub severity: Option<Severity>, // for the sources that tell it
+    pub color: Option<Color>,       // for the sources that pick it
+    pub column: Option<usize>,      // for the scripts that pick it
+    pub(crate) prepared: Option<Box<Prepared>>, // by the --workers threads
+    pub received: Instant,
+}
+
+impl InputLine {
+    pub fn new(source: &str, text: String) -> InputLine {
+        InputLine {
+            source: source.to_string(),
+            text,
+            severity: None,
+            color: None,
+            column: None,
+            prepared: None,
+            received: Instant::now(),
+        }
+    }
+}
+
+/// syslog priorities, the most urgent ones are merged
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Severity {
+    Critical,
+    Error,
+    Warning,
+    Notice,
+    Info,
+    Debug,
+}
+
+impl Severity {
+    pub fn from_priority(priority: u8) -> Severity {
+        match priority {
+            0..=2 => Severity::Critical,
+            3 => Severity::Error,
+            4 => Severity::Warning,
+            5 => Severity::Notice,
+            6 => Severity::Info,
+            _ => Severity::Debug,
+        }
+    }
+
+    /// the usual names of the levels, in any case
+    pub fn from_name(level: &str) -> Option<Severity> {
+        match level.to_ascii_lowercase().as_str() {
+            "critical" | "crit" | "fatal" | "emerg" | "alert" | "panic" => Some(Severity::Critical),
+            "error" | "err" => Some(Severity::Error),
+            "warning" | "warn" => Some(Severity::Warning),
+            "notice" => Some(Severity::Notice),
+            "info" | "information" => Some(Severity::Info),
+            "debug" | "trace" => Some(Severity::Debug),
+            _ => None,
+        }
+    }
+
+    /// color standing out from the usual lines, none for the ordinary severities
+    pub fn color(self) -> Option<Color> {
+        match self {
+            Severity::Critical => Some(Color::Magenta),
+            Severity::Error => Some(Color::Red),
+            Severity::Warning => Some(Color::Yellow),
+            Severity::Notice | Severity::Info => None,
+            Severity::Debug => Some(Color::Blue),
+        }
+    }
+}
+
+/// an input of the matrix, read from a thread of its own
+pub trait Source: Send {
+    /// start reading, the lines are sent to `tx` until it disconnects
+    fn spawn(self: Box<Self>, tx: LineSender, counters: Arc<Counters>) -> io::Result<()>;
+}
+
+// the built-in inputs are started by a plain function
+impl<F> Source for F
+where
+    F: FnOnce(LineSender, Arc<Counters>) -> io::Result<()> + Send,
+{
+    fn spawn(self: Box<Self>, tx: LineSender, counters: Arc<Counters>) -> io::Result<()> {
+        (*self)(tx, counters)
+    }
+}
+
+/// the inputs of a matrix, they all feed the same channel which disconnects once every
+/// one of them is done
+#[derive(Default)]
+pub struct Sources {
+    sources: Vec<Box<dyn Source>>,
+    stdin: bool,       // added by the options
+    new_sources: bool, // some name their lines as they arrive
+    filter_cmd: Option<String>,
+    queue_size: Option<usize>,
+    queue_policy: QueuePolicy,
+}
+
+impl Sources {
+    /// stdin is read when no other input is given or for `-`. the files matching the globs
+    /// are followed too, the ones created later on are picked up every `--rescan` period
+    pub fn from_args(opt: &Args) -> io::Result<Sources> {
+        let mut sources = Sources {
+            filter_cmd: opt.filter_cmd.clone(),
+            queue_size: opt.queue_size,
+            queue_policy: opt.queue_policy,
+            ..Sources::default()
+        };
+        let (files, globs, rescan) = (&opt.files, &opt.file_globs, opt.rescan);
+        if let Some(unit) = opt.journal.clone() {
+            sources.add(move |tx, counters| journal::spawn_journal(unit.as_deref(), tx, counters));
+        }
+        if !opt.docker.is_empty() || opt.docker_all {
+            let (containers, all) = (opt.docker.clone(), opt.docker_all);
+            sources.add(move |tx, counters| docker::spawn_docker(&containers, all, tx, counters));
+        }
+        for listen in opt.listen_syslog.clone() {
+            sources.add(move |tx, counters| syslog::spawn_listener(&listen, tx, counters));
+        }
+        for listen in opt.listen.clone() {
+            sources.add(listen);
+        }
+        for addr in opt.listen_gelf.clone() {
+            sources.add(move |tx, counters| gelf::spawn_listener(&addr, tx, counters));
+        }
+        for addr in opt.listen_otlp.clone() {
+            sources.add_named(move |tx, counters| otlp::spawn_listener(&addr, tx, counters));
+        }
+        for addr in opt.listen_fluent.clone() {
+            sources.add_named(move |tx, counters| fluent::spawn_listener(&addr, tx, counters));
+        }
+        if let Some(replay) = opt.timed_replay.clone() {
+            sources.add(move |tx, counters| timed::spawn_timed(&replay, tx, counters));
+        }
+        #[cfg(feature = "kube")]
+        if !opt.kube.is_empty() {
+            let (targets, api) = (opt.kube.clone(), opt.kube_api.clone());
+            sources
+                .add(move |tx, counters| kube::spawn_kube(&targets, api.as_deref(), tx, counters));
+        }
+        #[cfg(feature = "cloudwatch")]
+        if !opt.cloudwatch.is_empty() {
+            let (groups, region) = (opt.cloudwatch.clone(), opt.aws_region.clone());
+            sources.add_named(move |tx, counters| {
+                cloudwatch::spawn_cloudwatch(&groups, region.as_deref(), tx, counters)
+            });
+        }
+        #[cfg(feature = "kafka")]
+        if !opt.kafka.is_empty() {
+            let (brokers, topics) = (opt.kafka.clone(), opt.topic.clone());
+            let (group, offsets) = (opt.kafka_group.clone(), opt.kafka_offsets);
+            sources.add(move |tx, counters| {
+                kafka::spawn_kafka(&brokers, &topics, &group, offsets, tx, counters)
+            });
+        }
+        #[cfg(feature = "loki")]
+        if let (Some(url), Some(query)) = (opt.loki.clone(), opt.query.clone()) {
+            let label = opt.loki_label.clone();
+            sources.add_named(move |tx, counters| {
+                loki::spawn_loki(&url, &query, label.as_deref(), tx, counters)
+            });
+        }
+        #[cfg(feature = "mqtt")]
+        if let Some(url) = opt.mqtt.clone() {
+            let (topics, prefix) = (opt.topic.clone(), opt.topic_prefix);
+            sources.add_named(move |tx, counters| {
+                mqtt::spawn_mqtt(&url, &topics, prefix, tx, counters)
+            });
+        }
+        #[cfg(feature = "nats")]
+        if let Some(url) = opt.nats.clone() {
+            let subjects = opt.subject.clone();
+            sources.add_named(move |tx, counters| nats::spawn_nats(&url, &subjects, tx, counters));
+        }
+        #[cfg(feature = "sse")]
+        for url in opt.sse.clone() {
+            sources.add(move |tx, counters| sse::spawn_sse(&url, tx, counters));
+        }
+        #[cfg(feature = "ws")]
+        for url in opt.ws.clone() {
+            let field = opt.ws_field.clone();
+            sources.add(move |tx, counters| ws::spawn_ws(&url, field.as_deref(), tx, counters));
+        }
+
+        let matches = expand_globs(globs);
+        if !globs.is_empty() && matches.is_empty() && rescan.is_none() {
+            let patterns: Vec<&str> = globs.iter().map(Pattern::as_str).collect();
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no file matches {}", patterns.join(" ")),
+            ));
+        }
+        let mut followed = HashSet::new();
+        for path in files
+            .iter()
+            .filter(|path| path.as_os_str() != "-")
+            .chain(&matches)
+        {
+            if followed.insert(path.clone()) {
+                sources.add(FileSource(path.clone()));
+            }
+        }
+        if let Some(period) = rescan {
+            let globs = globs.to_vec();
+            sources.add(move |tx, counters| {
+                spawn_rescan(globs, period, followed, tx, counters);
+                Ok(())
+            });
+        }
+        let stdin_listed = files.iter().any(|path| path.as_os_str() == "-");
+        if !opt.no_stdin && (sources.sources.is_empty() || stdin_listed) {
+            sources.sources.insert(0, Box::new(Stdin));
+            sources.stdin = true;
+        }
+        Ok(sources)
+    }
+
+    pub fn add(&mut self, source: impl Source + 'static) -> &mut Sources {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    /// a source naming its lines as they arrive, after their topic, subject or stream. the
+    /// names take the next color of the palette as they show up
+    pub fn add_named(&mut self, source: impl Source + 'static) -> &mut Sources {
+        self.new_sources = true;
+        self.add(source)
+    }
+
+    /// whether some lines are named after what they carry rather than after their input
+    pub(crate) fn colors_new_sources(&self) -> bool {
+        self.new_sources
+    }
+
+    /// stdin is the only input
+    pub(crate) fn reads_stdin_only(&self) -> bool {
+        self.stdin && self.sources.len() == 1
+    }
+
+    /// every line goes through the shell command, its output is displayed instead
+    pub fn filter_cmd(&mut self, cmd: impl Into<String>) -> &mut Sources {
+        self.filter_cmd = Some(cmd.into());
+        self
+    }
+
+    /// at most `size` lines wait for the matrix, the policy tells what becomes of the next
+    /// ones. a line at least, none would block every reader
+    pub fn queue(&mut self, size: usize, policy: QueuePolicy) -> &mut Sources {
+        self.queue_size = Some(size.max(1));
+        self.queue_policy = policy;
+        self
+    }
+
+    /// a reader per input, started in the order they were added
+    pub fn spawn(self, counters: &Arc<Counters>) -> io::Result<LineReceiver> {
+        let queue = || queue::channel(self.queue_size, self.queue_policy, counters.clone());
+        // the command reads the lines as soon as they are sent, its output is queued
+        let (tx, rx) = match self.filter_cmd {
+            Some(_) => queue::unbounded(),
+            None => queue(),
+        };
+        for source in self.sources {
+            source.spawn(tx.clone(), counters.clone())?;
+        }
+        match &self.filter_cmd {
+            Some(cmd) => pipe::spawn_filter(cmd, rx, queue(), counters.clone()),
+            None => Ok(rx),
+        }
+    }
+}
+
+/// the lines typed or piped in
+pub struct Stdin;
+
+impl Source for Stdin {
+    fn spawn(self: Box<Self>, tx: LineSender, counters: Arc<Counters>) -> io::Result<()> {
+        spawn_stdin(tx, counters);
+        Ok(())
+    }
+}
+
+/// a file followed like `tail -f`
+pub struct FileSource(pub PathBuf);
+
+impl Source for FileSource {
+    fn spawn(self: Box<Self>, tx: LineSender, counters: Arc<Counters>) -> io::Result<()> {
+        file::spawn_follower(self.0, tx, counters)
+    }
+}
+
+impl Source for Listen {
+    fn spawn(self: Box<Self>, tx: LineSender, counters: Arc<Counters>) -> io::Result<()> {
+        listen::spawn_listener(&self, tx, counters)
+    }
+}
+
+/// the lines of an iterator, one every period, for the demos and the tests
+pub struct Generator<I> {
+    name: String,
+    lines: I,
+    period: Duration,
+    color: Option<Color>,
+}
+
+impl<I> Generator<I> {
+    pub fn new(name: &str, lines: I, period: Duration) -> Generator<I> {
+        Generator {
+            name: name.to_string(),
+            lines,
+            period,
+            color: None,
+        }
+    }
+
+    /// the color of the lines, unless their severity tells otherwise
+    pub fn color(mut self, color: Color) -> Generator<I> {
+        self.color = Some(color);
+        self
+    }
+}
+
+impl<I> Source for Generator<I>
+where
+    I: Iterator<Item = String> + Send + 'static,
+{
+    fn spawn(self: Box<Self>, tx: LineSender, _counters: Arc<Counters>) -> io::Result<()> {
+        let Generator {
+            name,
+            lines,
+            period,
+            color,
+        } = *self;
+        spawn(move || {
+            for text in lines {
+                let mut line = InputLine::new(&name, text);
+                line.color = color;
+                if tx.send(line).is_err() {
+                    return;
+                }
+                sleep(period);
+            }
+        });
+        Ok(())
+    }
+}
+
+/// existing files matching the globs, in order
+pub fn expand_globs(globs: &[Pattern]) -> Vec<PathBuf> {
+    globs
+        .iter()
+        .filter_map(|pattern| glob::glob(pattern.as_str()).ok())
+        .flat_map(|paths| paths.filter_map(Result::ok))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+fn spawn_rescan(
+    globs: Vec<Pattern>,
+    period: Duration,
+    mut followed: HashSet<PathBuf>,
+    tx: LineSender,
+    counters: Arc<Counters>,
+) {
+    spawn(move || {
+        loop {
+            sleep(period);
+            for path in expand_globs(&globs) {
+                // a file that could not be opened yet is tried again on the next scan
+                if !followed.contains(&path)
+                    && file::spawn_follower(path.clone(), tx.clone(), counters.clone()).is_ok()
+                {
+                    followed.insert(path);
+                }
+            }
+        }
+    });
+}
+
+// an accept which failed, e.g. with too many files open, is tried again after this
+pub const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// the clients of a listener as they connect, the accepts which failed are tried again
+/// after a pause rather than in a busy loop
+pub fn clients<S>(incoming: impl Iterator<Item = io::Result<S>>) -> impl Iterator<Item = S> {
+    incoming.filter_map(|stream| stream.inspect_err(|_| sleep(ACCEPT_BACKOFF)).ok())
+}
+
+/// name given to the lines read from the input, the file name or `stdin` for `-`
+pub fn source_name(path: &Path) -> String {
+    if path.as_os_str() == "-" {
+        return "stdin".to_string();
+    }
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn spawn_stdin(tx: LineSender, counters: Arc<Counters>) {
+    spawn(move || {
+        loop {
+            let mut buffer = String::new();
+            match io::stdin().read_line(&mut buffer) {
+                // the undecodable line is consumed, keep reading the next ones
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                    counters.dropped.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+                Err(_) | Ok(0) => break,
+                Ok(_) => {}
+            }
+            let buffer = buffer.replace("\n", "");
+            if tx.send(InputLine::new("stdin", buffer)).is_err() {
+                break;
+            }
+        }
+    });
+}
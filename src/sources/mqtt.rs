@@ -0,0 +1,409 @@
+use super::{InputLine, LineSender, runtime};
+use crate::Counters;
+use std::{
+    collections::HashSet,
+    io, process,
+    sync::{Arc, atomic::Ordering},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{
+        TcpStream,
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+    },
+    sync::{Mutex, mpsc},
+    time::sleep,
+};
+
+const DEFAULT_PORT: u16 = 1883;
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+// the broker drops the client after one and a half keep alive without a packet
+const KEEP_ALIVE: Duration = Duration::from_secs(60);
+const PING_PERIOD: Duration = Duration::from_secs(30);
+
+// packet types, in the high nibble of the first byte
+const CONNECT: u8 = 1;
+const CONNACK: u8 = 2;
+const PUBLISH: u8 = 3;
+const PUBACK: u8 = 4;
+const PUBREC: u8 = 5;
+const PUBREL: u8 = 6;
+const PUBCOMP: u8 = 7;
+const SUBSCRIBE: u8 = 8;
+const SUBACK: u8 = 9;
+const PINGREQ: u8 = 12;
+
+/// a subscribed connection, the writer is shared with the keep alive
+struct Connection {
+    reader: BufReader<OwnedReadHalf>,
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+}
+
+/// broker and credentials of `[mqtt://][USER:PASS@]HOST[:PORT]`
+#[derive(Clone)]
+struct Broker {
+    addr: String,
+    user: Option<String>,
+    pass: Option<String>,
+}
+
+impl Broker {
+    fn parse(url: &str) -> Broker {
+        let rest = url
+            .strip_prefix("mqtt://")
+            .unwrap_or(url)
+            .trim_end_matches('/');
+        let (credentials, host) = match rest.rsplit_once('@') {
+            Some((credentials, host)) => (Some(credentials), host),
+            None => (None, rest),
+        };
+        let (user, pass) = match credentials.map(|c| c.split_once(':')) {
+            Some(Some((user, pass))) => (Some(user.to_string()), Some(pass.to_string())),
+            Some(None) => (credentials.map(str::to_string), None),
+            None => (None, None),
+        };
+        let addr = match host.contains(':') {
+            true => host.to_string(),
+            false => format!("{host}:{DEFAULT_PORT}"),
+        };
+        Broker { addr, user, pass }
+    }
+
+    // connected and subscribed, MQTT 3.1.1
+    async fn subscribe(&self, topics: &[String]) -> io::Result<Connection> {
+        let context =
+            |err: io::Error| io::Error::new(err.kind(), format!("mqtt {}: {err}", self.addr));
+        let stream = TcpStream::connect(&self.addr).await.map_err(context)?;
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        // clean session, the messages published while disconnected are not wanted
+        let mut flags = 0x02;
+        let mut connect = string(b"MQTT");
+        connect.push(4);
+        let mut payload = string(format!("logmatrix-{}", process::id()).as_bytes());
+        if let Some(user) = &self.user {
+            flags |= 0x80;
+            payload.extend(string(user.as_bytes()));
+        }
+        if let Some(pass) = &self.pass {
+            flags |= 0x40;
+            payload.extend(string(pass.as_bytes()));
+        }
+        connect.push(flags);
+        connect.extend((KEEP_ALIVE.as_secs() as u16).to_be_bytes());
+        connect.extend(payload);
+        write_packet(&mut writer, CONNECT << 4, &connect)
+            .await
+            .map_err(context)?;
+        let (kind, body) = read_packet(&mut reader).await.map_err(context)?;
+        match (kind >> 4, body.get(1)) {
+            (CONNACK, Some(0)) => {}
+            (CONNACK, Some(code)) => {
+                let reason = match code {
+                    1 => "unacceptable protocol version",
+                    2 => "client identifier rejected",
+                    3 => "server unavailable",
+                    4 => "bad user name or password",
+                    5 => "not authorized",
+                    _ => "connection refused",
+                };
+                return Err(context(io::Error::other(reason)));
+            }
+            _ => return Err(context(io::ErrorKind::InvalidData.into())),
+        }
+
+        // packet identifier 1, every topic at QoS 0
+        let mut subscribe = 1u16.to_be_bytes().to_vec();
+        for topic in topics {
+            subscribe.extend(string(topic.as_bytes()));
+            subscribe.push(0);
+        }
+        write_packet(&mut writer, SUBSCRIBE << 4 | 0x02, &subscribe)
+            .await
+            .map_err(context)?;
+        loop {
+            let (kind, body) = read_packet(&mut reader).await.map_err(context)?;
+            if kind >> 4 != SUBACK {
+                continue;
+            }
+            if body.get(2..).is_some_and(|codes| codes.contains(&0x80)) {
+                let message = "subscription refused";
+                return Err(context(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    message,
+                )));
+            }
+            return Ok(Connection {
+                reader,
+                writer: Arc::new(Mutex::new(writer)),
+            });
+        }
+    }
+}
+
+/// the messages published on the topics, `+` and `#` wildcards included, are named after
+/// their topic and prefixed with it when asked to. the subscriptions are renewed when the
+/// connection drops
+pub fn spawn_mqtt(
+    url: &str,
+    topics: &[String],
+    prefix: bool,
+    tx: LineSender,
+    counters: Arc<Counters>,
+) -> io::Result<()> {
+    let broker = Broker::parse(url);
+    let topics = topics.to_vec();
+    // fail early on a wrong broker or credentials rather than retrying forever
+    let (first, first_topics) = (broker.clone(), topics.clone());
+    let connection = runtime::wait(async move { first.subscribe(&first_topics).await })??;
+    let tx = runtime::forward(tx);
+    runtime::spawn_task(async move {
+        let mut connection = Some(connection);
+        loop {
+            let connected = match connection.take() {
+                Some(connection) => Ok(connection),
+                None => broker.subscribe(&topics).await,
+            };
+            if let Ok(connection) = connected
+                && !receive(connection, prefix, &tx, &counters).await
+            {
+                return;
+            }
+            sleep(RECONNECT_DELAY).await;
+        }
+    })
+}
+
+// false once the matrix is gone
+async fn receive(
+    connection: Connection,
+    prefix: bool,
+    tx: &mpsc::Sender<InputLine>,
+    counters: &Counters,
+) -> bool {
+    let Connection { mut reader, writer } = connection;
+    // the keep alive is sent aside until the connection is dropped
+    let pinger = writer.clone();
+    let pinger = tokio::spawn(async move {
+        loop {
+            sleep(PING_PERIOD).await;
+            if write_packet(&mut *pinger.lock().await, PINGREQ << 4, &[])
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    });
+    // the QoS 2 messages shown and not released yet, sent again they are not shown twice
+    let mut unreleased = HashSet::new();
+    let alive = 'read: loop {
+        let Ok((kind, body)) = read_packet(&mut reader).await else {
+            break true;
+        };
+        match kind >> 4 {
+            PUBLISH => {}
+            // the last step of a QoS 2 delivery
+            PUBREL => {
+                let Some(&[high, low]) = body.get(..2) else {
+                    break true;
+                };
+                unreleased.remove(&[high, low]);
+                if write_packet(&mut *writer.lock().await, PUBCOMP << 4, &[high, low])
+                    .await
+                    .is_err()
+                {
+                    break true;
+                }
+                continue;
+            }
+            // SUBACK, PINGRESP
+            _ => continue,
+        }
+        let Some(publish) = Publish::parse(kind, &body) else {
+            break true;
+        };
+        // the topics are subscribed at QoS 0, a broker which still sends a higher one is
+        // answered as it expects
+        if let Some(id) = publish.id {
+            let ack = match publish.qos {
+                2 => PUBREC,
+                _ => PUBACK,
+            };
+            if write_packet(&mut *writer.lock().await, ack << 4, &id)
+                .await
+                .is_err()
+            {
+                break true;
+            }
+            if publish.qos == 2 && !unreleased.insert(id) {
+                continue;
+            }
+        }
+        let Ok(text) = std::str::from_utf8(publish.payload) else {
+            counters.dropped.fetch_add(1, Ordering::Relaxed);
+            continue;
+        };
+        let topic = publish.topic;
+        for line in text.lines() {
+            let line = match prefix {
+                true => format!("{topic} {line}"),
+                false => line.to_string(),
+            };
+            if tx.send(InputLine::new(topic, line)).await.is_err() {
+                break 'read false;
+            }
+        }
+    };
+    pinger.abort();
+    alive
+}
+
+/// a PUBLISH packet, with its identifier at QoS 1 and 2
+struct Publish<'a> {
+    topic: &'a str,
+    qos: u8,
+    id: Option<[u8; 2]>,
+    payload: &'a [u8],
+}
+
+impl<'a> Publish<'a> {
+    // none for a malformed packet, after which the connection is dropped
+    fn parse(header: u8, body: &'a [u8]) -> Option<Publish<'a>> {
+        let qos = (header >> 1) & 0x03;
+        let topic_len = u16::from_be_bytes([*body.first()?, *body.get(1)?]) as usize;
+        let mut offset = 2 + topic_len;
+        let topic = std::str::from_utf8(body.get(2..offset)?).ok()?;
+        let id = match qos {
+            0 => None,
+            1 | 2 => {
+                let id = [*body.get(offset)?, *body.get(offset + 1)?];
+                offset += 2;
+                Some(id)
+            }
+            _ => return None,
+        };
+        Some(Publish {
+            topic,
+            qos,
+            id,
+            payload: &body[offset..],
+        })
+    }
+}
+
+// UTF-8 string or binary data, prefixed with its length
+fn string(bytes: &[u8]) -> Vec<u8> {
+    let mut string = (bytes.len() as u16).to_be_bytes().to_vec();
+    string.extend(bytes);
+    string
+}
+
+async fn write_packet(
+    stream: &mut (impl AsyncWrite + Unpin),
+    header: u8,
+    body: &[u8],
+) -> io::Result<()> {
+    let mut packet = vec![header];
+    // remaining length, 7 bits per byte, the high bit set when more follow
+    let mut len = body.len();
+    loop {
+        let byte = (len % 128) as u8;
+        len /= 128;
+        packet.push(if len > 0 { byte | 0x80 } else { byte });
+        if len == 0 {
+            break;
+        }
+    }
+    packet.extend(body);
+    stream.write_all(&packet).await
+}
+
+// first byte of the packet and what follows its length
+async fn read_packet(stream: &mut (impl AsyncRead + Unpin)) -> io::Result<(u8, Vec<u8>)> {
+    let mut byte = [0];
+    stream.read_exact(&mut byte).await?;
+    let header = byte[0];
+    let (mut len, mut shift) = (0usize, 0);
+    loop {
+        stream.read_exact(&mut byte).await?;
+        len |= ((byte[0] & 0x7f) as usize) << shift;
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+        if shift > 21 {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+    }
+    let mut body = vec![0; len];
+    stream.read_exact(&mut body).await?;
+    Ok((header, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_on<T>(future: impl Future<Output = T>) -> T {
+        runtime::runtime().unwrap().block_on(future)
+    }
+
+    #[test]
+    fn broker_urls() {
+        let broker = Broker::parse("mqtt://user:p@ss@broker:8883/");
+        assert_eq!(broker.addr, "broker:8883");
+        assert_eq!(broker.user.as_deref(), Some("user"));
+        assert_eq!(broker.pass.as_deref(), Some("p@ss"));
+        let broker = Broker::parse("user@broker");
+        assert_eq!(broker.addr, "broker:1883");
+        assert_eq!((broker.user.as_deref(), broker.pass), (Some("user"), None));
+        let broker = Broker::parse("broker");
+        assert_eq!((broker.addr.as_str(), broker.user), ("broker:1883", None));
+    }
+
+    #[test]
+    fn remaining_lengths() {
+        for (len, encoded) in [
+            (0, &[0x00][..]),
+            (127, &[0x7f]),
+            (128, &[0x80, 0x01]),
+            (16_383, &[0xff, 0x7f]),
+            (16_384, &[0x80, 0x80, 0x01]),
+            (2_097_152, &[0x80, 0x80, 0x80, 0x01]),
+        ] {
+            let body = vec![7; len];
+            let mut packet = vec![];
+            block_on(write_packet(&mut packet, PUBLISH << 4, &body)).unwrap();
+            assert_eq!(&packet[1..1 + encoded.len()], encoded, "{len}");
+            let (header, read) = block_on(read_packet(&mut packet.as_slice())).unwrap();
+            assert_eq!((header, read.len()), (PUBLISH << 4, len));
+        }
+        // at most 4 bytes of length
+        let long = [PUBLISH << 4, 0xff, 0xff, 0xff, 0xff, 0x01];
+        let err = block_on(read_packet(&mut &long[..])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        // the body announced is not there
+        assert!(block_on(read_packet(&mut &[PUBLISH << 4, 0x05, 1, 2][..])).is_err());
+    }
+
+    #[test]
+    fn publish_packets() {
+        let body = [&string(b"a/b")[..], b"disk full"].concat();
+        let publish = Publish::parse(PUBLISH << 4, &body).unwrap();
+        assert_eq!((publish.topic, publish.qos, publish.id), ("a/b", 0, None));
+        assert_eq!(publish.payload, b"disk full");
+        let body = [&string(b"a/b")[..], &[0, 9], b"up"].concat();
+        for qos in [1, 2] {
+            let publish = Publish::parse(PUBLISH << 4 | qos << 1, &body).unwrap();
+            assert_eq!((publish.qos, publish.id), (qos, Some([0, 9])));
+            assert_eq!(publish.payload, b"up");
+        }
+        assert!(Publish::parse(PUBLISH << 4 | 3 << 1, &body).is_none());
+        assert!(Publish::parse(PUBLISH << 4, &[0, 5, b'a']).is_none());
+        assert!(Publish::parse(PUBLISH << 4 | 1 << 1, &string(b"a")).is_none());
+        assert!(Publish::parse(PUBLISH << 4, &[0, 1, 0xff]).is_none());
+    }
+}
@@ -0,0 +1,493 @@
+use super::{InputLine, LineSender, Severity, clients};
+use crate::Counters;
+use flate2::read::GzDecoder;
+use serde_json::{Map, Value};
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, atomic::Ordering},
+    thread::spawn,
+};
+
+const LOGS_PATH: &str = "/v1/logs";
+// a batch larger than this, compressed or not, is refused rather than buffered
+const MAX_BODY: usize = 16 << 20;
+
+/// receive the logs exported with OTLP over HTTP, JSON or protobuf encoded, e.g. by the
+/// `otlphttp` exporter of a collector. the lines are named after the `service.name` of
+/// their resource and colored after their severity number
+pub fn spawn_listener(addr: &str, tx: LineSender, counters: Arc<Counters>) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)
+        .map_err(|err| io::Error::new(err.kind(), format!("otlp {addr}: {err}")))?;
+    spawn(move || {
+        for stream in clients(listener.incoming()) {
+            let (tx, counters) = (tx.clone(), counters.clone());
+            spawn(move || serve(stream, &tx, &counters));
+        }
+    });
+    Ok(())
+}
+
+// the requests of a kept alive connection, until it is closed or the matrix is gone
+fn serve(stream: TcpStream, tx: &LineSender, counters: &Counters) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let mut reader = BufReader::new(stream);
+    loop {
+        let Some(request) = read_request(&mut reader) else {
+            return;
+        };
+        let protobuf = request.content_type.starts_with("application/x-protobuf");
+        let lines = match (request.method.as_str(), request.path.as_str()) {
+            ("POST", LOGS_PATH) => decode(&request, protobuf),
+            ("POST", _) => Err("404 Not Found"),
+            _ => Err("405 Method Not Allowed"),
+        };
+        // an empty ExportLogsServiceResponse, a full success
+        let (status, body): (&str, &[u8]) = match &lines {
+            Ok(_) if protobuf => ("200 OK", b""),
+            Ok(_) => ("200 OK", b"{}"),
+            Err(status) => (status, b""),
+        };
+        let content_type = if protobuf {
+            "application/x-protobuf"
+        } else {
+            "application/json"
+        };
+        let response = format!(
+            "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        );
+        if writer
+            .write_all(response.as_bytes())
+            .and_then(|_| writer.write_all(body))
+            .is_err()
+        {
+            return;
+        }
+        match lines {
+            Ok(lines) => {
+                for line in lines {
+                    if tx.send(line).is_err() {
+                        return;
+                    }
+                }
+            }
+            Err(_) => {
+                counters.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    content_type: String,
+    gzip: bool,
+    body: Vec<u8>,
+}
+
+// none once the connection is closed or broken
+fn read_request(reader: &mut BufReader<TcpStream>) -> Option<Request> {
+    let mut line = String::new();
+    reader.read_line(&mut line).ok().filter(|read| *read > 0)?;
+    let mut parts = line.split_whitespace();
+    let (method, path) = (parts.next()?.to_string(), parts.next()?.to_string());
+    let (mut length, mut content_type, mut gzip) = (0, String::new(), false);
+    loop {
+        let mut header = String::new();
+        reader
+            .read_line(&mut header)
+            .ok()
+            .filter(|read| *read > 0)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match name.to_ascii_lowercase().as_str() {
+            "content-length" => length = value.parse().ok()?,
+            "content-type" => content_type = value.to_ascii_lowercase(),
+            "content-encoding" => gzip = value.eq_ignore_ascii_case("gzip"),
+            _ => {}
+        }
+    }
+    if length > MAX_BODY {
+        return None;
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+    Some(Request {
+        method,
+        path,
+        content_type,
+        gzip,
+        body,
+    })
+}
+
+fn decode(request: &Request, protobuf: bool) -> Result<Vec<InputLine>, &'static str> {
+    let body = match request.gzip {
+        true => {
+            let mut body = vec![];
+            GzDecoder::new(request.body.as_slice())
+                .take(MAX_BODY as u64 + 1)
+                .read_to_end(&mut body)
+                .map_err(|_| "400 Bad Request")?;
+            if body.len() > MAX_BODY {
+                return Err("413 Content Too Large");
+            }
+            body
+        }
+        false => request.body.clone(),
+    };
+    let lines = match protobuf {
+        true => proto::logs(&body),
+        false => json_logs(&body),
+    };
+    lines.ok_or("400 Bad Request")
+}
+
+// the severity numbers go by four, from TRACE at 1 to FATAL at 21
+fn severity(number: u64) -> Option<Severity> {
+    match number {
+        1..=8 => Some(Severity::Debug),
+        9..=12 => Some(Severity::Info),
+        13..=16 => Some(Severity::Warning),
+        17..=20 => Some(Severity::Error),
+        21..=24 => Some(Severity::Critical),
+        _ => None,
+    }
+}
+
+fn record_lines(service: &str, severity_number: u64, body: &Value) -> Vec<InputLine> {
+    let text = match body {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        body => body.to_string(),
+    };
+    text.lines()
+        .map(|text| {
+            let mut line = InputLine::new(service, text.to_string());
+            line.severity = severity(severity_number);
+            line
+        })
+        .collect()
+}
+
+// {"resourceLogs": [{"resource": {"attributes": [..]}, "scopeLogs": [{"logRecords": [..]}]}]}
+fn json_logs(body: &[u8]) -> Option<Vec<InputLine>> {
+    let request: Value = serde_json::from_slice(body).ok()?;
+    let mut lines = vec![];
+    for resource in request["resourceLogs"].as_array()? {
+        let service = resource["resource"]["attributes"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|attribute| attribute["key"] == "service.name")
+            .and_then(|attribute| attribute["value"]["stringValue"].as_str())
+            .unwrap_or("otlp");
+        let scopes = resource["scopeLogs"].as_array().into_iter().flatten();
+        let records = scopes.flat_map(|scope| scope["logRecords"].as_array().into_iter().flatten());
+        for record in records {
+            let number = record["severityNumber"].as_u64().unwrap_or(0);
+            lines.extend(record_lines(
+                service,
+                number,
+                &json_any_value(&record["body"]),
+            ));
+        }
+    }
+    Some(lines)
+}
+
+// {"stringValue": ..}, {"intValue": ..}, {"arrayValue": {"values": [..]}}, ..
+fn json_any_value(value: &Value) -> Value {
+    let Some((kind, inner)) = value.as_object().and_then(|value| value.iter().next()) else {
+        return Value::Null;
+    };
+    match kind.as_str() {
+        "arrayValue" => Value::Array(
+            inner["values"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(json_any_value)
+                .collect(),
+        ),
+        "kvlistValue" => Value::Object(
+            inner["values"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|pair| {
+                    Some((
+                        pair["key"].as_str()?.to_string(),
+                        json_any_value(&pair["value"]),
+                    ))
+                })
+                .collect::<Map<_, _>>(),
+        ),
+        _ => inner.clone(),
+    }
+}
+
+// just enough of the protobuf wire format for ExportLogsServiceRequest
+mod proto {
+    use super::record_lines;
+    use crate::sources::InputLine;
+    use serde_json::{Map, Value};
+
+    enum Field<'a> {
+        Varint(u64),
+        Fixed64(u64),
+        Bytes(&'a [u8]),
+        Fixed32,
+    }
+
+    fn varint(buf: &mut &[u8]) -> Option<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let (byte, rest) = buf.split_first()?;
+            *buf = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    // the fields of a message with their number, none when it is malformed
+    fn fields(mut buf: &[u8]) -> Option<Vec<(u64, Field<'_>)>> {
+        let mut fields = vec![];
+        while !buf.is_empty() {
+            let key = varint(&mut buf)?;
+            let field = match key & 0x07 {
+                0 => Field::Varint(varint(&mut buf)?),
+                1 => {
+                    let (bytes, rest) = buf.split_at_checked(8)?;
+                    buf = rest;
+                    Field::Fixed64(u64::from_le_bytes(bytes.try_into().ok()?))
+                }
+                2 => {
+                    let len = varint(&mut buf)? as usize;
+                    let (bytes, rest) = buf.split_at_checked(len)?;
+                    buf = rest;
+                    Field::Bytes(bytes)
+                }
+                5 => {
+                    buf = buf.get(4..)?;
+                    Field::Fixed32
+                }
+                _ => return None,
+            };
+            fields.push((key >> 3, field));
+        }
+        Some(fields)
+    }
+
+    // embedded messages of a field
+    fn messages<'a>(fields: &[(u64, Field<'a>)], number: u64) -> Vec<&'a [u8]> {
+        fields
+            .iter()
+            .filter_map(|(field, value)| match value {
+                Field::Bytes(bytes) if *field == number => Some(*bytes),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn string(bytes: &[u8]) -> String {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+
+    pub fn logs(body: &[u8]) -> Option<Vec<InputLine>> {
+        let mut lines = vec![];
+        // ExportLogsServiceRequest.resource_logs = 1
+        for resource_logs in messages(&fields(body)?, 1) {
+            let resource_logs = fields(resource_logs)?;
+            // ResourceLogs.resource = 1, Resource.attributes = 1
+            let mut service = "otlp".to_string();
+            for resource in messages(&resource_logs, 1) {
+                for attribute in messages(&fields(resource)?, 1) {
+                    let (key, value) = key_value(attribute)?;
+                    if key == "service.name"
+                        && let Value::String(name) = value
+                    {
+                        service = name;
+                    }
+                }
+            }
+            // ResourceLogs.scope_logs = 2, ScopeLogs.log_records = 2
+            for scope_logs in messages(&resource_logs, 2) {
+                for record in messages(&fields(scope_logs)?, 2) {
+                    let record = fields(record)?;
+                    // LogRecord.severity_number = 2, LogRecord.body = 5
+                    let number = record.iter().find_map(|(field, value)| match value {
+                        Field::Varint(number) if *field == 2 => Some(*number),
+                        _ => None,
+                    });
+                    let body = match messages(&record, 5).first() {
+                        Some(body) => any_value(body)?,
+                        None => Value::Null,
+                    };
+                    lines.extend(record_lines(&service, number.unwrap_or(0), &body));
+                }
+            }
+        }
+        Some(lines)
+    }
+
+    // KeyValue.key = 1, KeyValue.value = 2
+    fn key_value(bytes: &[u8]) -> Option<(String, Value)> {
+        let fields = fields(bytes)?;
+        let key = messages(&fields, 1)
+            .first()
+            .map_or(String::new(), |key| string(key));
+        let value = match messages(&fields, 2).first() {
+            Some(value) => any_value(value)?,
+            None => Value::Null,
+        };
+        Some((key, value))
+    }
+
+    // AnyValue is a oneof of string 1, bool 2, int 3, double 4, array 5, kvlist 6, bytes 7
+    fn any_value(bytes: &[u8]) -> Option<Value> {
+        let oneof = fields(bytes)?;
+        let Some((number, field)) = oneof.first() else {
+            return Some(Value::Null);
+        };
+        Some(match (number, field) {
+            (1, Field::Bytes(text)) => Value::String(string(text)),
+            (2, Field::Varint(flag)) => Value::Bool(*flag != 0),
+            (3, Field::Varint(int)) => Value::from(*int as i64),
+            (4, Field::Fixed64(bits)) => Value::from(f64::from_bits(*bits)),
+            // ArrayValue.values = 1, KeyValueList.values = 1
+            (5, Field::Bytes(array)) => Value::Array(
+                messages(&fields(array)?, 1)
+                    .into_iter()
+                    .map(any_value)
+                    .collect::<Option<_>>()?,
+            ),
+            (6, Field::Bytes(list)) => Value::Object(
+                messages(&fields(list)?, 1)
+                    .into_iter()
+                    .map(key_value)
+                    .collect::<Option<Map<_, _>>>()?,
+            ),
+            (7, Field::Bytes(bytes)) => Value::String(string(bytes)),
+            _ => Value::Null,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compression, write::GzEncoder};
+
+    fn gzipped(body: &[u8]) -> Request {
+        let mut encoder = GzEncoder::new(vec![], Compression::best());
+        encoder.write_all(body).unwrap();
+        Request {
+            method: "POST".to_string(),
+            path: LOGS_PATH.to_string(),
+            content_type: "application/json".to_string(),
+            gzip: true,
+            body: encoder.finish().unwrap(),
+        }
+    }
+
+    #[test]
+    fn refuses_decompression_bombs() {
+        let bomb = gzipped(&vec![b' '; MAX_BODY + 1]);
+        assert!(bomb.body.len() < MAX_BODY);
+        assert_eq!(decode(&bomb, false).err(), Some("413 Content Too Large"));
+    }
+
+    // source, text and severity of the lines
+    fn summary(lines: Vec<InputLine>) -> Vec<(String, String, Option<Severity>)> {
+        lines
+            .into_iter()
+            .map(|line| (line.source, line.text, line.severity))
+            .collect()
+    }
+
+    // a length delimited field
+    fn field(number: u8, bytes: &[u8]) -> Vec<u8> {
+        let mut field = vec![number << 3 | 2];
+        let mut len = bytes.len();
+        while len >= 0x80 {
+            field.push(len as u8 | 0x80);
+            len >>= 7;
+        }
+        field.push(len as u8);
+        field.extend(bytes);
+        field
+    }
+
+    fn key_value(key: &str, value: &[u8]) -> Vec<u8> {
+        [field(1, key.as_bytes()), field(2, value)].concat()
+    }
+
+    #[test]
+    fn protobuf_logs() {
+        let resource = field(1, &key_value("service.name", &field(1, b"api")));
+        // severity_number 17, an ERROR
+        let error = [&[0x10, 17][..], &field(5, &field(1, b"disk\nfull"))].concat();
+        let list = field(6, &field(1, &key_value("code", &[0x18, 3])));
+        let unknown = [&[0x29][..], &[0; 8], &[0x35, 0, 0, 0, 0], &field(5, &list)].concat();
+        let scope = [field(2, &error), field(2, &unknown)].concat();
+        let request = field(1, &[field(1, &resource), field(2, &scope)].concat());
+        assert_eq!(
+            summary(proto::logs(&request).unwrap()),
+            [
+                ("api".into(), "disk".into(), Some(Severity::Error)),
+                ("api".into(), "full".into(), Some(Severity::Error)),
+                ("api".into(), "{\"code\":3}".into(), None),
+            ]
+        );
+        let anonymous = field(1, &field(2, &field(2, &field(5, &[0x10, 1]))));
+        assert_eq!(
+            summary(proto::logs(&anonymous).unwrap()),
+            [("otlp".into(), "true".into(), None)]
+        );
+        for broken in [&request[..request.len() - 1], &[0x0f], &[0x08, 0x80]] {
+            assert!(proto::logs(broken).is_none());
+        }
+    }
+
+    #[test]
+    fn json_logs_of_the_spec() {
+        let body = br#"{"resourceLogs": [{
+            "resource": {"attributes": [{"key": "service.name", "value": {"stringValue": "web"}}]},
+            "scopeLogs": [{"logRecords": [
+                {"severityNumber": 9, "body": {"stringValue": "GET /"}},
+                {"severityNumber": 13, "body": {"kvlistValue": {"values": [
+                    {"key": "slow", "value": {"arrayValue": {"values": [{"intValue": "3"}]}}}
+                ]}}},
+                {"body": {}}
+            ]}]
+        }, {"scopeLogs": [{"logRecords": [{"severityNumber": 21, "body": {"boolValue": true}}]}]}]}"#;
+        assert_eq!(
+            summary(json_logs(body).unwrap()),
+            [
+                ("web".into(), "GET /".into(), Some(Severity::Info)),
+                (
+                    "web".into(),
+                    "{\"slow\":[\"3\"]}".into(),
+                    Some(Severity::Warning)
+                ),
+                ("otlp".into(), "true".into(), Some(Severity::Critical)),
+            ]
+        );
+        assert!(json_logs(b"{}").is_none());
+        assert!(json_logs(b"not json").is_none());
+    }
+}
@@ -0,0 +1,79 @@
+use super::{InputLine, LineReceiver, LineSender};
+use crate::Counters;
+use std::{
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    process::{Command, Stdio},
+    sync::{
+        Arc,
+        atomic::Ordering,
+        mpsc::{self, TrySendError},
+    },
+    thread::spawn,
+};
+
+// lines waiting for a slow command, the next ones are dropped
+const PIPE_BACKLOG: usize = 1024;
+
+/// the lines written to the stdin of `sh -c cmd` one per line, its output lines take their
+/// place, named after the command. once the inputs are done its stdin is closed and the
+/// channel disconnects when it exits. the output lines are sent to `output`
+pub fn spawn_filter(
+    cmd: &str,
+    input: LineReceiver,
+    output: (LineSender, LineReceiver),
+    counters: Arc<Counters>,
+) -> io::Result<LineReceiver> {
+    let mut child = Command::new("sh")
+        .args(["-c", cmd])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| io::Error::new(err.kind(), format!("{cmd}: {err}")))?;
+    let stdin = child.stdin.take().expect("stdin is piped");
+    let stdout = child.stdout.take().expect("stdout is piped");
+
+    // the inputs are never blocked by the command, what it cannot keep up with is dropped
+    let (backlog_tx, backlog) = mpsc::sync_channel::<InputLine>(PIPE_BACKLOG);
+    let dropped = counters.clone();
+    spawn(move || {
+        for line in input {
+            match backlog_tx.try_send(line) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    dropped.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(TrySendError::Disconnected(_)) => break,
+            }
+        }
+    });
+    spawn(move || {
+        let mut stdin = BufWriter::new(stdin);
+        for line in backlog {
+            // flushed every line for the commands answering line by line
+            if writeln!(stdin, "{}", line.text)
+                .and_then(|()| stdin.flush())
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    let (tx, rx) = output;
+    let source = cmd.to_string();
+    spawn(move || {
+        for text in BufReader::new(stdout).lines() {
+            let Ok(text) = text else {
+                counters.dropped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            };
+            if tx.send(InputLine::new(&source, text)).is_err() {
+                break;
+            }
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+    });
+    Ok(rx)
+}
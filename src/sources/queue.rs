@@ -0,0 +1,178 @@
+use super::InputLine;
+use crate::Counters;
+use clap::ValueEnum;
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::Ordering,
+        mpsc::{RecvError, SendError, TryRecvError},
+    },
+};
+
+/// what becomes of a line sent to a full queue
+#[derive(ValueEnum, Debug, Clone, Copy, Default)]
+#[clap(rename_all = "kebab_case")]
+pub enum QueuePolicy {
+    /// the reader waits for room, the producer is slowed down through its pipe or socket
+    #[default]
+    Block,
+    /// the line waiting the longest makes room
+    DropOldest,
+    /// the line sent is dropped
+    DropNewest,
+}
+
+struct State {
+    lines: VecDeque<InputLine>,
+    senders: usize,
+    receiving: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    capacity: Option<usize>,
+    policy: QueuePolicy,
+    counters: Arc<Counters>,
+    sent: Condvar,     // a line was queued or the last sender left
+    received: Condvar, // room was made or the receiver left
+}
+
+/// the lines of the readers on their way to the matrix, at most `capacity` of them wait. like
+/// `mpsc` the receiver disconnects once every sender is dropped
+pub fn channel(
+    capacity: Option<usize>,
+    policy: QueuePolicy,
+    counters: Arc<Counters>,
+) -> (LineSender, LineReceiver) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            lines: VecDeque::new(),
+            senders: 1,
+            receiving: true,
+        }),
+        capacity,
+        policy,
+        counters,
+        sent: Condvar::new(),
+        received: Condvar::new(),
+    });
+    (LineSender(shared.clone()), LineReceiver(shared))
+}
+
+/// a queue with no limit, like `mpsc::channel`
+pub fn unbounded() -> (LineSender, LineReceiver) {
+    channel(None, QueuePolicy::Block, Arc::new(Counters::default()))
+}
+
+pub struct LineSender(Arc<Shared>);
+
+impl LineSender {
+    /// fails once the receiver is gone, the lines dropped by the policy are counted and
+    /// not an error
+    pub fn send(&self, line: InputLine) -> Result<(), SendError<InputLine>> {
+        let shared = &self.0;
+        let mut state = shared.state.lock().unwrap();
+        let full = |state: &State| shared.capacity.is_some_and(|cap| state.lines.len() >= cap);
+        if state.receiving && full(&state) {
+            match shared.policy {
+                QueuePolicy::Block => {
+                    state = shared
+                        .received
+                        .wait_while(state, |state| state.receiving && full(state))
+                        .unwrap();
+                }
+                QueuePolicy::DropOldest => {
+                    state.lines.pop_front();
+                    shared
+                        .counters
+                        .queue_dropped
+                        .fetch_add(1, Ordering::Relaxed);
+                }
+                QueuePolicy::DropNewest => {
+                    shared
+                        .counters
+                        .queue_dropped
+                        .fetch_add(1, Ordering::Relaxed);
+                    return Ok(());
+                }
+            }
+        }
+        if !state.receiving {
+            return Err(SendError(line));
+        }
+        state.lines.push_back(line);
+        shared.sent.notify_one();
+        Ok(())
+    }
+}
+
+impl Clone for LineSender {
+    fn clone(&self) -> LineSender {
+        self.0.state.lock().unwrap().senders += 1;
+        LineSender(self.0.clone())
+    }
+}
+
+impl Drop for LineSender {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().senders -= 1;
+        self.0.sent.notify_all();
+    }
+}
+
+pub struct LineReceiver(Arc<Shared>);
+
+impl LineReceiver {
+    pub fn try_recv(&self) -> Result<InputLine, TryRecvError> {
+        let mut state = self.0.state.lock().unwrap();
+        match state.lines.pop_front() {
+            Some(line) => {
+                self.0.received.notify_one();
+                Ok(line)
+            }
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// waits for a line, fails once every sender is gone and the queue is empty
+    pub fn recv(&self) -> Result<InputLine, RecvError> {
+        let state = self.0.state.lock().unwrap();
+        let mut state = self
+            .0
+            .sent
+            .wait_while(state, |state| state.lines.is_empty() && state.senders > 0)
+            .unwrap();
+        let line = state.lines.pop_front().ok_or(RecvError)?;
+        self.0.received.notify_one();
+        Ok(line)
+    }
+}
+
+impl Drop for LineReceiver {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().receiving = false;
+        self.0.received.notify_all();
+    }
+}
+
+impl IntoIterator for LineReceiver {
+    type Item = InputLine;
+    type IntoIter = Lines;
+
+    fn into_iter(self) -> Lines {
+        Lines(self)
+    }
+}
+
+/// the lines until every sender is gone
+pub struct Lines(LineReceiver);
+
+impl Iterator for Lines {
+    type Item = InputLine;
+
+    fn next(&mut self) -> Option<InputLine> {
+        self.0.recv().ok()
+    }
+}
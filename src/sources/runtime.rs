@@ -0,0 +1,66 @@
+use super::{InputLine, LineSender};
+use std::{io, sync::OnceLock, thread::spawn};
+use tokio::{
+    runtime::{Builder, Runtime},
+    sync::mpsc,
+};
+
+// lines of the tasks of a source waiting for the thread forwarding them
+const FORWARD_BACKLOG: usize = 1024;
+
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+/// the runtime the network sources are read on, started by the first of them. the matrix
+/// and its frames stay on their own thread
+pub fn runtime() -> io::Result<&'static Runtime> {
+    if let Some(runtime) = RUNTIME.get() {
+        return Ok(runtime);
+    }
+    let runtime = Builder::new_multi_thread()
+        .enable_all()
+        .thread_name("logmatrix-sources")
+        .build()?;
+    Ok(RUNTIME.get_or_init(|| runtime))
+}
+
+/// the task of a source, run until its lines are not wanted anymore
+pub fn spawn_task(task: impl Future<Output = ()> + Send + 'static) -> io::Result<()> {
+    runtime()?.spawn(task);
+    Ok(())
+}
+
+#[cfg(any(feature = "sse", feature = "kafka"))]
+/// the reader of a source whose client has no async API, on the blocking threads of the
+/// runtime
+pub fn spawn_blocking(reader: impl FnOnce() + Send + 'static) -> io::Result<()> {
+    runtime()?.spawn_blocking(reader);
+    Ok(())
+}
+
+#[cfg(any(feature = "ws", feature = "loki", feature = "nats", feature = "mqtt"))]
+/// the output of a future run on the runtime, for a source to fail its start on a wrong
+/// address or credentials rather than retrying forever. it is waited for on a channel,
+/// `block_on` panics when the source is started from the thread of a runtime
+pub fn wait<T: Send + 'static>(future: impl Future<Output = T> + Send + 'static) -> io::Result<T> {
+    let (tx, rx) = std::sync::mpsc::sync_channel(1);
+    runtime()?.spawn(async move {
+        let _ = tx.send(future.await);
+    });
+    rx.recv()
+        .map_err(|_| io::Error::other("the sources runtime is shutting down"))
+}
+
+/// the sender of the tasks of a source, their lines are merged into `tx` by a thread of
+/// their own so a full queue with `--queue-policy block` slows the tasks down without
+/// blocking the runtime. the blocking readers send theirs with `blocking_send`
+pub fn forward(tx: LineSender) -> mpsc::Sender<InputLine> {
+    let (task_tx, mut lines) = mpsc::channel(FORWARD_BACKLOG);
+    spawn(move || {
+        while let Some(line) = lines.blocking_recv() {
+            if tx.send(line).is_err() {
+                return;
+            }
+        }
+    });
+    task_tx
+}
@@ -0,0 +1,120 @@
+use super::{InputLine, LineSender, runtime};
+use crate::Counters;
+use std::{
+    io::{self, BufRead, BufReader},
+    sync::{Arc, atomic::Ordering},
+    thread::sleep,
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use ureq::{Agent, Body};
+
+// until the server asks for another one with `retry:`
+const RECONNECT_DELAY: Duration = Duration::from_secs(3);
+
+/// where to resume the stream after a disconnection
+struct Resume {
+    last_id: Option<String>,
+    delay: Duration,
+}
+
+/// display the data of the events of a `text/event-stream`, reconnecting with the id of
+/// the last event received so the server can send the missed ones. the client blocks, it
+/// is read on the blocking threads of the sources runtime
+pub fn spawn_sse(url: &str, tx: LineSender, counters: Arc<Counters>) -> io::Result<()> {
+    let agent = Agent::new_with_defaults();
+    let mut resume = Resume {
+        last_id: None,
+        delay: RECONNECT_DELAY,
+    };
+    // fail early on a wrong url rather than retrying forever
+    let body = open(&agent, url, &resume)?;
+    let (url, source) = (url.to_string(), host(url).to_string());
+    let tx = runtime::forward(tx);
+    runtime::spawn_blocking(move || {
+        let mut body = Some(body);
+        loop {
+            let connected = match body.take() {
+                Some(body) => Ok(body),
+                None => open(&agent, &url, &resume),
+            };
+            match connected {
+                Ok(body) => {
+                    if !receive(body, &source, &mut resume, &tx, &counters) {
+                        return;
+                    }
+                }
+                // the server asks to stop reconnecting
+                Err(err) if err.kind() == io::ErrorKind::NotFound => return,
+                Err(_) => {}
+            }
+            sleep(resume.delay);
+        }
+    })
+}
+
+fn open(agent: &Agent, url: &str, resume: &Resume) -> io::Result<Body> {
+    let mut request = agent
+        .get(url)
+        .header("Accept", "text/event-stream")
+        .header("Cache-Control", "no-cache");
+    if let Some(id) = &resume.last_id {
+        request = request.header("Last-Event-ID", id);
+    }
+    match request.call() {
+        Ok(response) if response.status() == 204 => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("sse {url}: no content"),
+        )),
+        Ok(response) => Ok(response.into_body()),
+        Err(err) => Err(io::Error::other(format!("sse {url}: {err}"))),
+    }
+}
+
+// false once the matrix is gone. the data lines of an event are sent once the blank
+// line ending it arrives
+fn receive(
+    body: Body,
+    source: &str,
+    resume: &mut Resume,
+    tx: &mpsc::Sender<InputLine>,
+    counters: &Counters,
+) -> bool {
+    let mut data: Vec<String> = vec![];
+    for line in BufReader::new(body.into_reader()).split(b'\n') {
+        let Ok(line) = line else { break };
+        let Ok(line) = String::from_utf8(line) else {
+            counters.dropped.fetch_add(1, Ordering::Relaxed);
+            continue;
+        };
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        if line.is_empty() {
+            for text in data.drain(..) {
+                if tx.blocking_send(InputLine::new(source, text)).is_err() {
+                    return false;
+                }
+            }
+            continue;
+        }
+        let (name, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match name {
+            "data" => data.push(value.to_string()),
+            "id" if !value.contains('\0') => resume.last_id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(millis) = value.parse() {
+                    resume.delay = Duration::from_millis(millis);
+                }
+            }
+            // comments, event types and unknown fields
+            _ => {}
+        }
+    }
+    true
+}
+
+// `https://host:port/path` is named `host:port`
+fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split('/').next().unwrap_or(rest)
+}
@@ -0,0 +1,227 @@
+use super::{InputLine, LineSender, Severity, clients};
+use crate::Counters;
+use std::{
+    io::{self, BufRead, BufReader, Read},
+    net::{TcpListener, TcpStream, UdpSocket},
+    sync::{Arc, atomic::Ordering},
+    thread::spawn,
+};
+
+// large enough for any datagram
+const MAX_DATAGRAM: usize = 65536;
+
+/// address to receive syslog messages on, `udp://HOST:PORT` or `tcp://HOST:PORT`
+#[derive(Clone)]
+pub enum SyslogListen {
+    Udp(String),
+    Tcp(String),
+}
+
+pub fn parse_syslog_listen(raw: &str) -> Result<SyslogListen, String> {
+    match raw.split_once("://") {
+        Some(("udp", addr)) => Ok(SyslogListen::Udp(addr.to_string())),
+        Some(("tcp", addr)) => Ok(SyslogListen::Tcp(addr.to_string())),
+        _ => Err(format!(
+            "expected `udp://HOST:PORT` or `tcp://HOST:PORT`, got `{raw}`"
+        )),
+    }
+}
+
+/// bind the socket right away and receive the messages in the background, every
+/// TCP client gets its own thread
+pub fn spawn_listener(
+    listen: &SyslogListen,
+    tx: LineSender,
+    counters: Arc<Counters>,
+) -> io::Result<()> {
+    match listen {
+        SyslogListen::Udp(addr) => {
+            let socket = UdpSocket::bind(addr).map_err(|err| context(addr, err))?;
+            spawn(move || {
+                let mut datagram = vec![0u8; MAX_DATAGRAM];
+                while let Ok(size) = socket.recv(&mut datagram) {
+                    if !forward(&datagram[..size], &tx, &counters) {
+                        break;
+                    }
+                }
+            });
+        }
+        SyslogListen::Tcp(addr) => {
+            let listener = TcpListener::bind(addr).map_err(|err| context(addr, err))?;
+            spawn(move || {
+                for stream in clients(listener.incoming()) {
+                    let (tx, counters) = (tx.clone(), counters.clone());
+                    spawn(move || receive_stream(stream, &tx, &counters));
+                }
+            });
+        }
+    }
+    Ok(())
+}
+
+// messages are framed by their length (RFC 6587 octet counting) or by a newline
+fn receive_stream(stream: TcpStream, tx: &LineSender, counters: &Counters) {
+    let mut reader = BufReader::new(stream);
+    loop {
+        let framed_by_length = match reader.fill_buf() {
+            Ok([]) | Err(_) => return,
+            Ok(buffer) => buffer[0].is_ascii_digit(),
+        };
+        let mut message = Vec::new();
+        if framed_by_length {
+            let mut length = Vec::new();
+            if reader.read_until(b' ', &mut length).is_err() {
+                return;
+            }
+            let Some(length) = std::str::from_utf8(&length)
+                .ok()
+                .and_then(|length| length.trim_end().parse::<u64>().ok())
+            else {
+                return;
+            };
+            if reader
+                .by_ref()
+                .take(length)
+                .read_to_end(&mut message)
+                .is_err()
+            {
+                return;
+            }
+        } else if reader.read_until(b'\n', &mut message).is_err() {
+            return;
+        }
+        if !forward(&message, tx, counters) {
+            return;
+        }
+    }
+}
+
+// false once the matrix is gone
+fn forward(message: &[u8], tx: &LineSender, counters: &Counters) -> bool {
+    let message = String::from_utf8_lossy(message);
+    match parse_message(message.trim_end_matches(['\r', '\n', '\0'])) {
+        Some(line) => tx.send(line).is_ok(),
+        None => {
+            counters.dropped.fetch_add(1, Ordering::Relaxed);
+            true
+        }
+    }
+}
+
+/// RFC 5424 `<PRI>1 TIMESTAMP HOST APP PROCID MSGID SD MSG` or
+/// RFC 3164 `<PRI>Mmm dd hh:mm:ss HOST TAG: MSG`, named after the sending host
+fn parse_message(message: &str) -> Option<InputLine> {
+    let (priority, rest) = message.strip_prefix('<')?.split_once('>')?;
+    let priority = priority.parse::<u8>().ok()?;
+
+    let (host, text) = match rest.strip_prefix("1 ") {
+        Some(rest) => {
+            let mut fields = rest.splitn(6, ' ');
+            let (_timestamp, host, app) = (fields.next()?, fields.next()?, fields.next()?);
+            let (_procid, _msgid) = (fields.next()?, fields.next()?);
+            let text = skip_structured_data(fields.next().unwrap_or(""));
+            let text = text.trim_start_matches('\u{feff}');
+            (host, format!("{app}: {text}"))
+        }
+        // the timestamp is always 15 characters long, `Mmm dd hh:mm:ss`
+        None => match rest.get(15..).and_then(|rest| rest.strip_prefix(' ')) {
+            Some(rest) => {
+                let (host, text) = rest.split_once(' ').unwrap_or(("-", rest));
+                (host, text.to_string())
+            }
+            None => ("-", rest.to_string()),
+        },
+    };
+    let mut line = InputLine::new(if host == "-" { "syslog" } else { host }, text);
+    line.severity = Some(Severity::from_priority(priority & 7));
+    Some(line)
+}
+
+// `-` or one or more `[id key="value"...]` elements before the message
+fn skip_structured_data(rest: &str) -> &str {
+    if rest == "-" {
+        return "";
+    }
+    if let Some(message) = rest.strip_prefix("- ") {
+        return message;
+    }
+    if !rest.starts_with('[') {
+        return rest;
+    }
+    let (mut depth, mut quoted, mut escaped) = (0, false, false);
+    for (index, c) in rest.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            '[' if !quoted => depth += 1,
+            ']' if !quoted => depth -= 1,
+            ' ' if depth == 0 && !quoted => return &rest[index + 1..],
+            _ => {}
+        }
+    }
+    ""
+}
+
+fn context(addr: &str, err: io::Error) -> io::Error {
+    io::Error::new(err.kind(), format!("syslog {addr}: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(message: &str) -> (String, String, Severity) {
+        let line = parse_message(message).unwrap();
+        (line.source, line.text, line.severity.unwrap())
+    }
+
+    #[test]
+    fn rfc5424_messages() {
+        let message = "<165>1 2003-10-11T22:14:15.003Z mymachine evntslog - ID47 \
+            [exampleSDID@32473 iut=\"3\" eventSource=\"App] lic\"][other a=\"b\"] \u{feff}An \
+            application event";
+        assert_eq!(
+            parsed(message),
+            (
+                "mymachine".into(),
+                "evntslog: An application event".into(),
+                Severity::Notice
+            )
+        );
+        assert_eq!(
+            parsed("<11>1 - - app 12 - - disk full"),
+            ("syslog".into(), "app: disk full".into(), Severity::Error)
+        );
+        assert_eq!(parsed("<14>1 - host app - - -").1, "app: ");
+    }
+
+    #[test]
+    fn rfc3164_messages() {
+        assert_eq!(
+            parsed("<34>Oct 11 22:14:15 mymachine su: 'su root' failed on /dev/pts/8"),
+            (
+                "mymachine".into(),
+                "su: 'su root' failed on /dev/pts/8".into(),
+                Severity::Critical
+            )
+        );
+        assert_eq!(
+            parsed("<15>no timestamp here"),
+            ("syslog".into(), "no timestamp here".into(), Severity::Debug)
+        );
+    }
+
+    #[test]
+    fn malformed_messages_are_dropped() {
+        for message in [
+            "no priority",
+            "<>1 - - - - - -",
+            "<300>1 a b c d e",
+            "<13",
+            "<13>1 a b",
+        ] {
+            assert!(parse_message(message).is_none(), "{message}");
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use super::{InputLine, LineSender};
+use crate::{Counters, clock, keys::Keyboard};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::PathBuf,
+    sync::{Arc, atomic::Ordering},
+    thread::{sleep, spawn},
+    time::Duration,
+};
+
+// the keyboard is checked this often while waiting for the next line
+const TICK: Duration = Duration::from_millis(50);
+// 10000-01-01, the numbers past it are no timestamps
+const MAX_EPOCH: f64 = 253_402_300_800.;
+
+/// a captured log whose lines start with their timestamp, played back at its own pace
+#[derive(Clone)]
+pub struct TimedReplay {
+    pub path: PathBuf,
+    pub speed: f64,
+}
+
+/// the gaps between the timestamps are waited for, divided by the speed, the lines without
+/// one come along with the previous line. space pauses and resumes the replay
+pub fn spawn_timed(
+    replay: &TimedReplay,
+    tx: LineSender,
+    counters: Arc<Counters>,
+) -> io::Result<()> {
+    let path = &replay.path;
+    let file = File::open(path)
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", path.display())))?;
+    let source = path
+        .file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned();
+    let speed = replay.speed;
+    spawn(move || {
+        // none without a terminal, the replay can't be paused then
+        let mut keyboard = Keyboard::open().ok();
+        let mut paused = false;
+        let mut previous: Option<f64> = None;
+        for line in BufReader::new(file).lines() {
+            let Ok(line) = line else {
+                counters.dropped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            };
+            if let Some(at) = timestamp(&line) {
+                let gap = previous.map_or(0., |previous| (at - previous).max(0.));
+                previous = Some(at);
+                // more than a lifetime at a tiny speed
+                let mut wait = Duration::try_from_secs_f64(gap / speed).unwrap_or(Duration::MAX);
+                while !wait.is_zero() || paused {
+                    if let Some(keyboard) = &mut keyboard
+                        && keyboard.pressed().contains(&b' ')
+                    {
+                        paused = !paused;
+                    }
+                    let tick = wait.min(TICK);
+                    sleep(if paused { TICK } else { tick });
+                    if !paused {
+                        wait -= tick;
+                    }
+                }
+            }
+            if tx.send(InputLine::new(&source, line)).is_err() {
+                return;
+            }
+        }
+    });
+    Ok(())
+}
+
+// seconds since the epoch of the first token of the line, `[..]` allowed around it:
+// RFC 3339, ISO 8601 with a space between the date and the time, or epoch seconds
+fn timestamp(line: &str) -> Option<f64> {
+    let mut tokens = line.split_whitespace().map(|token| {
+        token
+            .trim_start_matches('[')
+            .trim_end_matches([']', ',', ':'])
+    });
+    let first = tokens.next()?;
+    if let Ok(seconds) = first.parse::<f64>() {
+        // a line counter or a level number is no timestamp, 2001-09-09 onwards only. `inf`
+        // and `NaN` are left out too
+        return (1e9..MAX_EPOCH).contains(&seconds).then_some(seconds);
+    }
+    let (date, time) = match first.split_once(['T', 't']) {
+        Some((date, time)) => (date, time.to_string()),
+        None => (first, tokens.next()?.to_string()),
+    };
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    if !(1..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // the offset from UTC comes last, `Z` or `+hh:mm`
+    let (clock, offset) = match time.find(['Z', 'z', '+', '-']) {
+        Some(at) => time.split_at(at),
+        None => (time.as_str(), ""),
+    };
+    let offset = match offset.split_at_checked(1) {
+        Some((sign @ ("+" | "-"), hours_minutes)) => {
+            let (hours, minutes) = hours_minutes
+                .split_once(':')
+                .unwrap_or((hours_minutes, "0"));
+            let hours = hours
+                .parse::<i64>()
+                .ok()
+                .filter(|hours| (0..24).contains(hours))?;
+            let minutes = minutes
+                .parse::<i64>()
+                .ok()
+                .filter(|minutes| (0..60).contains(minutes))?;
+            let seconds = hours * 3600 + minutes * 60;
+            if sign == "-" { -seconds } else { seconds }
+        }
+        _ => 0,
+    };
+    let mut clock = clock.splitn(3, ':');
+    let (hours, minutes) = (
+        clock.next()?.parse::<i64>().ok()?,
+        clock.next()?.parse::<i64>().ok()?,
+    );
+    let seconds = clock
+        .next()
+        .unwrap_or("0")
+        .replace(',', ".")
+        .parse::<f64>()
+        .ok()?;
+    // a leap second at most
+    if !(0..=24).contains(&hours) || !(0..60).contains(&minutes) || !(0. ..61.).contains(&seconds) {
+        return None;
+    }
+    let days = clock::days_from_civil(year, month, day);
+    Some((days * 86400 + hours * 3600 + minutes * 60 - offset) as f64 + seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp("2024-03-01T12:00:00Z started"), Some(1709294400.));
+        assert_eq!(
+            timestamp("[2024-03-01 12:00:00.5] started"),
+            Some(1709294400.5)
+        );
+        assert_eq!(
+            timestamp("2024-03-01T14:30:00+02:30 started"),
+            Some(1709294400.)
+        );
+        assert_eq!(
+            timestamp("2024-03-01T09:00:00,25-03:00 started"),
+            Some(1709294400.25)
+        );
+        assert_eq!(timestamp("1709294400.5 started"), Some(1709294400.5));
+    }
+
+    #[test]
+    fn no_timestamps() {
+        for line in [
+            "42 lines",
+            "started",
+            "",
+            "inf started",
+            "NaN started",
+            "1e300 started",
+            "9223372036854775807-01-01T00:00:00Z",
+            "2024-13-01T00:00:00Z",
+            "2024-03-01T00:00:00+9223372036854775807:00",
+            "2024-03-01T00:00:inf",
+            "2024-03-01T99999999999999:00:00Z",
+        ] {
+            assert_eq!(timestamp(line), None, "{line}");
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use super::{InputLine, LineSender, runtime};
+use crate::Counters;
+use futures_util::StreamExt;
+use serde_json::Value;
+use std::{
+    io,
+    sync::{Arc, atomic::Ordering},
+    time::Duration,
+};
+use tokio::{net::TcpStream, sync::mpsc, time::sleep};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungstenite::Message};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// stream the text frames of a WebSocket endpoint, reconnecting when it goes away. with
+/// a field, the frames holding a JSON object display the value under that key instead
+pub fn spawn_ws(
+    url: &str,
+    field: Option<&str>,
+    tx: LineSender,
+    counters: Arc<Counters>,
+) -> io::Result<()> {
+    let (url, field) = (url.to_string(), field.map(str::to_string));
+    // fail early on a wrong url rather than retrying forever
+    let first = url.clone();
+    let socket = runtime::wait(async move { open(&first).await })??;
+    let source = host(&url).to_string();
+    let tx = runtime::forward(tx);
+    runtime::spawn_task(async move {
+        let mut socket = Some(socket);
+        loop {
+            let connected = match socket.take() {
+                Some(socket) => Ok(socket),
+                None => open(&url).await,
+            };
+            if let Ok(mut socket) = connected
+                && !receive(&mut socket, &source, field.as_deref(), &tx, &counters).await
+            {
+                return;
+            }
+            sleep(RECONNECT_DELAY).await;
+        }
+    })
+}
+
+async fn open(url: &str) -> io::Result<Socket> {
+    connect_async(url)
+        .await
+        .map(|(socket, _response)| socket)
+        .map_err(|err| io::Error::other(format!("ws {url}: {err}")))
+}
+
+// false once the matrix is gone, the pings are answered while reading
+async fn receive(
+    socket: &mut Socket,
+    source: &str,
+    field: Option<&str>,
+    tx: &mpsc::Sender<InputLine>,
+    counters: &Counters,
+) -> bool {
+    while let Some(message) = socket.next().await {
+        match message {
+            Ok(Message::Text(frame)) => {
+                let text = extract(frame.as_str(), field);
+                for line in text.lines() {
+                    if tx
+                        .send(InputLine::new(source, line.to_string()))
+                        .await
+                        .is_err()
+                    {
+                        return false;
+                    }
+                }
+            }
+            Ok(Message::Binary(_)) => {
+                counters.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(_) => {}
+            Err(_) => return true,
+        }
+    }
+    true
+}
+
+fn extract(frame: &str, field: Option<&str>) -> String {
+    let value = field.and_then(|field| {
+        let message: Value = serde_json::from_str(frame).ok()?;
+        match message.get(field)? {
+            Value::String(text) => Some(text.clone()),
+            value => Some(value.to_string()),
+        }
+    });
+    value.unwrap_or_else(|| frame.to_string())
+}
+
+// `wss://host:port/path` is named `host:port`
+fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split('/').next().unwrap_or(rest)
+}
@@ -0,0 +1,278 @@
+use crate::{
+    Args, RUNNING, SshArgs,
+    commands::or_exit,
+    renderer::Remote,
+    serve::{self, Clients, Crlf, MAX_CLIENTS, Viewer, viewer_args},
+};
+use russh::{
+    Channel, ChannelId, CryptoVec,
+    keys::{
+        PrivateKey,
+        ssh_key::{LineEnding, private::Ed25519Keypair},
+    },
+    server::{Auth, Config, Handle, Handler, Msg, Server, Session},
+};
+use std::{
+    collections::HashMap,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex, atomic::Ordering},
+    thread::{JoinHandle, sleep, spawn},
+    time::Duration,
+};
+use tokio::runtime::{self, Runtime};
+
+const SHUTDOWN_PERIOD: Duration = Duration::from_millis(100);
+
+/// `ssh USER@HOST -p PORT` shows the animation at the size of the client's window, without
+/// a password nor a shell account
+pub fn ssh(opts: Box<SshArgs>) {
+    let SshArgs {
+        port,
+        bind,
+        user,
+        host_key,
+        args,
+    } = *opts;
+    let key = match &host_key {
+        Some(path) => or_exit(host_key_at(path), "could not read the host key"),
+        // a new key at every start, the clients will complain of it
+        None => PrivateKey::from(Ed25519Keypair::from_seed(&rand::random())),
+    };
+    let config = Arc::new(Config {
+        keys: vec![key],
+        auth_rejection_time: Duration::from_secs(1),
+        auth_rejection_time_initial: Some(Duration::ZERO),
+        ..Default::default()
+    });
+    let runtime = or_exit(Runtime::new(), "could not start the ssh server");
+    let listener = or_exit(
+        runtime.block_on(tokio::net::TcpListener::bind(format!("{bind}:{port}"))),
+        &format!("could not listen on {bind}:{port}"),
+    );
+    let clients = Clients::spawn(&args);
+    ctrlc::set_handler(|| RUNNING.store(false, Ordering::SeqCst))
+        .expect("Error setting Ctrl-C handler");
+
+    let renders = Arc::new(Mutex::new(vec![]));
+    let mut server = Wall {
+        user,
+        args: viewer_args(args),
+        clients,
+        renders: renders.clone(),
+    };
+    let running = server.run_on_socket(config, &listener);
+    let shutdown = running.handle();
+    spawn(move || {
+        while RUNNING.load(Ordering::SeqCst) {
+            sleep(SHUTDOWN_PERIOD);
+        }
+        shutdown.shutdown("logmatrix is stopping".to_string());
+    });
+    let _ = runtime.block_on(running);
+    let renders: Vec<JoinHandle<()>> = renders.lock().unwrap().drain(..).collect();
+    for render in renders {
+        let _ = render.join();
+    }
+}
+
+// created on the first start for the clients to know the server afterwards
+fn host_key_at(path: &Path) -> io::Result<PrivateKey> {
+    if path.exists() {
+        return russh::keys::load_secret_key(path, None).map_err(io::Error::other);
+    }
+    let key = PrivateKey::from(Ed25519Keypair::from_seed(&rand::random()));
+    key.write_openssh_file(path, LineEnding::LF)
+        .map_err(io::Error::other)?;
+    Ok(key)
+}
+
+struct Wall {
+    user: String,
+    args: Args,
+    clients: Clients,
+    renders: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl Server for Wall {
+    type Handler = Connection;
+
+    fn new_client(&mut self, _peer: Option<std::net::SocketAddr>) -> Connection {
+        Connection {
+            user: self.user.clone(),
+            args: self.args.clone(),
+            clients: self.clients.clone(),
+            renders: self.renders.clone(),
+            viewers: HashMap::new(),
+        }
+    }
+}
+
+/// the sessions of an ssh connection, each one with its own matrix
+struct Connection {
+    user: String,
+    args: Args,
+    clients: Clients,
+    renders: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    viewers: HashMap<ChannelId, Arc<Viewer>>,
+}
+
+impl Connection {
+    fn viewer(&mut self, channel: ChannelId) -> Arc<Viewer> {
+        self.viewers
+            .entry(channel)
+            .or_insert_with(|| Arc::new(Viewer::new()))
+            .clone()
+    }
+
+    // a size that doesn't fit in a u16 is ignored rather than truncated, the others are
+    // capped by the viewer
+    fn resize(&mut self, channel: ChannelId, width: u32, height: u32) {
+        if let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) {
+            self.viewer(channel).resize(width, height);
+        }
+    }
+
+    fn leave(&mut self, channel: ChannelId) {
+        if let Some(viewer) = self.viewers.remove(&channel) {
+            viewer.leave();
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        for viewer in self.viewers.values() {
+            viewer.leave();
+        }
+    }
+}
+
+impl Handler for Connection {
+    type Error = russh::Error;
+
+    async fn auth_none(&mut self, user: &str) -> Result<Auth, Self::Error> {
+        Ok(match user == self.user {
+            true => Auth::Accept,
+            false => Auth::reject(),
+        })
+    }
+
+    async fn channel_open_session(
+        &mut self,
+        channel: Channel<Msg>,
+        _session: &mut Session,
+    ) -> Result<bool, Self::Error> {
+        self.viewer(channel.id());
+        Ok(true)
+    }
+
+    async fn pty_request(
+        &mut self,
+        channel: ChannelId,
+        _term: &str,
+        col_width: u32,
+        row_height: u32,
+        _pix_width: u32,
+        _pix_height: u32,
+        _modes: &[(russh::Pty, u32)],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.resize(channel, col_width, row_height);
+        session.channel_success(channel)
+    }
+
+    async fn window_change_request(
+        &mut self,
+        channel: ChannelId,
+        col_width: u32,
+        row_height: u32,
+        _pix_width: u32,
+        _pix_height: u32,
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.resize(channel, col_width, row_height);
+        Ok(())
+    }
+
+    async fn shell_request(
+        &mut self,
+        channel: ChannelId,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        let viewer = self.viewer(channel);
+        let mut renders = self.renders.lock().unwrap();
+        renders.retain(|render| !render.is_finished());
+        if renders.len() >= MAX_CLIENTS {
+            return session.channel_failure(channel);
+        }
+        let out = ChannelWriter {
+            handle: session.handle(),
+            channel,
+            runtime: runtime::Handle::current(),
+        };
+        let (args, feed) = (self.args.clone(), self.clients.subscribe());
+        renders.push(spawn(move || {
+            let (handle, runtime) = (out.handle.clone(), out.runtime.clone());
+            let out = Box::new(Remote::new(BufWriter::new(Crlf(out)), viewer));
+            serve::show(args, feed, out);
+            runtime.block_on(async {
+                let _ = handle.exit_status_request(channel, 0).await;
+                let _ = handle.eof(channel).await;
+                let _ = handle.close(channel).await;
+            });
+        }));
+        session.channel_success(channel)
+    }
+
+    // Ctrl-C, Ctrl-D and q
+    async fn data(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        if data.iter().any(|key| matches!(key, 3 | 4 | b'q')) {
+            self.leave(channel);
+        }
+        Ok(())
+    }
+
+    async fn channel_eof(
+        &mut self,
+        channel: ChannelId,
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.leave(channel);
+        Ok(())
+    }
+
+    async fn channel_close(
+        &mut self,
+        channel: ChannelId,
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.leave(channel);
+        Ok(())
+    }
+}
+
+// the frames are drawn from a thread of their own, outside of the runtime
+struct ChannelWriter {
+    handle: Handle,
+    channel: ChannelId,
+    runtime: runtime::Handle,
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.runtime
+            .block_on(self.handle.data(self.channel, CryptoVec::from_slice(buf)))
+            .map_err(|_| io::ErrorKind::BrokenPipe)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
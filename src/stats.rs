@@ -0,0 +1,156 @@
+use crate::sources::{InputLine, Severity};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+// a source stays active for this long after its last line
+const ACTIVE_PERIOD: Duration = Duration::from_secs(60);
+
+const SEVERITIES: [Severity; 6] = [
+    Severity::Critical,
+    Severity::Error,
+    Severity::Warning,
+    Severity::Notice,
+    Severity::Info,
+    Severity::Debug,
+];
+
+/// the figures of the statistics overlay
+pub struct Stats {
+    received: u64,
+    severities: [u64; SEVERITIES.len()],
+    unknown: u64,                      // lines without a severity, most inputs give none
+    since: Instant,                    // start of the current period of the rate
+    counted: u64,                      // lines received since then
+    rate: f64,                         // lines per second over the last period
+    backlog: usize,                    // lines waiting in the columns
+    sources: HashMap<String, Instant>, // with their last line
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats {
+            received: 0,
+            severities: [0; SEVERITIES.len()],
+            unknown: 0,
+            since: Instant::now(),
+            counted: 0,
+            rate: 0.,
+            backlog: 0,
+            sources: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, line: &InputLine) {
+        self.roll();
+        self.received += 1;
+        self.counted += 1;
+        match line.severity {
+            Some(severity) => {
+                let index = SEVERITIES.iter().position(|known| *known == severity);
+                self.severities[index.expect("every severity is listed")] += 1;
+            }
+            None => self.unknown += 1,
+        }
+        match self.sources.get_mut(&line.source) {
+            Some(last) => *last = line.received,
+            None => {
+                self.sources.insert(line.source.clone(), line.received);
+            }
+        }
+    }
+
+    /// the sources which sent a line lately, by name
+    pub fn active_sources(&self) -> Vec<&str> {
+        let mut sources: Vec<&str> = self
+            .sources
+            .iter()
+            .filter(|(_, last)| last.elapsed() < ACTIVE_PERIOD)
+            .map(|(source, _)| source.as_str())
+            .collect();
+        sources.sort_unstable();
+        sources
+    }
+
+    pub fn backlog(&self) -> usize {
+        self.backlog
+    }
+
+    /// updated with every tick of the columns
+    pub fn set_backlog(&mut self, lines: usize) {
+        self.backlog = lines;
+    }
+
+    // the rate is measured over periods of about a second, the sources gone quiet are
+    // forgotten along the way
+    fn roll(&mut self) {
+        let elapsed = self.since.elapsed();
+        if elapsed < Duration::from_secs(1) {
+            return;
+        }
+        self.rate = self.counted as f64 / elapsed.as_secs_f64();
+        self.since = Instant::now();
+        self.counted = 0;
+        self.sources
+            .retain(|_, last| last.elapsed() < ACTIVE_PERIOD);
+    }
+
+    /// one line per figure, the severities never seen are left out and so are the lines
+    /// without one when none has one. the lines dropped by a full queue are only told with
+    /// --queue-size, the ones missing from the sequence numbers with --seq-field
+    pub fn lines(
+        &mut self,
+        dropped: u64,
+        queue_dropped: Option<u64>,
+        missing: Option<u64>,
+        missed: u64,
+    ) -> Vec<String> {
+        self.roll();
+        let mut lines = vec![
+            format!("lines/s {:.1}", self.rate),
+            format!("total {}", self.received),
+        ];
+        for (severity, count) in SEVERITIES.iter().zip(self.severities) {
+            if count > 0 {
+                lines.push(format!(
+                    "{} {count}",
+                    format!("{severity:?}").to_lowercase()
+                ));
+            }
+        }
+        if self.unknown > 0 && self.unknown < self.received {
+            lines.push(format!("unknown {}", self.unknown));
+        }
+        lines.push(format!("backlog {}", self.backlog));
+        lines.push(format!("dropped {dropped}"));
+        if let Some(queue_dropped) = queue_dropped {
+            lines.push(format!("queue full {queue_dropped}"));
+        }
+        if let Some(missing) = missing {
+            lines.push(format!("missing {missing}"));
+        }
+        lines.push(format!("missed frames {missed}"));
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quiet_sources_are_forgotten() {
+        let mut stats = Stats::new();
+        for source in 0..1000 {
+            let mut line = InputLine::new(&format!("conn-{source}"), "gone".to_string());
+            line.received = Instant::now() - ACTIVE_PERIOD;
+            stats.record(&line);
+        }
+        stats.record(&InputLine::new("app", "up".to_string()));
+        stats.since = Instant::now() - Duration::from_secs(1);
+        stats.lines(0, None, None, 0);
+        assert_eq!(stats.sources.len(), 1);
+        assert_eq!(stats.active_sources(), ["app"]);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::control;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+/// terminal mode switched on while the matrix is displayed
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TermMode {
+    AltScreen,
+    HiddenCursor,
+    Mouse,
+}
+
+const ALL_MODES: [TermMode; 3] = [TermMode::AltScreen, TermMode::HiddenCursor, TermMode::Mouse];
+
+impl TermMode {
+    fn name(self) -> &'static str {
+        match self {
+            TermMode::AltScreen => "alt-screen",
+            TermMode::HiddenCursor => "hidden-cursor",
+            TermMode::Mouse => "mouse",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<TermMode> {
+        ALL_MODES.into_iter().find(|mode| mode.name() == name)
+    }
+
+    pub fn enter_sequence(self) -> &'static str {
+        match self {
+            TermMode::AltScreen => "\x1b[?1049h",
+            TermMode::HiddenCursor => "\x1b[?25l",
+            // the clicks, in the SGR encoding which is not limited to 223 columns
+            TermMode::Mouse => "\x1b[?1000h\x1b[?1006h",
+        }
+    }
+
+    pub fn exit_sequence(self) -> &'static str {
+        match self {
+            TermMode::AltScreen => "\x1b[?1049l",
+            TermMode::HiddenCursor => "\x1b[?25h",
+            TermMode::Mouse => "\x1b[?1006l\x1b[?1000l",
+        }
+    }
+}
+
+/// the terminal shows nothing written between the two sequences until the second one, a
+/// frame appears whole (synchronized update, DEC mode 2026). the terminals which do not know
+/// the mode ignore it
+pub const BEGIN_UPDATE: &str = "\x1b[?2026h";
+pub const END_UPDATE: &str = "\x1b[?2026l";
+
+/// file listing the modes currently switched on, survives a killed instance
+pub fn state_file() -> PathBuf {
+    control::runtime_path("term")
+}
+
+// the name of the file in the temporary directory can be guessed, a link put there by
+// someone else is never followed
+fn open_state(path: &Path, options: &mut OpenOptions) -> io::Result<File> {
+    options.custom_flags(libc::O_NOFOLLOW).open(path)
+}
+
+fn write_state(path: &Path, state: &str) -> io::Result<()> {
+    // left by a killed instance
+    let _ = fs::remove_file(path);
+    let mut file = open_state(
+        path,
+        OpenOptions::new().write(true).create_new(true).mode(0o600),
+    )?;
+    file.write_all(state.as_bytes())
+}
+
+fn read_state(path: &Path) -> io::Result<String> {
+    let mut state = String::new();
+    open_state(path, OpenOptions::new().read(true))?.read_to_string(&mut state)?;
+    Ok(state)
+}
+
+/// switch the modes on and remember them in the state file
+pub fn enter(modes: &[TermMode]) {
+    for mode in modes {
+        print!("{}", mode.enter_sequence());
+    }
+    let names: Vec<&str> = modes.iter().map(|mode| mode.name()).collect();
+    let _ = write_state(&state_file(), &names.join("\n"));
+}
+
+/// switch the modes off in reverse order and forget the state file
+pub fn leave(modes: &[TermMode]) {
+    for mode in modes.iter().rev() {
+        print!("{}", mode.exit_sequence());
+    }
+    let _ = io::stdout().flush();
+    let _ = fs::remove_file(state_file());
+}
+
+/// restore a terminal left broken by a killed instance, every known mode is
+/// switched off when no state file was left behind
+pub fn reset() -> io::Result<Vec<&'static str>> {
+    let recorded: Vec<TermMode> = read_state(&state_file())
+        .map(|state| state.lines().filter_map(TermMode::from_name).collect())
+        .unwrap_or_else(|_| ALL_MODES.to_vec());
+
+    let mut stdout = io::stdout();
+    // SGR attributes, scroll region, origin mode, reverse video and a synchronized update
+    // are never recorded, reset them anyway
+    write!(stdout, "{END_UPDATE}\x1b[0m\x1b[?6l\x1b[r\x1b[?5l")?;
+    for mode in recorded.iter().rev() {
+        write!(stdout, "{}", mode.exit_sequence())?;
+    }
+    stdout.flush()?;
+    let _ = fs::remove_file(state_file());
+    Ok(recorded.into_iter().map(TermMode::name).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempPath;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn state_file_is_private_and_never_followed() {
+        let dir = TempPath::dir("term");
+        let path = dir.join("logmatrix.term");
+        let target = dir.join("target");
+        fs::write(&target, "kept").unwrap();
+        std::os::unix::fs::symlink(&target, &path).unwrap();
+
+        write_state(&path, "mouse").unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "kept");
+        assert_eq!(read_state(&path).unwrap(), "mouse");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        fs::remove_file(&path).unwrap();
+        std::os::unix::fs::symlink(&target, &path).unwrap();
+        assert!(read_state(&path).is_err());
+    }
+}
@@ -0,0 +1,69 @@
+use std::{
+    env, fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// the tests of a run are numbered so they never share a path
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+/// a path of the temporary directory for a single test, whatever was created there is
+/// removed once the test is done with it
+pub struct TempPath(PathBuf);
+
+impl TempPath {
+    pub fn new(name: &str) -> TempPath {
+        let test = NEXT.fetch_add(1, Ordering::Relaxed);
+        let name = format!("logmatrix-{}-{test}-{name}", process::id());
+        TempPath(env::temp_dir().join(name))
+    }
+
+    #[cfg(feature = "lua")]
+    /// a file holding `contents`, the scripts of the tests
+    pub fn with(name: &str, contents: &str) -> TempPath {
+        let path = TempPath::new(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// an empty directory, for the tests creating several files
+    pub fn dir(name: &str) -> TempPath {
+        let path = TempPath::new(name);
+        fs::create_dir(&path).unwrap();
+        path
+    }
+
+    pub fn read(&self) -> String {
+        fs::read_to_string(self).unwrap()
+    }
+
+    /// the path as the value of an option
+    pub fn arg(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Deref for TempPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = match self.0.is_dir() {
+            true => fs::remove_dir_all(&self.0),
+            false => fs::remove_file(&self.0),
+        };
+    }
+}
@@ -0,0 +1,466 @@
+use clap::ValueEnum;
+use regex::{Match, Regex};
+use std::{borrow::Cow, iter::repeat_n, net::IpAddr};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// expand tabs to the next tab stop and replace the other control characters,
+/// every char left in the line takes exactly one cell
+pub fn sanitize(line: &str, tab_width: usize, placeholder: char) -> String {
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    let mut out = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let stop = if tab_width == 0 {
+                0
+            } else {
+                tab_width - column % tab_width
+            };
+            out.extend(repeat_n(' ', stop));
+            column += stop;
+        } else {
+            out.push(if c.is_control() { placeholder } else { c });
+            column += 1;
+        }
+    }
+    out
+}
+
+const OSC_NOTIFY: &str = "\x1b]777;notify;";
+
+/// notification sent by a tool through its output
+#[derive(Clone)]
+pub enum Notification {
+    Bell,
+    Desktop { title: String, body: String }, // OSC 777
+}
+
+impl Notification {
+    pub fn to_ansi(&self) -> String {
+        match self {
+            Notification::Bell => "\x07".to_string(),
+            Notification::Desktop { title, body } => format!("{OSC_NOTIFY}{title};{body}\x07"),
+        }
+    }
+}
+
+/// take the BEL characters and the OSC 777 `notify` sequences, ended by BEL or ST, out of
+/// the line
+pub fn take_notifications(line: &str) -> (String, Vec<Notification>) {
+    let (mut out, mut notifications) = (String::with_capacity(line.len()), vec![]);
+    let mut rest = line;
+    while let Some(start) = rest.find(['\x07', '\x1b']) {
+        out.push_str(&rest[..start]);
+        let sequence = &rest[start..];
+        if let Some(after) = sequence.strip_prefix('\x07') {
+            notifications.push(Notification::Bell);
+            rest = after;
+            continue;
+        }
+        let terminated = sequence.strip_prefix(OSC_NOTIFY).and_then(|osc| {
+            let bel = osc.find('\x07').map(|end| (end, 1));
+            let st = osc.find("\x1b\\").map(|end| (end, 2));
+            bel.into_iter()
+                .chain(st)
+                .min()
+                .map(|(end, len)| (&osc[..end], &osc[end + len..]))
+        });
+        match terminated {
+            Some((params, after)) => {
+                // the fields cannot smuggle other sequences to the terminal
+                let clean = |field: &str| field.chars().filter(|c| !c.is_control()).collect();
+                let (title, body) = params.split_once(';').unwrap_or((params, ""));
+                notifications.push(Notification::Desktop {
+                    title: clean(title),
+                    body: clean(body),
+                });
+                rest = after;
+            }
+            // any other escape is left to `sanitize`
+            None => {
+                out.push('\x1b');
+                rest = &sequence[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    (out, notifications)
+}
+
+/// longest prefix of `text` fitting in `width` terminal cells, centered with spaces
+pub fn center(text: &str, width: usize) -> String {
+    let mut used = 0;
+    let mut fitted = String::new();
+    for c in text.chars() {
+        let cells = c.width().unwrap_or(0);
+        if used + cells > width {
+            break;
+        }
+        used += cells;
+        fitted.push(c);
+    }
+    let left = (width - used) / 2;
+    format!(
+        "{}{fitted}{}",
+        " ".repeat(left),
+        " ".repeat(width - used - left)
+    )
+}
+
+/// per grapheme substitution applied when a cell is filled
+pub type GlyphTransform = fn(&str) -> Cow<'_, str>;
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[clap(rename_all = "kebab_case")]
+pub enum Glyphs {
+    /// the text as received
+    Plain,
+    /// half-width katakana and mirrored digits like the film, the highlight keeps the real
+    /// text
+    Matrix,
+}
+
+impl Glyphs {
+    pub fn transform(self) -> GlyphTransform {
+        match self {
+            Glyphs::Plain => plain_glyph,
+            Glyphs::Matrix => matrix_glyph,
+        }
+    }
+}
+
+fn plain_glyph(grapheme: &str) -> Cow<'_, str> {
+    Cow::Borrowed(grapheme)
+}
+
+// Unicode has no mirrored digits, the letters closest to them stand in. 0, 1 and 8 look
+// the same either way
+const MIRRORED_DIGITS: [char; 10] = ['0', '1', 'Ƨ', 'Ɛ', 'ᔪ', 'Ƹ', 'ᑯ', 'ᒥ', '8', 'ᑭ'];
+
+// the same character always lands on the same katakana
+fn matrix_glyph(grapheme: &str) -> Cow<'_, str> {
+    match grapheme.chars().next() {
+        Some(' ') => Cow::Borrowed(grapheme),
+        Some(c @ '0'..='9') => Cow::Owned(MIRRORED_DIGITS[c as usize - '0' as usize].to_string()),
+        Some(c) => {
+            // U+FF66 to U+FF9D, half-width katakana from WO to N
+            let katakana = char::from_u32(0xff66 + (c as u32 * 7) % 56).unwrap_or(c);
+            Cow::Owned(katakana.to_string())
+        }
+        None => Cow::Borrowed(grapheme),
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[clap(rename_all = "kebab_case")]
+pub enum Redaction {
+    Email,
+    Ip,
+    Bearer,
+}
+
+impl Redaction {
+    fn pattern(self) -> &'static str {
+        match self {
+            Redaction::Email => r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+            // the runs an address can hide in, `mask_ip` finds it
+            Redaction::Ip => r"[0-9A-Fa-f:.]{2,}",
+            Redaction::Bearer => r"(?i)bearer\s+[A-Za-z0-9\-._~+/]+=*",
+        }
+    }
+}
+
+// the longest address text can hold, an IPv6 ending with an IPv4
+const MAX_IP: usize = 45;
+
+// the address in a run of hex digits, colons and dots masked. the run is no address when
+// it is glued to a word, like `std::io`, and times like 12:30:45 parse as none
+fn mask_ip(line: &str, run: Match) -> String {
+    let text = run.as_str();
+    let word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let first = (!word(line[..run.start()].chars().next_back())).then_some(0);
+    let starts = first
+        .into_iter()
+        .chain(text.match_indices(':').map(|(index, _)| index + 1));
+    // a port or the dot ending a sentence is no part of it
+    let last = (!word(line[run.end()..].chars().next())).then_some(text.len());
+    let ends: Vec<usize> = text
+        .match_indices(':')
+        .map(|(index, _)| index)
+        .chain(text.strip_suffix('.').map(str::len))
+        .chain(last)
+        .collect();
+    for start in starts {
+        let longest = ends
+            .iter()
+            .rev()
+            .filter(|end| (start + 1..=start + MAX_IP).contains(*end))
+            .find(|end| text[start..**end].parse::<IpAddr>().is_ok());
+        if let Some(&end) = longest {
+            return format!(
+                "{}{}{}",
+                &text[..start],
+                "*".repeat(end - start),
+                &text[end..]
+            );
+        }
+    }
+    text.to_string()
+}
+
+/// masks sensitive text with `*` before it reaches the screen
+#[derive(Clone)]
+pub struct Redactor {
+    patterns: Vec<Regex>,
+    ips: Option<Regex>, // checked to be addresses before they are masked
+}
+
+impl Redactor {
+    pub fn new(custom: &[Regex], builtins: &[Redaction]) -> Redactor {
+        let mut patterns = custom.to_vec();
+        let mut ips = None;
+        for builtin in builtins {
+            let pattern = Regex::new(builtin.pattern()).expect("invalid built-in pattern");
+            match builtin {
+                Redaction::Ip => ips = Some(pattern),
+                _ => patterns.push(pattern),
+            }
+        }
+        Redactor { patterns, ips }
+    }
+
+    pub fn redact(&self, line: String) -> String {
+        let line = self.patterns.iter().fold(line, |line, pattern| {
+            pattern
+                .replace_all(&line, |caps: &regex::Captures| {
+                    "*".repeat(caps[0].chars().count())
+                })
+                .into_owned()
+        });
+        match &self.ips {
+            Some(ips) => ips
+                .replace_all(&line, |caps: &regex::Captures| {
+                    mask_ip(&line, caps.get(0).expect("the whole match"))
+                })
+                .into_owned(),
+            None => line,
+        }
+    }
+}
+
+/// keep at most `max` grapheme clusters, the ellipsis included. an ellipsis longer than
+/// `max` is cut too
+pub fn truncate(line: String, max: usize, ellipsis: &str) -> String {
+    if line.graphemes(true).count() <= max {
+        return line;
+    }
+    let kept = max.saturating_sub(ellipsis.graphemes(true).count());
+    line.graphemes(true)
+        .take(kept)
+        .chain(ellipsis.graphemes(true))
+        .take(max)
+        .collect()
+}
+
+/// keep at most `width` terminal cells, the ellipsis included, for the double width
+/// characters not to wrap the row
+pub fn truncate_width(line: String, width: usize, ellipsis: &str) -> String {
+    if line.width() <= width {
+        return line;
+    }
+    let room = width.saturating_sub(ellipsis.width());
+    let mut used = 0;
+    let mut truncated: String = line
+        .graphemes(true)
+        .take_while(|grapheme| {
+            used += grapheme.width();
+            used <= room
+        })
+        .collect();
+    if ellipsis.width() <= width {
+        truncated.push_str(ellipsis);
+    }
+    truncated
+}
+
+/// chunks of at most `max` grapheme clusters
+pub fn split(line: &str, max: usize) -> Vec<String> {
+    let graphemes: Vec<&str> = line.graphemes(true).collect();
+    graphemes
+        .chunks(max.max(1))
+        .map(|chunk| chunk.concat())
+        .collect()
+}
+
+/// value of the first `key=value` token of the line, quotes trimmed
+pub fn field<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    line.split_whitespace().find_map(|token| {
+        token
+            .split_once('=')
+            .filter(|(name, _)| *name == key)
+            .map(|(_, value)| value.trim_matches('"'))
+    })
+}
+
+/// the fields of a JSON object line, the nested values as JSON, or else its `key=value`
+/// tokens, quotes trimmed
+pub fn fields(line: &str) -> Vec<(String, String)> {
+    if let Ok(serde_json::Value::Object(object)) = serde_json::from_str(line) {
+        return object
+            .into_iter()
+            .map(|(key, value)| match value {
+                serde_json::Value::String(value) => (key, value),
+                value => (key, value.to_string()),
+            })
+            .collect();
+    }
+    line.split_whitespace()
+        .filter_map(|token| token.split_once('='))
+        .filter(|(key, _)| !key.is_empty())
+        .map(|(key, value)| (key.to_string(), value.trim_matches('"').to_string()))
+        .collect()
+}
+
+/// standard base64 with padding, for the escape sequences carrying data
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+            group | u32::from(*byte) << (16 - 8 * index)
+        });
+        for index in 0..4 {
+            match index <= chunk.len() {
+                true => encoded.push(ALPHABET[(group >> (18 - 6 * index) & 63) as usize] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+    encoded
+}
+
+/// glyph of the ramp for a freshness between 0 and 1, the last one is the freshest
+pub fn shade(ramp: &str, freshness: f32) -> char {
+    let glyphs: Vec<char> = ramp.chars().collect();
+    let staleness = ((1.0 - freshness.clamp(0.0, 1.0)) * glyphs.len() as f32) as usize;
+    glyphs[glyphs.len() - 1 - staleness.min(glyphs.len() - 1)]
+}
+
+/// the line with every number replaced by `0`, lines only differing by their numbers
+/// share it
+pub fn template(line: &str) -> String {
+    let mut template = String::with_capacity(line.len());
+    for c in line.chars() {
+        if !c.is_ascii_digit() {
+            template.push(c);
+        } else if !template.ends_with('0') {
+            template.push('0');
+        }
+    }
+    template
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unicode_width::UnicodeWidthStr;
+
+    #[test]
+    fn matrix_glyphs_take_a_cell() {
+        for c in (' '..='~').chain(['é', '→']) {
+            let glyph = matrix_glyph(&c.to_string()).into_owned();
+            assert_eq!(glyph.chars().count(), 1);
+            assert_eq!(glyph.width(), 1, "{c} became {glyph}");
+        }
+        assert_eq!(matrix_glyph("2"), "Ƨ");
+        assert_eq!(matrix_glyph("a"), matrix_glyph("a"));
+    }
+
+    #[test]
+    fn ips_are_redacted() {
+        let redactor = Redactor::new(&[], &[Redaction::Ip]);
+        for (line, redacted) in [
+            ("from 10.0.0.1 port 22", "from ******** port 22"),
+            ("to 10.0.0.1:8080.", "to ********:8080."),
+            ("at 10.0.0.1.", "at ********."),
+            ("[fe80::1%eth0]:22", "[*******%eth0]:22"),
+            ("ip=2001:db8::8a2e:370:7334", "ip=***********************"),
+            ("addr:::1 up", "addr:*** up"),
+            ("v6 ::ffff:192.0.2.1", "v6 ****************"),
+        ] {
+            assert_eq!(redactor.redact(line.to_string()), redacted);
+        }
+    }
+
+    #[test]
+    fn times_are_no_ips() {
+        let redactor = Redactor::new(&[], &[Redaction::Ip]);
+        for line in [
+            "12:30:45 started",
+            "2024-03-01T12:30:45.123Z",
+            "Dec 12 10:15:30.5 up",
+            "use std::io::Error",
+            "aa:bb:cc:dd:ee:ff",
+            "version 1.2.3.4.5",
+            "999.1.1.1",
+            "sha 9f86d081884c7d659a2feaa0c55ad015",
+        ] {
+            assert_eq!(redactor.redact(line.to_string()), line);
+        }
+    }
+
+    #[test]
+    fn split_keeps_graphemes_whole() {
+        assert_eq!(split("abcde", 2), ["ab", "cd", "e"]);
+        assert_eq!(split("e\u{301}e\u{301}e", 2), ["e\u{301}e\u{301}", "e"]);
+        assert_eq!(split("abc", 0), ["a", "b", "c"]);
+        assert!(split("", 3).is_empty());
+    }
+
+    #[test]
+    fn truncate_width_counts_the_cells() {
+        assert_eq!(
+            truncate_width("日本語です".to_string(), 10, "…"),
+            "日本語です"
+        );
+        assert_eq!(truncate_width("日本語です".to_string(), 6, "…"), "日本…");
+        assert_eq!(truncate_width("a日本".to_string(), 4, "…"), "a日…");
+        assert_eq!(truncate_width("a日本".to_string(), 3, "…"), "a…");
+        assert_eq!(truncate_width("🚀🚀🚀".to_string(), 5, "…"), "🚀🚀…");
+        assert_eq!(truncate_width("abc".to_string(), 0, "…"), "");
+    }
+
+    #[test]
+    fn truncate_counts_the_ellipsis() {
+        assert_eq!(truncate("abcdef".to_string(), 6, "…"), "abcdef");
+        assert_eq!(truncate("abcdef".to_string(), 4, "…"), "abc…");
+        assert_eq!(truncate("abcdef".to_string(), 4, "..."), "a...");
+        assert_eq!(truncate("abcdef".to_string(), 1, "..."), ".");
+        assert_eq!(truncate("abcdef".to_string(), 0, "…"), "");
+        assert_eq!(truncate("🇫🇷🇫🇷🇫🇷".to_string(), 2, "…"), "🇫🇷…");
+    }
+
+    #[test]
+    fn template_merges_numbers() {
+        assert_eq!(template("took 125ms on 10.0.0.1"), "took 0ms on 0.0.0.0");
+        assert_eq!(template("retry 3 of 5"), template("retry 4 of 5"));
+        assert_eq!(template("no digits"), "no digits");
+    }
+
+    #[test]
+    fn base64_of_the_rfc() {
+        for (raw, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64(raw.as_bytes()), encoded);
+        }
+        assert_eq!(base64(&[0xfb, 0xff]), "+/8=");
+    }
+}
@@ -0,0 +1,50 @@
+use crate::Color;
+use std::io::Write;
+use unicode_segmentation::UnicodeSegmentation;
+
+// between the end of a scrolling line and its start coming back
+const GAP: &str = "   ";
+
+/// the last error line received, in full on a row of its own. the lines longer than the
+/// row scroll a character per frame
+#[derive(Default)]
+pub struct Ticker {
+    line: Vec<String>, // the grapheme clusters of the line shown
+    frames: usize,     // it was drawn in
+}
+
+impl Ticker {
+    /// the critical and error lines and the ones matching the pattern replace the one shown,
+    /// redacted and sanitized
+    pub fn show(&mut self, text: &str) {
+        self.line = text.graphemes(true).map(String::from).collect();
+        self.frames = 0;
+    }
+
+    pub fn draw(&mut self, out: &mut dyn Write, row: u16, width: usize) {
+        let shown: String = match self.line.len() <= width {
+            true => self.line.concat(),
+            false => {
+                let gap = GAP.graphemes(true);
+                let cycle: Vec<&str> = self.line.iter().map(String::as_str).chain(gap).collect();
+                let start = self.frames % cycle.len();
+                cycle
+                    .iter()
+                    .cycle()
+                    .skip(start)
+                    .take(width)
+                    .copied()
+                    .collect()
+            }
+        };
+        self.frames += 1;
+        let padding = width.saturating_sub(shown.graphemes(true).count());
+        let _ = write!(
+            out,
+            "\x1b[{row};1H{}{shown}{}{}",
+            Color::Red.to_ansi(),
+            " ".repeat(padding),
+            Color::Default.to_ansi()
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{
+    Args,
+    control::{ControlClient, ControlMessage},
+};
+use std::{
+    env,
+    io::{self, BufRead},
+    path::Path,
+    process::{self, Command},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+// set for the instance started inside tmux so it does not launch itself again
+const CHILD_MARKER: &str = "LOGMATRIX_TMUX_CHILD";
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn wants_launch(opt: &Args) -> bool {
+    (opt.tmux_popup || opt.tmux_pane.is_some()) && env::var_os(CHILD_MARKER).is_none()
+}
+
+/// start an instance in a tmux popup or pane and forward stdin to its control socket,
+/// the instance is told to quit once stdin is closed or on Ctrl-C
+pub fn launch(opt: &Args) -> io::Result<()> {
+    if opt.tmux_popup && env::var_os("TMUX").is_none() {
+        return Err(io::Error::other(
+            "--tmux-popup needs to run inside a tmux session",
+        ));
+    }
+    let socket = opt
+        .control_socket
+        .clone()
+        .unwrap_or_else(|| env::temp_dir().join(format!("logmatrix-tmux-{}.sock", process::id())));
+
+    let mut command = vec![
+        format!("{CHILD_MARKER}=1"),
+        quote(&env::current_exe()?.to_string_lossy()),
+    ];
+    command.extend(env::args().skip(1).map(|arg| quote(&arg)));
+    if opt.control_socket.is_none() {
+        command.push("--control-socket".to_string());
+        command.push(quote(&socket.to_string_lossy()));
+    }
+    if !opt.no_stdin {
+        command.push("--no-stdin".to_string());
+    }
+    let command = command.join(" ");
+
+    let mut tmux = Command::new("tmux");
+    match &opt.tmux_pane {
+        Some(target) => tmux.args(["respawn-pane", "-k", "-t", target, &command]),
+        None => tmux.args([
+            "display-popup",
+            "-E",
+            "-w",
+            &opt.tmux_size,
+            "-h",
+            &opt.tmux_size,
+            &command,
+        ]),
+    };
+    let mut child = tmux.spawn()?;
+
+    let mut client = wait_for_instance(&socket)?;
+    let quit_socket = socket.clone();
+    ctrlc::set_handler(move || {
+        if let Ok(mut client) = ControlClient::connect(&quit_socket) {
+            let _ = client.send(&ControlMessage::Quit);
+        }
+        process::exit(130);
+    })
+    .map_err(io::Error::other)?;
+
+    for line in io::stdin().lock().lines().map_while(Result::ok) {
+        // the instance is gone once the popup or pane is closed
+        if client.send(&ControlMessage::Line(line)).is_err() {
+            return Ok(());
+        }
+    }
+    let _ = client.send(&ControlMessage::Quit);
+    child.wait()?;
+    Ok(())
+}
+
+fn wait_for_instance(socket: &Path) -> io::Result<ControlClient> {
+    let start = Instant::now();
+    loop {
+        match ControlClient::connect(socket) {
+            Ok(client) => return Ok(client),
+            Err(err) if start.elapsed() > STARTUP_TIMEOUT => {
+                return Err(io::Error::new(
+                    err.kind(),
+                    format!("logmatrix did not start in tmux: {err}"),
+                ));
+            }
+            Err(_) => sleep(Duration::from_millis(50)),
+        }
+    }
+}
+
+// single quoted for the shell tmux runs the command with
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
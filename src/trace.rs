@@ -0,0 +1,39 @@
+use crate::sources::InputLine;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+/// follows the lines matching a pattern through every stage, into a debug log
+pub struct Tracer {
+    log: File,
+    next_id: u64,
+}
+
+impl Tracer {
+    pub fn open(path: &Path) -> io::Result<Tracer> {
+        let log = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Tracer { log, next_id: 0 })
+    }
+
+    /// id given to the line matching the pattern, by the caller
+    pub fn start(&mut self, line: &InputLine, frame: u64) -> u64 {
+        self.next_id += 1;
+        let severity = match line.severity {
+            Some(severity) => format!("{severity:?}"),
+            None => "no".to_string(),
+        };
+        let stage = format!(
+            "received from {} with {severity} severity: {}",
+            line.source, line.text
+        );
+        self.log(self.next_id, frame, &stage);
+        self.next_id
+    }
+
+    pub fn log(&mut self, id: u64, frame: u64, stage: &str) {
+        // a full disk must not stop the matrix
+        let _ = writeln!(self.log, "frame {frame} line #{id} {stage}");
+    }
+}
@@ -0,0 +1,137 @@
+#[cfg(any(feature = "lua", feature = "wasm"))]
+use crate::context;
+#[cfg(feature = "wasm")]
+use crate::plugin;
+#[cfg(feature = "lua")]
+use crate::script::LuaScript;
+use crate::{Args, scoring::Scorer, sources::InputLine, text};
+use regex::Regex;
+use std::io;
+
+/// a step of the chain the incoming lines go through before they are queued, the weights
+/// and the scorer still see them as they were received
+pub trait Transform: Send {
+    /// named in the traces of `--trace-line`
+    fn name(&self) -> &str;
+
+    /// the line handed to the next step, none to drop it
+    fn apply(&mut self, line: InputLine) -> Option<InputLine>;
+
+    /// the scorer of `--scorer script`, sharing the state of the step
+    fn scorer(&self) -> Option<Box<dyn Scorer>> {
+        None
+    }
+}
+
+/// the steps of the options: the plugins and the script have the last word on the lines,
+/// which come to them redacted, sanitized and colored, and their text is sanitized again
+pub fn chain(opt: &Args) -> io::Result<Vec<Box<dyn Transform>>> {
+    let mut chain: Vec<Box<dyn Transform>> = vec![];
+    #[cfg(feature = "wasm")]
+    if let Some(dir) = &opt.plugin_dir {
+        let plugins = plugin::load_dir(dir).map_err(context("could not load the plugins"))?;
+        chain.extend(
+            plugins
+                .into_iter()
+                .map(|plugin| Box::new(plugin) as Box<dyn Transform>),
+        );
+    }
+    #[cfg(feature = "lua")]
+    if let Some(path) = &opt.script {
+        let script = LuaScript::load(path).map_err(context("could not load the script"))?;
+        chain.push(Box::new(script));
+    }
+    // the text the plugins, the script and its errors return can hold any character,
+    // escapes included
+    if !chain.is_empty() {
+        chain.push(Box::new(Sanitize {
+            tab_width: opt.tab_width,
+            placeholder: opt.placeholder,
+        }));
+    }
+    Ok(chain)
+}
+
+/// the tabs expanded and the other control characters replaced by the placeholder
+#[derive(Clone, Copy)]
+pub struct Sanitize {
+    pub tab_width: usize,
+    pub placeholder: char,
+}
+
+impl Transform for Sanitize {
+    fn name(&self) -> &str {
+        "sanitize"
+    }
+
+    fn apply(&mut self, mut line: InputLine) -> Option<InputLine> {
+        line.text = text::sanitize(&line.text, self.tab_width, self.placeholder);
+        Some(line)
+    }
+}
+
+/// the lines matching the pattern are kept, or dropped
+pub struct Filter {
+    pattern: Regex,
+    keep: bool,
+}
+
+impl Filter {
+    pub fn keep(pattern: Regex) -> Filter {
+        Filter {
+            pattern,
+            keep: true,
+        }
+    }
+
+    pub fn exclude(pattern: Regex) -> Filter {
+        Filter {
+            pattern,
+            keep: false,
+        }
+    }
+}
+
+impl Transform for Filter {
+    fn name(&self) -> &str {
+        "filter"
+    }
+
+    fn apply(&mut self, line: InputLine) -> Option<InputLine> {
+        (self.pattern.is_match(&line.text) == self.keep).then_some(line)
+    }
+}
+
+#[cfg(all(test, feature = "lua"))]
+mod tests {
+    use super::*;
+    use crate::testing::TempPath;
+
+    // the line as the chain of the options leaves it
+    fn run_chain(options: &[&str], text: &str) -> Option<String> {
+        let opt = Args::parse_from(["logmatrix"].iter().chain(options));
+        let line = InputLine::new("stdin", text.to_string());
+        chain(&opt)
+            .unwrap()
+            .iter_mut()
+            .try_fold(line, |line, step| step.apply(line))
+            .map(|line| line.text)
+    }
+
+    #[test]
+    fn script_output_is_sanitized() {
+        let script = "function on_line(line) return '\\27[2J' .. line.text end";
+        let script = TempPath::with("script.lua", script);
+        let text = run_chain(&["--script", script.arg()], "hello");
+        assert_eq!(text.as_deref(), Some("?[2Jhello"));
+    }
+
+    #[test]
+    fn script_errors_are_sanitized() {
+        let script = "function on_line(line) error('\\27]52;c;eA==\\7') end";
+        let script = TempPath::with("error.lua", script);
+        let text = run_chain(&["--script", script.arg()], "hello").unwrap();
+        assert!(text.contains("?]52;c;eA==?"));
+        assert!(!text.chars().any(char::is_control));
+    }
+}
@@ -0,0 +1,293 @@
+use crate::{
+    Args, Color, Counters, SOURCE_PALETTE, SourceColor, Weight,
+    alert::AlertRule,
+    exec::ExecRule,
+    source_colors,
+    sources::{self, InputLine, LineReceiver, QueuePolicy, Severity},
+    text::{self, Notification, Redactor},
+};
+use regex::Regex;
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Arc, Mutex},
+    thread::spawn,
+};
+
+/// the lines prepared by `count` threads on their way to the matrix, which is left with
+/// composing the frames. the lines of a source always go to the same thread so they keep
+/// their order, the ones of different sources can overtake each other. the lines waiting
+/// for the threads share the room of `queue_size`, each thread hands its lines over one
+/// at a time
+pub fn spawn_pool(
+    input: LineReceiver,
+    count: usize,
+    prepare: &Prepare,
+    queue_size: Option<usize>,
+    counters: Arc<Counters>,
+) -> LineReceiver {
+    let queue = |size| sources::channel(size, QueuePolicy::Block, counters.clone());
+    let (tx, rx) = queue(queue_size.map(|_| count));
+    let mut workers = vec![];
+    for _ in 0..count {
+        let (worker_tx, worker_rx) = queue(queue_size.map(|size| size.div_ceil(count)));
+        let prepare = prepare.clone();
+        let tx = tx.clone();
+        spawn(move || {
+            for line in worker_rx {
+                if tx.send(prepare.apply(line)).is_err() {
+                    return;
+                }
+            }
+        });
+        workers.push(worker_tx);
+    }
+    spawn(move || {
+        for line in input {
+            let mut hasher = DefaultHasher::new();
+            line.source.hash(&mut hasher);
+            let worker = &workers[hasher.finish() as usize % workers.len()];
+            if worker.send(line).is_err() {
+                return;
+            }
+        }
+    });
+    rx
+}
+
+/// what was made of a line before the matrix takes it
+#[derive(Clone)]
+pub struct Prepared {
+    pub text: String,                     // sanitized, for the matrix to show
+    pub received: Option<String>,         // before the redaction, for --tee and --match-out
+    pub seq: Option<u64>,                 // of --seq-field
+    pub choices: usize,                   // columns the line picks from, by --weight
+    pub notifications: Vec<Notification>, // passed through to the terminal
+    pub matches: Matches,
+}
+
+/// the patterns of the options the line matches
+#[derive(Clone, Default)]
+pub struct Matches {
+    pub ticker: bool,
+    pub bell: bool,
+    pub fail_on: bool,
+    pub pause_on: bool,
+    #[cfg(feature = "notify")]
+    pub notify: bool,
+    pub trace: bool,
+    pub match_out: bool,   // as it was received
+    pub alerts: Vec<bool>, // by --alert rule
+    pub exec: Vec<bool>,   // by --exec-on rule
+}
+
+/// what is done to every line, by the `--workers` threads or by the matrix for the lines
+/// which did not go through them
+#[derive(Clone)]
+pub struct Prepare {
+    redactor: Redactor,
+    colorize: Colorize,
+    tab_width: usize,
+    placeholder: char,
+    passthrough_notifications: bool,
+    keep_received: bool,
+    seq_field: Option<String>,
+    weights: Vec<Weight>,
+    patterns: Patterns,
+}
+
+// the patterns of the options, matched against the redacted text
+#[derive(Clone)]
+struct Patterns {
+    ticker: Option<Regex>,
+    bell: Option<Regex>,
+    fail_on: Option<Regex>,
+    pause_on: Option<Regex>,
+    #[cfg(feature = "notify")]
+    notify: Option<Regex>,
+    trace: Option<Regex>,
+    match_out: Vec<Regex>, // matched against the text as it was received
+    alerts: Vec<AlertRule>,
+    exec: Vec<ExecRule>,
+}
+
+impl Patterns {
+    fn new(opt: &Args) -> Patterns {
+        Patterns {
+            ticker: opt.error_ticker.clone(),
+            bell: opt.bell.clone(),
+            fail_on: opt.fail_on.clone(),
+            pause_on: opt.pause_on.clone(),
+            #[cfg(feature = "notify")]
+            notify: opt.notify.clone(),
+            trace: opt.trace_line.clone(),
+            match_out: match opt.match_out {
+                Some(_) => opt.match_regex.clone(),
+                None => vec![],
+            },
+            alerts: opt.alerts.clone(),
+            exec: opt.exec_on.clone(),
+        }
+    }
+
+    fn matches(&self, received: &str, text: &str) -> Matches {
+        let matches = |pattern: &Option<Regex>| pattern.as_ref().is_some_and(|p| p.is_match(text));
+        Matches {
+            ticker: matches(&self.ticker),
+            bell: matches(&self.bell),
+            fail_on: matches(&self.fail_on),
+            pause_on: matches(&self.pause_on),
+            #[cfg(feature = "notify")]
+            notify: matches(&self.notify),
+            trace: matches(&self.trace),
+            match_out: self.match_out.iter().any(|regex| regex.is_match(received)),
+            alerts: self.alerts.iter().map(|rule| rule.matches(text)).collect(),
+            exec: self.exec.iter().map(|rule| rule.matches(text)).collect(),
+        }
+    }
+}
+
+impl Prepare {
+    /// `new_sources` when the sources name their lines as they arrive, the names take
+    /// the next color of the palette
+    pub fn new(opt: &Args, new_sources: bool) -> Prepare {
+        Prepare {
+            redactor: Redactor::new(&opt.redact, &opt.redact_builtin),
+            colorize: Colorize::new(opt, new_sources),
+            tab_width: opt.tab_width,
+            placeholder: opt.placeholder,
+            passthrough_notifications: opt.passthrough_notifications,
+            keep_received: opt.tee.is_some() || opt.match_out.is_some(),
+            seq_field: opt.seq_field.clone(),
+            weights: opt.weights.clone(),
+            patterns: Patterns::new(opt),
+        }
+    }
+
+    /// the line with what was made of it
+    pub fn apply(&self, mut line: InputLine) -> InputLine {
+        let prepared = self.prepare(&mut line);
+        line.prepared = Some(Box::new(prepared));
+        line
+    }
+
+    /// the text is redacted and the line colored in place. the control characters are kept
+    /// in the text for the alerts, the tee and the others. the notifications the matrix
+    /// passes through are left out of the sanitized one, as the matrix takes them out of
+    /// the text before it is shown
+    pub fn prepare(&self, line: &mut InputLine) -> Prepared {
+        let received = std::mem::take(&mut line.text);
+        line.text = self.redactor.redact(received.clone());
+        line.color = self.colorize.color(line);
+        let (shown, notifications) = match self.passthrough_notifications {
+            true => text::take_notifications(&line.text),
+            false => (line.text.clone(), vec![]),
+        };
+        Prepared {
+            text: text::sanitize(&shown, self.tab_width, self.placeholder),
+            seq: (self.seq_field.as_ref())
+                .and_then(|key| text::field(&line.text, key))
+                .and_then(|value| value.parse::<u64>().ok()),
+            choices: (self.weights.iter())
+                .find(|weight| weight.matches(line))
+                .map_or(1, |weight| weight.weight),
+            notifications,
+            matches: self.patterns.matches(&received, &line.text),
+            received: self.keep_received.then_some(received),
+        }
+    }
+}
+
+/// the severity colors first, then the color the source picked, then the one given to the
+/// source. none leaves the lines the color of their column
+#[derive(Clone)]
+struct Colorize {
+    // shared by the threads, the sources only known once their lines arrive get a color
+    // in the order they show up
+    colors: Arc<Mutex<Vec<SourceColor>>>,
+    new_sources: bool,
+    highlight: Color,
+}
+
+impl Colorize {
+    fn new(opt: &Args, new_sources: bool) -> Colorize {
+        Colorize {
+            colors: Arc::new(Mutex::new(source_colors(opt))),
+            new_sources,
+            highlight: opt.highlight_color,
+        }
+    }
+
+    fn color(&self, line: &InputLine) -> Option<Color> {
+        line.severity
+            .and_then(Severity::color)
+            .or(line.color)
+            .or_else(|| self.source_color(&line.source))
+    }
+
+    fn source_color(&self, source: &str) -> Option<Color> {
+        let mut colors = self.colors.lock().unwrap();
+        if let Some(mapping) = colors.iter().find(|mapping| mapping.source == source) {
+            return Some(mapping.color);
+        }
+        if !self.new_sources {
+            return None;
+        }
+        // a source seen for the first time takes the next color of the palette when they
+        // are not all known up front
+        let palette: Vec<Color> = SOURCE_PALETTE
+            .into_iter()
+            .filter(|color| *color != self.highlight)
+            .collect();
+        let color = palette[colors.len() % palette.len()];
+        colors.push(SourceColor {
+            source: source.to_string(),
+            color,
+        });
+        Some(color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_come_prepared_and_in_order() {
+        let options = [
+            "--seq-field",
+            "seq",
+            "--bell",
+            "down",
+            "--weight",
+            "app=db:3",
+        ];
+        let argv = ["logmatrix", "--source-color", "a=red"]
+            .into_iter()
+            .chain(options);
+        let opt = Args::parse_from(argv);
+        let (tx, rx) = sources::unbounded();
+        let counters = Arc::new(Counters::default());
+        let prepared = spawn_pool(rx, 3, &Prepare::new(&opt, false), Some(4), counters);
+        for seq in 0..100 {
+            let state = if seq == 7 { "down" } else { "up" };
+            let text = format!("seq={seq} app=db {state}\t");
+            tx.send(InputLine::new(["a", "b"][seq % 2], text)).unwrap();
+        }
+        drop(tx);
+        let mut last = [None, None];
+        for line in prepared {
+            let prepared = line.prepared.unwrap();
+            let seq = prepared.seq.unwrap();
+            let source = usize::from(line.source == "b");
+            assert!(last[source] < Some(seq), "{seq} after {:?}", last[source]);
+            last[source] = Some(seq);
+            assert_eq!(prepared.choices, 3);
+            assert_eq!(prepared.matches.bell, seq == 7);
+            assert!(!prepared.text.contains('\t'));
+            if line.source == "a" {
+                assert_eq!(line.color, Some(Color::Red));
+            }
+        }
+        assert_eq!(last, [Some(98), Some(99)]);
+    }
+}